@@ -10,7 +10,9 @@ use criterion::Criterion;
 
 use serde::de::Deserialize;
 
-use avvy::{ Schema, AvroDeserializer };
+use avvy::{ Schema, AvroDeserializer, DecodePlan };
+
+const RECORD: [u8; 257] = [0, 0, 0, 2, 106, 0, 186, 149, 235, 179, 11, 86, 118, 105, 97, 115, 97, 116, 45, 97, 98, 45, 118, 110, 111, 45, 112, 109, 46, 117, 116, 46, 112, 100, 102, 46, 102, 108, 45, 115, 100, 117, 45, 109, 97, 114, 107, 101, 100, 45, 99, 111, 117, 110, 116, 0, 0, 2, 22, 10, 97, 110, 45, 105, 100, 2, 49, 10, 112, 100, 102, 105, 100, 8, 49, 48, 53, 50, 16, 115, 109, 97, 99, 100, 45, 105, 100, 6, 49, 52, 55, 24, 115, 97, 116, 101, 108, 108, 105, 116, 101, 45, 105, 100, 2, 52, 34, 115, 109, 97, 99, 45, 115, 101, 114, 118, 105, 99, 101, 45, 110, 97, 109, 101, 26, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 115, 50, 16, 109, 97, 99, 45, 97, 100, 100, 114, 24, 48, 48, 97, 48, 98, 99, 56, 99, 55, 57, 55, 102, 10, 115, 116, 97, 116, 101, 14, 111, 110, 95, 108, 105, 110, 101, 14, 98, 101, 97, 109, 45, 105, 100, 10, 49, 49, 48, 52, 53, 22, 99, 97, 114, 114, 105, 101, 114, 100, 45, 105, 100, 2, 55, 12, 118, 110, 111, 45, 105, 100, 6, 120, 99, 105, 44, 115, 101, 114, 118, 105, 110, 103, 45, 115, 109, 97, 99, 45, 104, 111, 115, 116, 45, 110, 97, 109, 101, 36, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 110, 50, 45, 98, 101, 116, 97, 0, 0];
 
 pub trait InfluxDBLineProtocol<W: std::io::Write> {
     fn to_line_protocol(&self, writer: &mut W) -> Result<(),std::io::Error>;
@@ -251,7 +253,7 @@ macro_rules! ut_test {
 
                 b.iter(|| {
                     let mut deserializer = AvroDeserializer::from_slice( &visitor,&record[..]);
-                    deserializer.skip(5);
+                    deserializer.read_confluent_header().unwrap();
 
                     let _ = $i::deserialize(&mut deserializer).unwrap();
                 })
@@ -268,12 +270,35 @@ fn ut_deserializer_benchmark(c: &mut Criterion) {
     ut_test!("UTHashMap", UTHashMap, c);
 }
 
+fn ut_plan_benchmark(c: &mut Criterion) {
+    c.bench_function("UTVec with a decode plan", |b| {
+        let record : [u8; 257] = RECORD;
+        let visitor = Schema::from_str(SCHEMA_STR).unwrap();
+        let plan = DecodePlan::new::<UTVec>(&visitor).unwrap();
+
+        b.iter(|| {
+            let mut deserializer = AvroDeserializer::with_plan(&plan, &record[5..]);
+            let _ = UTVec::deserialize(&mut deserializer).unwrap();
+        })
+    });
+
+    c.bench_function("UTVec without a decode plan", |b| {
+        let record : [u8; 257] = RECORD;
+        let visitor = Schema::from_str(SCHEMA_STR).unwrap();
+
+        b.iter(|| {
+            let mut deserializer = AvroDeserializer::from_slice(&visitor, &record[5..]);
+            let _ = UTVec::deserialize(&mut deserializer).unwrap();
+        })
+    });
+}
+
 fn ut_vec_string_conversion_benchmark(c: &mut Criterion) {
     c.bench_function("serialize for String-deserialized influxdb", |b| {
         let record : [u8; 257] = [0, 0, 0, 2, 106, 0, 186, 149, 235, 179, 11, 86, 118, 105, 97, 115, 97, 116, 45, 97, 98, 45, 118, 110, 111, 45, 112, 109, 46, 117, 116, 46, 112, 100, 102, 46, 102, 108, 45, 115, 100, 117, 45, 109, 97, 114, 107, 101, 100, 45, 99, 111, 117, 110, 116, 0, 0, 2, 22, 10, 97, 110, 45, 105, 100, 2, 49, 10, 112, 100, 102, 105, 100, 8, 49, 48, 53, 50, 16, 115, 109, 97, 99, 100, 45, 105, 100, 6, 49, 52, 55, 24, 115, 97, 116, 101, 108, 108, 105, 116, 101, 45, 105, 100, 2, 52, 34, 115, 109, 97, 99, 45, 115, 101, 114, 118, 105, 99, 101, 45, 110, 97, 109, 101, 26, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 115, 50, 16, 109, 97, 99, 45, 97, 100, 100, 114, 24, 48, 48, 97, 48, 98, 99, 56, 99, 55, 57, 55, 102, 10, 115, 116, 97, 116, 101, 14, 111, 110, 95, 108, 105, 110, 101, 14, 98, 101, 97, 109, 45, 105, 100, 10, 49, 49, 48, 52, 53, 22, 99, 97, 114, 114, 105, 101, 114, 100, 45, 105, 100, 2, 55, 12, 118, 110, 111, 45, 105, 100, 6, 120, 99, 105, 44, 115, 101, 114, 118, 105, 110, 103, 45, 115, 109, 97, 99, 45, 104, 111, 115, 116, 45, 110, 97, 109, 101, 36, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 110, 50, 45, 98, 101, 116, 97, 0, 0];
         let visitor = Schema::from_str(SCHEMA_STR).unwrap();
-        let mut deserializer = AvroDeserializer{buf: &record[..], schema: &visitor, current_field_index: None };
-        deserializer.skip(5);
+        let mut deserializer = AvroDeserializer::from_slice(&visitor, &record[..]);
+        deserializer.read_confluent_header().unwrap();
         let utvec = UTVecString::deserialize(&mut deserializer).unwrap();
         let record_count = 10000;
         let data : Vec<UTVecString> = (1..record_count).map(|_| (utvec).clone() ).collect();
@@ -293,8 +318,8 @@ fn ut_vec_raw_string_conversion_benchmark(c: &mut Criterion) {
     c.bench_function("serialize String::from_utf8_lossy influxdb", |b| {
         let record : [u8; 257] = [0, 0, 0, 2, 106, 0, 186, 149, 235, 179, 11, 86, 118, 105, 97, 115, 97, 116, 45, 97, 98, 45, 118, 110, 111, 45, 112, 109, 46, 117, 116, 46, 112, 100, 102, 46, 102, 108, 45, 115, 100, 117, 45, 109, 97, 114, 107, 101, 100, 45, 99, 111, 117, 110, 116, 0, 0, 2, 22, 10, 97, 110, 45, 105, 100, 2, 49, 10, 112, 100, 102, 105, 100, 8, 49, 48, 53, 50, 16, 115, 109, 97, 99, 100, 45, 105, 100, 6, 49, 52, 55, 24, 115, 97, 116, 101, 108, 108, 105, 116, 101, 45, 105, 100, 2, 52, 34, 115, 109, 97, 99, 45, 115, 101, 114, 118, 105, 99, 101, 45, 110, 97, 109, 101, 26, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 115, 50, 16, 109, 97, 99, 45, 97, 100, 100, 114, 24, 48, 48, 97, 48, 98, 99, 56, 99, 55, 57, 55, 102, 10, 115, 116, 97, 116, 101, 14, 111, 110, 95, 108, 105, 110, 101, 14, 98, 101, 97, 109, 45, 105, 100, 10, 49, 49, 48, 52, 53, 22, 99, 97, 114, 114, 105, 101, 114, 100, 45, 105, 100, 2, 55, 12, 118, 110, 111, 45, 105, 100, 6, 120, 99, 105, 44, 115, 101, 114, 118, 105, 110, 103, 45, 115, 109, 97, 99, 45, 104, 111, 115, 116, 45, 110, 97, 109, 101, 36, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 110, 50, 45, 98, 101, 116, 97, 0, 0];
         let visitor = Schema::from_str(SCHEMA_STR).unwrap();
-        let mut deserializer = AvroDeserializer{buf: &record[..], schema: &visitor, current_field_index: None };
-        deserializer.skip(5);
+        let mut deserializer = AvroDeserializer::from_slice(&visitor, &record[..]);
+        deserializer.read_confluent_header().unwrap();
         let utvec = UTVec::deserialize(&mut deserializer).unwrap();
         let record_count = 10000;
         let data : Vec<UTVec> = (1..record_count).map(|_| (utvec).clone() ).collect();
@@ -315,8 +340,8 @@ fn ut_vec_raw_buf_conversion_benchmark(c: &mut Criterion) {
     c.bench_function("serialize for std::str::from_utf8_unchecked influxdb", |b| {
         let record : [u8; 257] = [0, 0, 0, 2, 106, 0, 186, 149, 235, 179, 11, 86, 118, 105, 97, 115, 97, 116, 45, 97, 98, 45, 118, 110, 111, 45, 112, 109, 46, 117, 116, 46, 112, 100, 102, 46, 102, 108, 45, 115, 100, 117, 45, 109, 97, 114, 107, 101, 100, 45, 99, 111, 117, 110, 116, 0, 0, 2, 22, 10, 97, 110, 45, 105, 100, 2, 49, 10, 112, 100, 102, 105, 100, 8, 49, 48, 53, 50, 16, 115, 109, 97, 99, 100, 45, 105, 100, 6, 49, 52, 55, 24, 115, 97, 116, 101, 108, 108, 105, 116, 101, 45, 105, 100, 2, 52, 34, 115, 109, 97, 99, 45, 115, 101, 114, 118, 105, 99, 101, 45, 110, 97, 109, 101, 26, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 115, 50, 16, 109, 97, 99, 45, 97, 100, 100, 114, 24, 48, 48, 97, 48, 98, 99, 56, 99, 55, 57, 55, 102, 10, 115, 116, 97, 116, 101, 14, 111, 110, 95, 108, 105, 110, 101, 14, 98, 101, 97, 109, 45, 105, 100, 10, 49, 49, 48, 52, 53, 22, 99, 97, 114, 114, 105, 101, 114, 100, 45, 105, 100, 2, 55, 12, 118, 110, 111, 45, 105, 100, 6, 120, 99, 105, 44, 115, 101, 114, 118, 105, 110, 103, 45, 115, 109, 97, 99, 45, 104, 111, 115, 116, 45, 110, 97, 109, 101, 36, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 110, 50, 45, 98, 101, 116, 97, 0, 0];
         let visitor = Schema::from_str(SCHEMA_STR).unwrap();
-        let mut deserializer = AvroDeserializer{buf: &record[..], schema: &visitor, current_field_index: None };
-        deserializer.skip(5);
+        let mut deserializer = AvroDeserializer::from_slice(&visitor, &record[..]);
+        deserializer.read_confluent_header().unwrap();
         let utvec = UTVec::deserialize(&mut deserializer).unwrap();
         let record_count = 10000;
         let data : Vec<UTVec> = (1..record_count).map(|_| (utvec).clone() ).collect();
@@ -336,8 +361,8 @@ fn ut_vec_raw_buf_forloop2_conversion_benchmark(c: &mut Criterion) {
     c.bench_function("serialize for std::str::from_utf8_unchecked (borrowed for loop) influxdb", |b| {
         let record : [u8; 257] = [0, 0, 0, 2, 106, 0, 186, 149, 235, 179, 11, 86, 118, 105, 97, 115, 97, 116, 45, 97, 98, 45, 118, 110, 111, 45, 112, 109, 46, 117, 116, 46, 112, 100, 102, 46, 102, 108, 45, 115, 100, 117, 45, 109, 97, 114, 107, 101, 100, 45, 99, 111, 117, 110, 116, 0, 0, 2, 22, 10, 97, 110, 45, 105, 100, 2, 49, 10, 112, 100, 102, 105, 100, 8, 49, 48, 53, 50, 16, 115, 109, 97, 99, 100, 45, 105, 100, 6, 49, 52, 55, 24, 115, 97, 116, 101, 108, 108, 105, 116, 101, 45, 105, 100, 2, 52, 34, 115, 109, 97, 99, 45, 115, 101, 114, 118, 105, 99, 101, 45, 110, 97, 109, 101, 26, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 115, 50, 16, 109, 97, 99, 45, 97, 100, 100, 114, 24, 48, 48, 97, 48, 98, 99, 56, 99, 55, 57, 55, 102, 10, 115, 116, 97, 116, 101, 14, 111, 110, 95, 108, 105, 110, 101, 14, 98, 101, 97, 109, 45, 105, 100, 10, 49, 49, 48, 52, 53, 22, 99, 97, 114, 114, 105, 101, 114, 100, 45, 105, 100, 2, 55, 12, 118, 110, 111, 45, 105, 100, 6, 120, 99, 105, 44, 115, 101, 114, 118, 105, 110, 103, 45, 115, 109, 97, 99, 45, 104, 111, 115, 116, 45, 110, 97, 109, 101, 36, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 110, 50, 45, 98, 101, 116, 97, 0, 0];
         let visitor = Schema::from_str(SCHEMA_STR).unwrap();
-        let mut deserializer = AvroDeserializer{buf: &record[..], schema: &visitor, current_field_index: None };
-        deserializer.skip(5);
+        let mut deserializer = AvroDeserializer::from_slice(&visitor, &record[..]);
+        deserializer.read_confluent_header().unwrap();
         let utvec = UTVec::deserialize(&mut deserializer).unwrap();
         let record_count = 10000;
         let data : Vec<UTVec> = (1..record_count).map(|_| (utvec).clone() ).collect();
@@ -357,8 +382,8 @@ fn ut_vec_write_buf_noloop_benchmark(c: &mut Criterion) {
     c.bench_function("serialize for std::str::from_utf8_unchecked (one record serialization) influxdb", |b| {
         let record : [u8; 257] = [0, 0, 0, 2, 106, 0, 186, 149, 235, 179, 11, 86, 118, 105, 97, 115, 97, 116, 45, 97, 98, 45, 118, 110, 111, 45, 112, 109, 46, 117, 116, 46, 112, 100, 102, 46, 102, 108, 45, 115, 100, 117, 45, 109, 97, 114, 107, 101, 100, 45, 99, 111, 117, 110, 116, 0, 0, 2, 22, 10, 97, 110, 45, 105, 100, 2, 49, 10, 112, 100, 102, 105, 100, 8, 49, 48, 53, 50, 16, 115, 109, 97, 99, 100, 45, 105, 100, 6, 49, 52, 55, 24, 115, 97, 116, 101, 108, 108, 105, 116, 101, 45, 105, 100, 2, 52, 34, 115, 109, 97, 99, 45, 115, 101, 114, 118, 105, 99, 101, 45, 110, 97, 109, 101, 26, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 115, 50, 16, 109, 97, 99, 45, 97, 100, 100, 114, 24, 48, 48, 97, 48, 98, 99, 56, 99, 55, 57, 55, 102, 10, 115, 116, 97, 116, 101, 14, 111, 110, 95, 108, 105, 110, 101, 14, 98, 101, 97, 109, 45, 105, 100, 10, 49, 49, 48, 52, 53, 22, 99, 97, 114, 114, 105, 101, 114, 100, 45, 105, 100, 2, 55, 12, 118, 110, 111, 45, 105, 100, 6, 120, 99, 105, 44, 115, 101, 114, 118, 105, 110, 103, 45, 115, 109, 97, 99, 45, 104, 111, 115, 116, 45, 110, 97, 109, 101, 36, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 110, 50, 45, 98, 101, 116, 97, 0, 0];
         let visitor = Schema::from_str(SCHEMA_STR).unwrap();
-        let mut deserializer = AvroDeserializer{buf: &record[..], schema: &visitor, current_field_index: None };
-        deserializer.skip(5);
+        let mut deserializer = AvroDeserializer::from_slice(&visitor, &record[..]);
+        deserializer.read_confluent_header().unwrap();
         let utvec = UTVec::deserialize(&mut deserializer).unwrap();
         let record_count = 10;
 
@@ -376,8 +401,8 @@ fn ut_vec_write_buf_noloop_nodebug_benchmark(c: &mut Criterion) {
     c.bench_function("serialize for std::str::from_utf8_unchecked (one record serialization, no debug print) influxdb", |b| {
         let record : [u8; 257] = [0, 0, 0, 2, 106, 0, 186, 149, 235, 179, 11, 86, 118, 105, 97, 115, 97, 116, 45, 97, 98, 45, 118, 110, 111, 45, 112, 109, 46, 117, 116, 46, 112, 100, 102, 46, 102, 108, 45, 115, 100, 117, 45, 109, 97, 114, 107, 101, 100, 45, 99, 111, 117, 110, 116, 0, 0, 2, 22, 10, 97, 110, 45, 105, 100, 2, 49, 10, 112, 100, 102, 105, 100, 8, 49, 48, 53, 50, 16, 115, 109, 97, 99, 100, 45, 105, 100, 6, 49, 52, 55, 24, 115, 97, 116, 101, 108, 108, 105, 116, 101, 45, 105, 100, 2, 52, 34, 115, 109, 97, 99, 45, 115, 101, 114, 118, 105, 99, 101, 45, 110, 97, 109, 101, 26, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 115, 50, 16, 109, 97, 99, 45, 97, 100, 100, 114, 24, 48, 48, 97, 48, 98, 99, 56, 99, 55, 57, 55, 102, 10, 115, 116, 97, 116, 101, 14, 111, 110, 95, 108, 105, 110, 101, 14, 98, 101, 97, 109, 45, 105, 100, 10, 49, 49, 48, 52, 53, 22, 99, 97, 114, 114, 105, 101, 114, 100, 45, 105, 100, 2, 55, 12, 118, 110, 111, 45, 105, 100, 6, 120, 99, 105, 44, 115, 101, 114, 118, 105, 110, 103, 45, 115, 109, 97, 99, 45, 104, 111, 115, 116, 45, 110, 97, 109, 101, 36, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 110, 50, 45, 98, 101, 116, 97, 0, 0];
         let visitor = Schema::from_str(SCHEMA_STR).unwrap();
-        let mut deserializer = AvroDeserializer{buf: &record[..], schema: &visitor, current_field_index: None };
-        deserializer.skip(5);
+        let mut deserializer = AvroDeserializer::from_slice(&visitor, &record[..]);
+        deserializer.read_confluent_header().unwrap();
         let utvec = UTVec::deserialize(&mut deserializer).unwrap();
         let record_count = 10;
 
@@ -433,5 +458,5 @@ fn buferator_benchmark(c: &mut Criterion) {
 }
 
 
-criterion_group!(benches, buferator_benchmark, /* ut_deserializer_benchmark, ut_vec_string_conversion_benchmark, ut_vec_raw_string_conversion_benchmark, ut_vec_raw_buf_conversion_benchmark, ut_vec_raw_buf_forloop2_conversion_benchmark, ut_vec_write_buf_noloop_benchmark, ut_vec_write_buf_noloop_nodebug_benchmark*/);
+criterion_group!(benches, buferator_benchmark, ut_plan_benchmark, /* ut_deserializer_benchmark, ut_vec_string_conversion_benchmark, ut_vec_raw_string_conversion_benchmark, ut_vec_raw_buf_conversion_benchmark, ut_vec_raw_buf_forloop2_conversion_benchmark, ut_vec_write_buf_noloop_benchmark, ut_vec_write_buf_noloop_nodebug_benchmark*/);
 criterion_main!(benches);
@@ -0,0 +1,355 @@
+use std::io::Read;
+use std::sync::Arc;
+
+use arrow_array::types::*;
+use arrow_array::{ ArrayRef, ArrowPrimitiveType, BinaryArray, BooleanArray, FixedSizeBinaryArray, ListArray, MapArray,
+                   NullArray, PrimitiveArray, RecordBatch, RecordBatchOptions, StringArray, StructArray, UnionArray };
+use arrow_buffer::{ BooleanBuffer, Buffer, NullBuffer, OffsetBuffer, ScalarBuffer };
+use arrow_schema::{ ArrowError, DataType, Field, FieldRef, Fields, SchemaRef, TimeUnit, UnionFields, UnionMode };
+
+use super::super::*;
+use super::super::container::{ Block, ContainerReader };
+use super::{ BinaryColumn, Bitmap, Column, ColumnBatch };
+
+/// How many rows go in a record batch unless asked otherwise
+pub const DEFAULT_BATCH_SIZE: usize = 8192;
+
+/// The Arrow schema of record batches decoded from `schema`. Records become
+/// a batch's columns, `["null", T]` unions nullable `T`s, other unions dense
+/// unions, maps and arrays Arrow maps and lists, and `int`s or `long`s with
+/// a logical type the matching date, time or timestamp.
+pub fn arrow_schema(schema: &Schema) -> arrow_schema::Schema {
+    arrow_schema::Schema::new(arrow_fields(schema))
+}
+
+/// The Arrow type of a whole record, for records nested in other Arrow data
+pub fn arrow_struct(schema: &Schema) -> DataType {
+    DataType::Struct(arrow_fields(schema))
+}
+
+fn arrow_fields(schema: &Schema) -> Fields {
+    schema.fields.iter().map(arrow_field).collect()
+}
+
+fn arrow_field(field: &SchemaField) -> Field {
+    let nullable = field.types.contains(&SchemaFieldType::Primitive(Primitive::Null));
+    Field::new(&field.name[..], field_data_type(field), nullable)
+}
+
+// Follows `Column::for_field` in telling nullables from unions
+fn field_data_type(field: &SchemaField) -> DataType {
+    match nullable_branch(&field.types[..]) {
+        Some(branch) => branch_data_type(field, branch),
+        None if field.types.len() == 1 => branch_data_type(field, 0),
+        None => DataType::Union(union_fields(field), UnionMode::Dense),
+    }
+}
+
+// The index of the branch that isn't null
+fn nullable_branch(types: &[SchemaFieldType]) -> Option<usize> {
+    match types {
+        [SchemaFieldType::Primitive(Primitive::Null), _] => Some(1),
+        [_, SchemaFieldType::Primitive(Primitive::Null)] => Some(0),
+        _ => None,
+    }
+}
+
+fn union_fields(field: &SchemaField) -> UnionFields {
+    let fields = field.types.iter().enumerate()
+        .map(|(branch, field_type)| Field::new(field_type.type_name(), branch_data_type(field, branch), true));
+    UnionFields::new(0..field.types.len() as i8, fields)
+}
+
+fn branch_data_type(field: &SchemaField, branch: usize) -> DataType {
+    data_type(&field.types[branch], field.logical_type(branch))
+}
+
+fn data_type(field_type: &SchemaFieldType, logical: Option<LogicalType>) -> DataType {
+    match *field_type {
+        SchemaFieldType::Primitive(ref primitive) => primitive_data_type(primitive, logical),
+        SchemaFieldType::Complex(Complex::Fixed { size, .. }) => DataType::FixedSizeBinary(size as i32),
+        SchemaFieldType::Complex(Complex::Array { ref items }) => DataType::List(list_field(items)),
+        SchemaFieldType::Complex(Complex::Map { ref values }) => DataType::Map(entries_field(values), false),
+    }
+}
+
+fn primitive_data_type(primitive: &Primitive, logical: Option<LogicalType>) -> DataType {
+    let utc = || Some("+00:00".into());
+    match (primitive, logical) {
+        (&Primitive::Null, _) => DataType::Null,
+        (&Primitive::Boolean, _) => DataType::Boolean,
+        (&Primitive::Int, Some(LogicalType::Date)) => DataType::Date32,
+        (&Primitive::Int, Some(LogicalType::TimeMillis)) => DataType::Time32(TimeUnit::Millisecond),
+        (&Primitive::Int, _) => DataType::Int32,
+        (&Primitive::Long, Some(LogicalType::TimeMicros)) => DataType::Time64(TimeUnit::Microsecond),
+        (&Primitive::Long, Some(LogicalType::TimestampMillis)) => DataType::Timestamp(TimeUnit::Millisecond, utc()),
+        (&Primitive::Long, Some(LogicalType::TimestampMicros)) => DataType::Timestamp(TimeUnit::Microsecond, utc()),
+        (&Primitive::Long, Some(LogicalType::TimestampNanos)) => DataType::Timestamp(TimeUnit::Nanosecond, utc()),
+        (&Primitive::Long, Some(LogicalType::LocalTimestampMillis)) => DataType::Timestamp(TimeUnit::Millisecond, None),
+        (&Primitive::Long, Some(LogicalType::LocalTimestampMicros)) => DataType::Timestamp(TimeUnit::Microsecond, None),
+        (&Primitive::Long, Some(LogicalType::LocalTimestampNanos)) => DataType::Timestamp(TimeUnit::Nanosecond, None),
+        (&Primitive::Long, _) => DataType::Int64,
+        (&Primitive::Float, _) => DataType::Float32,
+        (&Primitive::Double, _) => DataType::Float64,
+        (&Primitive::Bytes, _) => DataType::Binary,
+        (&Primitive::String, _) => DataType::Utf8,
+        (&Primitive::Uint64T, _) | (&Primitive::Int64T, _) => DataType::FixedSizeBinary(8),
+    }
+}
+
+// Elements are named by type, so they're primitives without logical types
+fn element_data_type(name: &str) -> DataType {
+    Primitive::from_name(name).map_or(DataType::Null, |primitive| primitive_data_type(&primitive, None))
+}
+
+fn list_field(items: &str) -> FieldRef {
+    Arc::new(Field::new_list_field(element_data_type(items), items == "null"))
+}
+
+fn entries_field(values: &str) -> FieldRef {
+    let entries = Fields::from(vec![
+        Field::new("keys", DataType::Utf8, false),
+        Field::new("values", element_data_type(values), values == "null"),
+    ]);
+    Arc::new(Field::new("entries", DataType::Struct(entries), false))
+}
+
+impl ColumnBatch {
+    /// Copies the batch into an Arrow record batch with `arrow_schema`'s
+    /// layout. The columns keep their buffers for the next batch.
+    pub fn to_record_batch(&self) -> Result<RecordBatch, AvroError> {
+        let schema = Arc::new(arrow_schema(self.schema()));
+        self.record_batch(schema)
+    }
+
+    fn record_batch(&self, arrow_schema: SchemaRef) -> Result<RecordBatch, AvroError> {
+        let arrays = self.schema().fields.iter().zip(self.columns())
+            .map(|(field, column)| field_array(column, field)
+                .map_err(|err| AvroError{ reason: format!("{}: {}", field.name, err) }))
+            .collect::<Result<Vec<_>, _>>()?;
+        let options = RecordBatchOptions::new().with_row_count(Some(self.len()));
+        RecordBatch::try_new_with_options(arrow_schema, arrays, &options).map_err(|err| AvroError{ reason: err.to_string() })
+    }
+}
+
+fn field_array(column: &Column, field: &SchemaField) -> Result<ArrayRef, ArrowError> {
+    match *column {
+        Column::Nullable { ref validity, ref values, .. } => {
+            let branch = nullable_branch(&field.types[..]).expect("nullable columns come from two branch unions with a null");
+            array(values, &branch_data_type(field, branch), Some(null_buffer(validity)))
+        },
+        Column::Union(ref union) => {
+            let children = union.children.iter().enumerate()
+                .map(|(branch, child)| array(child, &branch_data_type(field, branch), None))
+                .collect::<Result<Vec<_>, _>>()?;
+            let union = UnionArray::try_new(
+                union_fields(field),
+                ScalarBuffer::from(union.type_ids.clone()),
+                Some(ScalarBuffer::from(union.offsets.clone())),
+                children,
+            )?;
+            Ok(Arc::new(union))
+        },
+        ref column => array(column, &field_data_type(field), None),
+    }
+}
+
+// One branch's values, as `data_type` lays them out
+fn array(column: &Column, data_type: &DataType, nulls: Option<NullBuffer>) -> Result<ArrayRef, ArrowError> {
+    let array: ArrayRef = match (column, data_type) {
+        (Column::Null(len), _) => Arc::new(NullArray::new(*len)),
+        (Column::Boolean(values), _) => Arc::new(BooleanArray::new(BooleanBuffer::from(&values[..]), nulls)),
+        (Column::Int(values), DataType::Date32) => primitive::<Date32Type>(values, data_type, nulls),
+        (Column::Int(values), DataType::Time32(_)) => primitive::<Time32MillisecondType>(values, data_type, nulls),
+        (Column::Int(values), _) => primitive::<Int32Type>(values, data_type, nulls),
+        (Column::Long(values), DataType::Time64(_)) => primitive::<Time64MicrosecondType>(values, data_type, nulls),
+        (Column::Long(values), DataType::Timestamp(TimeUnit::Millisecond, _)) => primitive::<TimestampMillisecondType>(values, data_type, nulls),
+        (Column::Long(values), DataType::Timestamp(TimeUnit::Microsecond, _)) => primitive::<TimestampMicrosecondType>(values, data_type, nulls),
+        (Column::Long(values), DataType::Timestamp(_, _)) => primitive::<TimestampNanosecondType>(values, data_type, nulls),
+        (Column::Long(values), _) => primitive::<Int64Type>(values, data_type, nulls),
+        (Column::Float(values), _) => primitive::<Float32Type>(values, data_type, nulls),
+        (Column::Double(values), _) => primitive::<Float64Type>(values, data_type, nulls),
+        (Column::Bytes(values), _) => Arc::new(BinaryArray::try_new(offsets(&values.offsets), data(values), nulls)?),
+        (Column::String(values), _) => Arc::new(StringArray::try_new(offsets(&values.offsets), data(values), nulls)?),
+        (Column::Fixed { size, data }, _) => Arc::new(FixedSizeBinaryArray::try_new(*size as i32, Buffer::from_slice_ref(data), nulls)?),
+        (Column::Array { offsets: list, items }, DataType::List(field)) => {
+            let items = array(items, field.data_type(), None)?;
+            Arc::new(ListArray::try_new(field.clone(), offsets(list), items, nulls)?)
+        },
+        (Column::Map { offsets: map, keys, values }, DataType::Map(field, _)) => {
+            let fields = match field.data_type() {
+                DataType::Struct(fields) => fields.clone(),
+                other => return Err(ArrowError::SchemaError(format!("map entries can't be {}", other))),
+            };
+            let keys: ArrayRef = Arc::new(StringArray::try_new(offsets(&keys.offsets), data(keys), None)?);
+            let values = array(values, fields[1].data_type(), None)?;
+            let entries = StructArray::try_new(fields, vec![keys, values], None)?;
+            Arc::new(MapArray::try_new(field.clone(), offsets(map), entries, nulls, false)?)
+        },
+        (column, data_type) => return Err(ArrowError::SchemaError(format!("can't lay out {:?} as {}", column, data_type))),
+    };
+    Ok(array)
+}
+
+fn primitive<T: ArrowPrimitiveType>(values: &[T::Native], data_type: &DataType, nulls: Option<NullBuffer>) -> ArrayRef {
+    Arc::new(PrimitiveArray::<T>::new(ScalarBuffer::from(values.to_vec()), nulls).with_data_type(data_type.clone()))
+}
+
+fn offsets(offsets: &[i32]) -> OffsetBuffer<i32> {
+    OffsetBuffer::new(ScalarBuffer::from(offsets.to_vec()))
+}
+
+fn data(values: &BinaryColumn) -> Buffer {
+    Buffer::from_slice_ref(&values.data)
+}
+
+fn null_buffer(validity: &Bitmap) -> NullBuffer {
+    NullBuffer::new(BooleanBuffer::new(Buffer::from_slice_ref(validity.as_bytes()), 0, validity.len()))
+}
+
+/// Decodes back to back records into Arrow record batches of up to
+/// `batch_size` rows, reusing one set of column builders throughout
+pub struct ArrowDecoder {
+    batch: ColumnBatch,
+    arrow_schema: SchemaRef,
+    batch_size: usize,
+}
+
+impl ArrowDecoder {
+    pub fn new(schema: &Schema) -> Result<Self, AvroError> {
+        Ok(ArrowDecoder {
+            batch: ColumnBatch::new(schema)?,
+            arrow_schema: Arc::new(arrow_schema(schema)),
+            batch_size: DEFAULT_BATCH_SIZE,
+        })
+    }
+
+    pub fn batch_size(mut self, rows: usize) -> Self {
+        self.batch_size = rows.max(1);
+        self
+    }
+
+    pub fn schema(&self) -> &Schema {
+        self.batch.schema()
+    }
+
+    pub fn arrow_schema(&self) -> SchemaRef {
+        self.arrow_schema.clone()
+    }
+
+    /// Rows decoded since the last flush
+    pub fn len(&self) -> usize {
+        self.batch.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batch.is_empty()
+    }
+
+    /// Whether the next batch is ready to flush
+    pub fn is_full(&self) -> bool {
+        self.batch.len() >= self.batch_size
+    }
+
+    /// Decodes records from `buf` until it runs out or the batch fills up,
+    /// returning how many bytes were used. Records can't be split across
+    /// calls, pass whole ones.
+    pub fn decode(&mut self, buf: &[u8]) -> Result<usize, AvroError> {
+        let room = self.batch_size.saturating_sub(self.batch.len());
+        self.batch.extend_from_slice(buf, room)
+    }
+
+    /// Decodes a datum that holds exactly one record. If it holds anything
+    /// else, nothing is kept. Doesn't check whether the batch is full.
+    pub fn decode_datum(&mut self, datum: &[u8]) -> Result<(), AvroError> {
+        let len = self.batch.len();
+        let used = self.batch.extend_from_slice(datum, 1)?;
+        if self.batch.len() == len {
+            return Err(AvroError{ reason: "datum holds no record".into() })
+        }
+        if used < datum.len() {
+            self.batch.truncate(len);
+            return Err(AvroError{ reason: format!("datum has {} bytes left after its record", datum.len() - used) })
+        }
+        Ok(())
+    }
+
+    /// The rows decoded so far as a record batch, `None` if there are none
+    pub fn flush(&mut self) -> Result<Option<RecordBatch>, AvroError> {
+        if self.batch.is_empty() {
+            return Ok(None)
+        }
+        let batch = self.batch.record_batch(self.arrow_schema.clone())?;
+        self.batch.clear();
+        Ok(Some(batch))
+    }
+}
+
+/// Record batches from a container file, filled across block boundaries
+pub struct ArrowBatches<R> {
+    reader: ContainerReader<R>,
+    decoder: ArrowDecoder,
+    block: Option<Block>,
+    pos: usize,
+    remaining: usize,
+    failed: bool,
+}
+
+impl<R: Read> ContainerReader<R> {
+    /// Reads the rest of the file as record batches of up to `batch_size` rows
+    pub fn arrow_batches(self, batch_size: usize) -> Result<ArrowBatches<R>, AvroError> {
+        let decoder = ArrowDecoder::new(self.schema())?.batch_size(batch_size);
+        Ok(ArrowBatches { reader: self, decoder, block: None, pos: 0, remaining: 0, failed: false })
+    }
+}
+
+impl<R: Read> ArrowBatches<R> {
+    pub fn reader(&self) -> &ContainerReader<R> {
+        &self.reader
+    }
+
+    pub fn arrow_schema(&self) -> SchemaRef {
+        self.decoder.arrow_schema()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>, AvroError> {
+        while !self.decoder.is_full() {
+            if self.remaining == 0 {
+                match self.reader.next_block()? {
+                    Some(block) => {
+                        self.pos = 0;
+                        self.remaining = block.count;
+                        self.block = Some(block);
+                        continue
+                    },
+                    None => break,
+                }
+            }
+
+            let block = self.block.as_ref().expect("a block with records left");
+            let room = self.decoder.batch_size - self.decoder.len();
+            let before = self.decoder.len();
+            self.pos += self.decoder.batch.extend_from_slice(&block.data[self.pos..], room.min(self.remaining))
+                .map_err(|err| AvroError{ reason: format!("block at offset {}: {}", block.offset, err.reason) })?;
+            let decoded = self.decoder.len() - before;
+            if decoded == 0 || (decoded == self.remaining && self.pos < block.data.len()) {
+                return Err(AvroError{ reason: format!("block at offset {} doesn't hold the {} records it says", block.offset, block.count) })
+            }
+            self.remaining -= decoded;
+        }
+        self.decoder.flush()
+    }
+}
+
+impl<R: Read> Iterator for ArrowBatches<R> {
+    type Item = Result<RecordBatch, AvroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None
+        }
+        let next = self.next_batch();
+        self.failed = next.is_err();
+        next.transpose()
+    }
+}
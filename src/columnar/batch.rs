@@ -0,0 +1,110 @@
+use super::super::*;
+use super::super::container::Block;
+use super::Column;
+
+/// Records decoded field by field into columns, one per schema field. The
+/// builders are laid out from the schema once and keep their allocations
+/// across `clear`, so decoding batch after batch into the same `ColumnBatch`
+/// allocates nothing per record once the buffers have grown.
+#[derive(Debug, Clone)]
+pub struct ColumnBatch {
+    schema: Schema,
+    columns: Vec<Column>,
+    len: usize,
+}
+
+impl ColumnBatch {
+    pub fn new(schema: &Schema) -> Result<Self, AvroError> {
+        let columns = schema.fields.iter()
+            .map(|field| Column::for_field(&field.types[..]).map_err(|err| AvroError{ reason: format!("{}: {}", field.name, err.reason) }))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ColumnBatch { schema: schema.clone(), columns, len: 0 })
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// How many records the batch holds
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns[..]
+    }
+
+    pub fn column(&self, index: usize) -> Option<&Column> {
+        self.columns.get(index)
+    }
+
+    pub fn column_named(&self, name: &str) -> Option<&Column> {
+        self.schema.fields.iter()
+            .position(|field| field.name == name)
+            .map(|index| &self.columns[index])
+    }
+
+    /// Decodes one record off the front of `de`'s buffer. A record that
+    /// fails partway is taken back out of every column.
+    pub fn push(&mut self, de: &mut AvroDeserializer) -> Result<(), AvroError> {
+        push_record(&mut self.columns, &self.schema, self.len, de)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Decodes up to `max` back to back records from `buf`, returning how
+    /// many bytes they took. Stops early at the end of the buffer.
+    pub fn extend_from_slice(&mut self, buf: &[u8], max: usize) -> Result<usize, AvroError> {
+        let mut de = AvroDeserializer::from_slice(&self.schema, buf);
+        let mut pushed = 0;
+        while pushed < max && !de.buf.is_empty() {
+            push_record(&mut self.columns, &self.schema, self.len, &mut de)?;
+            self.len += 1;
+            pushed += 1;
+        }
+        Ok(buf.len() - de.buf.len())
+    }
+
+    /// Decodes all of a container block's records
+    pub fn extend_from_block(&mut self, block: &Block) -> Result<(), AvroError> {
+        let len = self.len;
+        let used = self.extend_from_slice(&block.data[..], block.count)
+            .map_err(|err| AvroError{ reason: format!("block at offset {}: {}", block.offset, err.reason) })?;
+        if self.len - len < block.count || used < block.data.len() {
+            self.truncate(len);
+            return Err(AvroError{ reason: format!("block at offset {} doesn't hold the {} records it says", block.offset, block.count) })
+        }
+        Ok(())
+    }
+
+    /// Drops records past `len`
+    pub fn truncate(&mut self, len: usize) {
+        for column in &mut self.columns {
+            column.truncate(len);
+        }
+        self.len = self.len.min(len);
+    }
+
+    /// Empties the batch for the next run of records, keeping the columns' buffers
+    pub fn clear(&mut self) {
+        self.truncate(0)
+    }
+}
+
+// Apart from `ColumnBatch::push` so the schema can be lent to a deserializer
+// while the columns are written
+fn push_record(columns: &mut [Column], schema: &Schema, len: usize, de: &mut AvroDeserializer) -> Result<(), AvroError> {
+    for index in 0..columns.len() {
+        if let Err(err) = columns[index].push(de) {
+            for column in columns.iter_mut() {
+                column.truncate(len);
+            }
+            return Err(AvroError{ reason: format!("record {}, {}: {}", len, schema.fields[index].name, err.reason) })
+        }
+    }
+    Ok(())
+}
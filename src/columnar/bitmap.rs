@@ -0,0 +1,64 @@
+/// A packed validity bitmap, least significant bit first as Arrow lays them out
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bitmap {
+    bits: Vec<u8>,
+    len: usize,
+}
+
+impl Bitmap {
+    pub fn new() -> Self {
+        Bitmap::default()
+    }
+
+    pub fn with_capacity(len: usize) -> Self {
+        Bitmap { bits: Vec::with_capacity(len.div_ceil(8)), len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, set: bool) {
+        if self.len.is_multiple_of(8) {
+            self.bits.push(0);
+        }
+        if set {
+            self.bits[self.len / 8] |= 1 << (self.len % 8);
+        }
+        self.len += 1;
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        index < self.len && self.bits[index / 8] & (1 << (index % 8)) != 0
+    }
+
+    /// How many bits are unset
+    pub fn unset(&self) -> usize {
+        self.len - self.bits.iter().map(|byte| byte.count_ones() as usize).sum::<usize>()
+    }
+
+    /// The packed bytes, the last one padded with unset bits
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bits[..]
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return
+        }
+        self.bits.truncate(len.div_ceil(8));
+        if !len.is_multiple_of(8) {
+            self.bits[len / 8] &= (1 << (len % 8)) - 1;
+        }
+        self.len = len;
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.clear();
+        self.len = 0;
+    }
+}
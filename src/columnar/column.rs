@@ -0,0 +1,308 @@
+use std::convert::TryFrom;
+use std::str;
+
+use super::super::*;
+use super::Bitmap;
+
+/// Values of one field, laid out the way Arrow lays out its arrays: fixed
+/// width values back to back, variable width ones as offsets into a single
+/// data buffer, nested ones as offsets into a child column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    /// Only the count is kept, there is nothing else to a null
+    Null(usize),
+    Boolean(Vec<bool>),
+    Int(Vec<i32>),
+    Long(Vec<i64>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+    Bytes(BinaryColumn),
+    /// Like `Bytes`, but checked to be utf-8 as it's decoded
+    String(BinaryColumn),
+    /// `size` bytes per value, also used for `uint64_t` and `int64_t`
+    Fixed { size: usize, data: Vec<u8> },
+    /// A union of null and one other type. The values column has a slot for
+    /// every row, nulls holding a placeholder.
+    Nullable { null: usize, validity: Bitmap, values: Box<Column> },
+    Union(UnionColumn),
+    Array { offsets: Vec<i32>, items: Box<Column> },
+    Map { offsets: Vec<i32>, keys: BinaryColumn, values: Box<Column> },
+}
+
+/// Variable width values, value `i` spanning `data[offsets[i]..offsets[i + 1]]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryColumn {
+    pub offsets: Vec<i32>,
+    pub data: Vec<u8>,
+}
+
+/// A dense union. Row `i` took branch `type_ids[i]` and its value is at
+/// `offsets[i]` in that branch's column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnionColumn {
+    pub type_ids: Vec<i8>,
+    pub offsets: Vec<i32>,
+    pub children: Vec<Column>,
+}
+
+impl Column {
+    /// An empty builder for a field of these types
+    pub fn for_field(types: &[SchemaFieldType]) -> Result<Self, AvroError> {
+        let null = types.iter().position(|field_type| *field_type == SchemaFieldType::Primitive(Primitive::Null));
+        match (types.len(), null) {
+            (0, _) => Err(AvroError{ reason: "a field needs at least one type".into() }),
+            (1, _) => Column::for_type(&types[0]),
+            (2, Some(null)) => Ok(Column::Nullable {
+                null,
+                validity: Bitmap::new(),
+                values: Box::new(Column::for_type(&types[1 - null])?),
+            }),
+            (branches, _) if branches > i8::MAX as usize + 1 => Err(AvroError{ reason: format!("unions of {} branches can't be laid out in columns", branches) }),
+            _ => Ok(Column::Union(UnionColumn {
+                type_ids: Vec::new(),
+                offsets: Vec::new(),
+                children: types.iter().map(Column::for_type).collect::<Result<Vec<_>, _>>()?,
+            })),
+        }
+    }
+
+    pub fn for_type(field_type: &SchemaFieldType) -> Result<Self, AvroError> {
+        let column = match *field_type {
+            SchemaFieldType::Primitive(ref primitive) => Column::for_primitive(primitive),
+            SchemaFieldType::Complex(Complex::Fixed { size, .. }) => Column::Fixed { size, data: Vec::new() },
+            SchemaFieldType::Complex(Complex::Array { ref items }) => Column::Array {
+                offsets: vec![0],
+                items: Box::new(Column::for_primitive(&Primitive::element(items)?)),
+            },
+            SchemaFieldType::Complex(Complex::Map { ref values }) => Column::Map {
+                offsets: vec![0],
+                keys: BinaryColumn::new(),
+                values: Box::new(Column::for_primitive(&Primitive::element(values)?)),
+            },
+        };
+        Ok(column)
+    }
+
+    fn for_primitive(primitive: &Primitive) -> Self {
+        match *primitive {
+            Primitive::Null => Column::Null(0),
+            Primitive::Boolean => Column::Boolean(Vec::new()),
+            Primitive::Int => Column::Int(Vec::new()),
+            Primitive::Long => Column::Long(Vec::new()),
+            Primitive::Float => Column::Float(Vec::new()),
+            Primitive::Double => Column::Double(Vec::new()),
+            Primitive::Bytes => Column::Bytes(BinaryColumn::new()),
+            Primitive::String => Column::String(BinaryColumn::new()),
+            Primitive::Uint64T | Primitive::Int64T => Column::Fixed { size: 8, data: Vec::new() },
+        }
+    }
+
+    /// How many rows the column holds
+    pub fn len(&self) -> usize {
+        match *self {
+            Column::Null(len) => len,
+            Column::Boolean(ref values) => values.len(),
+            Column::Int(ref values) => values.len(),
+            Column::Long(ref values) => values.len(),
+            Column::Float(ref values) => values.len(),
+            Column::Double(ref values) => values.len(),
+            Column::Bytes(ref values) | Column::String(ref values) => values.len(),
+            Column::Fixed { size, ref data } => data.len().checked_div(size).unwrap_or(0),
+            Column::Nullable { ref validity, .. } => validity.len(),
+            Column::Union(ref union) => union.type_ids.len(),
+            Column::Array { ref offsets, .. } | Column::Map { ref offsets, .. } => offsets.len() - 1,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Decodes one value onto the end of the column
+    pub fn push(&mut self, de: &mut AvroDeserializer) -> Result<(), AvroError> {
+        match *self {
+            Column::Null(ref mut len) => *len += 1,
+            Column::Boolean(ref mut values) => values.push(de.take(1)?[0] != 0),
+            Column::Int(ref mut values) => values.push(de.visit_int()?),
+            Column::Long(ref mut values) => values.push(de.visit_long()?),
+            Column::Float(ref mut values) => values.push(de.visit_f32()?),
+            Column::Double(ref mut values) => values.push(de.visit_f64()?),
+            Column::Bytes(ref mut values) => values.push(de.visit_borrow_bytes()?)?,
+            Column::String(ref mut values) => {
+                let bytes = de.visit_borrow_bytes()?;
+                str::from_utf8(bytes).map_err(|err| AvroError{ reason: format!("string isn't utf-8: {}", err) })?;
+                values.push(bytes)?;
+            },
+            Column::Fixed { size, ref mut data } => data.extend_from_slice(de.take(size)?),
+            Column::Nullable { null, ref mut validity, ref mut values } => {
+                let branch = de.visit_long()?;
+                if branch == null as i64 {
+                    values.push_default()?;
+                    validity.push(false);
+                } else if branch == 1 - null as i64 {
+                    values.push(de)?;
+                    validity.push(true);
+                } else {
+                    return Err(AvroError{ reason: format!("union branch {} is out of scope, max is 2", branch) })
+                }
+            },
+            Column::Union(ref mut union) => {
+                let branch = de.visit_long()?;
+                let child = match union.children.get_mut(branch as usize) {
+                    Some(child) if branch >= 0 => child,
+                    _ => return Err(AvroError{ reason: format!("union branch {} is out of scope, max is {}", branch, union.children.len()) }),
+                };
+                let offset = offset(child.len())?;
+                child.push(de)?;
+                union.type_ids.push(branch as i8);
+                union.offsets.push(offset);
+            },
+            Column::Array { ref mut offsets, ref mut items } => {
+                let mut remaining = de.visit_block_len()?;
+                while remaining > 0 {
+                    match **items {
+                        // nulls take no bytes, a block of them is only its count
+                        Column::Null(ref mut len) => *len = len.saturating_add(remaining),
+                        ref mut items => for _ in 0..remaining {
+                            items.push(de)?;
+                        },
+                    }
+                    remaining = de.visit_block_len()?;
+                }
+                offsets.push(offset(items.len())?);
+            },
+            Column::Map { ref mut offsets, ref mut keys, ref mut values } => {
+                let mut remaining = de.visit_block_len()?;
+                while remaining > 0 {
+                    for _ in 0..remaining {
+                        let key = de.visit_borrow_bytes()?;
+                        str::from_utf8(key).map_err(|err| AvroError{ reason: format!("map key isn't utf-8: {}", err) })?;
+                        keys.push(key)?;
+                        values.push(de)?;
+                    }
+                    remaining = de.visit_block_len()?;
+                }
+                offsets.push(offset(keys.len())?);
+            },
+        }
+        Ok(())
+    }
+
+    /// Fills a slot with a zero value, under a null in the column above
+    fn push_default(&mut self) -> Result<(), AvroError> {
+        match *self {
+            Column::Null(ref mut len) => *len += 1,
+            Column::Boolean(ref mut values) => values.push(false),
+            Column::Int(ref mut values) => values.push(0),
+            Column::Long(ref mut values) => values.push(0),
+            Column::Float(ref mut values) => values.push(0.0),
+            Column::Double(ref mut values) => values.push(0.0),
+            Column::Bytes(ref mut values) | Column::String(ref mut values) => values.push(&[])?,
+            Column::Fixed { size, ref mut data } => { let len = data.len(); data.resize(len + size, 0) },
+            Column::Nullable { ref mut validity, ref mut values, .. } => {
+                values.push_default()?;
+                validity.push(false);
+            },
+            Column::Union(ref mut union) => {
+                let offset = offset(union.children[0].len())?;
+                union.children[0].push_default()?;
+                union.type_ids.push(0);
+                union.offsets.push(offset);
+            },
+            Column::Array { ref mut offsets, ref items } => offsets.push(offset(items.len())?),
+            Column::Map { ref mut offsets, ref keys, .. } => offsets.push(offset(keys.len())?),
+        }
+        Ok(())
+    }
+
+    /// Drops rows past `len`, along with whatever they hold in child columns.
+    /// Child values a failed `push` left behind go too.
+    pub fn truncate(&mut self, len: usize) {
+        match *self {
+            Column::Null(ref mut count) => *count = len.min(*count),
+            Column::Boolean(ref mut values) => values.truncate(len),
+            Column::Int(ref mut values) => values.truncate(len),
+            Column::Long(ref mut values) => values.truncate(len),
+            Column::Float(ref mut values) => values.truncate(len),
+            Column::Double(ref mut values) => values.truncate(len),
+            Column::Bytes(ref mut values) | Column::String(ref mut values) => values.truncate(len),
+            Column::Fixed { size, ref mut data } => data.truncate(len * size),
+            Column::Nullable { ref mut validity, ref mut values, .. } => {
+                validity.truncate(len);
+                values.truncate(len);
+            },
+            Column::Union(ref mut union) => {
+                union.type_ids.truncate(len);
+                union.offsets.truncate(len);
+                for (branch, child) in union.children.iter_mut().enumerate() {
+                    let kept = union.type_ids.iter().filter(|id| **id as usize == branch).count();
+                    child.truncate(kept);
+                }
+            },
+            Column::Array { ref mut offsets, ref mut items } => {
+                offsets.truncate(len + 1);
+                let len = offsets.len() - 1;
+                items.truncate(offsets[len] as usize);
+            },
+            Column::Map { ref mut offsets, ref mut keys, ref mut values } => {
+                offsets.truncate(len + 1);
+                let len = offsets.len() - 1;
+                keys.truncate(offsets[len] as usize);
+                values.truncate(offsets[len] as usize);
+            },
+        }
+    }
+
+    /// Empties the column, keeping what it has allocated
+    pub fn clear(&mut self) {
+        self.truncate(0)
+    }
+}
+
+impl BinaryColumn {
+    pub fn new() -> Self {
+        BinaryColumn { offsets: vec![0], data: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fails once the data would outgrow Arrow's 32 bit offsets
+    pub fn push(&mut self, value: &[u8]) -> Result<(), AvroError> {
+        let end = offset(self.data.len() + value.len())?;
+        self.data.extend_from_slice(value);
+        self.offsets.push(end);
+        Ok(())
+    }
+
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        if index >= self.len() {
+            return None
+        }
+        Some(&self.data[self.offsets[index] as usize..self.offsets[index + 1] as usize])
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len() {
+            return
+        }
+        self.offsets.truncate(len + 1);
+        self.data.truncate(self.offsets[len] as usize);
+    }
+}
+
+impl Default for BinaryColumn {
+    fn default() -> Self {
+        BinaryColumn::new()
+    }
+}
+
+// Arrow offsets are 32 bit; past that the batch has to be flushed sooner
+fn offset(len: usize) -> Result<i32, AvroError> {
+    i32::try_from(len).map_err(|_| AvroError{ reason: format!("column outgrew 32 bit offsets at {}, flush smaller batches", len) })
+}
@@ -0,0 +1,18 @@
+mod bitmap;
+pub use self::bitmap::*;
+
+mod column;
+pub use self::column::*;
+
+mod batch;
+pub use self::batch::*;
+
+#[cfg(feature = "arrow")]
+mod arrow;
+#[cfg(feature = "arrow")]
+pub use self::arrow::*;
+
+#[cfg(feature = "parquet")]
+mod parquet;
+#[cfg(feature = "parquet")]
+pub use self::parquet::*;
@@ -0,0 +1,294 @@
+use std::io::{ self, Read, Write };
+use std::sync::Arc;
+
+use arrow_array::{ new_null_array, Array, ArrayRef, RecordBatch, RecordBatchOptions, StructArray, UInt32Array, UnionArray };
+use arrow_cast::cast;
+use arrow_schema::{ ArrowError, DataType, Field, Fields, SchemaRef, UnionFields };
+use arrow_select::interleave::interleave;
+use arrow_select::take::take;
+use byteorder::{ BigEndian, ReadBytesExt };
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{ Compression, GzipLevel, ZstdLevel };
+use parquet::file::properties::WriterProperties;
+
+use super::super::*;
+use super::super::container::{ Block, Codec, ContainerReader };
+use super::super::message::ConfluentDecoder;
+use super::{ arrow_schema, ArrowDecoder };
+
+/// Rows per row group unless asked otherwise, parquet's own default
+pub const DEFAULT_ROW_GROUP_SIZE: usize = 1024 * 1024;
+
+/// Writes records out as a parquet file. The parquet schema is the Arrow
+/// one `arrow_schema` derives, except for unions, which parquet doesn't
+/// have: unions of numbers become one column of the widest of them (`long`
+/// for only `int`s and `long`s, `double` once a `float` or `double` is in
+/// the mix), and other unions a struct with a nullable member per branch.
+/// A union's `null` branch makes the column null.
+pub struct ParquetWriter<W: Write + Send> {
+    decoder: ArrowDecoder,
+    parquet_schema: SchemaRef,
+    out: Option<W>,
+    writer: Option<ArrowWriter<W>>,
+    row_group_size: usize,
+    codec: Codec,
+    /// Rows already handed to the parquet writer
+    flushed: u64,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    /// Starts a new file. Nothing is written until the first row group is full.
+    pub fn new(schema: &Schema, writer: W) -> Result<Self, AvroError> {
+        Ok(ParquetWriter {
+            decoder: ArrowDecoder::new(schema)?,
+            parquet_schema: Arc::new(parquet_schema(schema)),
+            out: Some(writer),
+            writer: None,
+            row_group_size: DEFAULT_ROW_GROUP_SIZE,
+            codec: Codec::Null,
+            flushed: 0,
+        })
+    }
+
+    pub fn row_group_size(mut self, rows: usize) -> Self {
+        self.row_group_size = rows.max(1);
+        self
+    }
+
+    /// Compresses pages with `codec`, which has to be compiled in. Deflate
+    /// is written as parquet's gzip, bzip2 and xz aren't available.
+    pub fn compression(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    pub fn schema(&self) -> &Schema {
+        self.decoder.schema()
+    }
+
+    /// The Arrow schema of the file, unions already mapped
+    pub fn parquet_schema(&self) -> SchemaRef {
+        self.parquet_schema.clone()
+    }
+
+    /// How many records have been written
+    pub fn rows(&self) -> u64 {
+        self.flushed + self.decoder.len() as u64
+    }
+
+    /// Writes back to back records encoded with the writer's schema
+    pub fn write_records(&mut self, buf: &[u8]) -> Result<(), AvroError> {
+        let mut pos = 0;
+        while pos < buf.len() {
+            pos += self.decoder.decode(&buf[pos..])?;
+            if self.decoder.is_full() {
+                self.flush_batch()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_block(&mut self, block: &Block) -> Result<(), AvroError> {
+        let before = self.rows();
+        self.write_records(&block.data[..])
+            .map_err(|err| AvroError{ reason: format!("block at offset {}: {}", block.offset, err.reason) })?;
+        if self.rows() - before != block.count as u64 {
+            return Err(AvroError{ reason: format!("block at offset {} doesn't hold the {} records it says", block.offset, block.count) })
+        }
+        Ok(())
+    }
+
+    /// Writes the rest of a container file, which has to be written with
+    /// the same schema. Returns how many records it held.
+    pub fn write_container<R: Read>(&mut self, mut reader: ContainerReader<R>) -> Result<u64, AvroError> {
+        if reader.schema().fingerprint() != self.schema().fingerprint() {
+            return Err(AvroError{ reason: format!("container holds {}, not {}", reader.schema().full_name(), self.schema().full_name()) })
+        }
+        let before = self.rows();
+        while let Some(block) = reader.next_block()? {
+            self.write_block(&block)?;
+        }
+        Ok(self.rows() - before)
+    }
+
+    /// Writes a Confluent framed message, resolved to the writer's schema
+    /// if it was written with another. `decoder`'s reader schema has to be
+    /// the writer's schema. Nothing is written unless the message holds
+    /// exactly one record.
+    pub fn write_message(&mut self, decoder: &ConfluentDecoder, message: &[u8]) -> Result<(), AvroError> {
+        let datum = decoder.reader_datum(message)?;
+        self.decoder.decode_datum(&datum[..])?;
+        if self.decoder.is_full() {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    /// Writes every message of a dump of Confluent framed messages, each
+    /// preceded by its length as 4 big-endian bytes, the framing
+    /// `DatumCodec` and `ConfluentCodec` use. Returns how many there were.
+    pub fn write_message_dump<R: Read>(&mut self, decoder: &ConfluentDecoder, mut dump: R) -> Result<u64, AvroError> {
+        if decoder.reader_schema().fingerprint() != self.schema().fingerprint() {
+            return Err(AvroError{ reason: format!("decoder reads {}, not {}", decoder.reader_schema().full_name(), self.schema().full_name()) })
+        }
+        let mut message = Vec::new();
+        let mut count = 0;
+        loop {
+            let len = match dump.read_u32::<BigEndian>() {
+                Ok(len) => len as usize,
+                Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(count),
+                Err(err) => return Err(err.into()),
+            };
+            message.resize(len, 0);
+            dump.read_exact(&mut message[..])
+                .map_err(|err| AvroError{ reason: format!("message {}: {}", count, err) })?;
+            self.write_message(decoder, &message[..])
+                .map_err(|err| AvroError{ reason: format!("message {}: {}", count, err.reason) })?;
+            count += 1;
+        }
+    }
+
+    /// Writes what's buffered and the file footer, handing back the writer
+    pub fn finish(mut self) -> Result<W, AvroError> {
+        self.flush_batch()?;
+        self.open()?;
+        let writer = self.writer.take().expect("an open writer");
+        writer.into_inner().map_err(parquet_error)
+    }
+
+    fn flush_batch(&mut self) -> Result<(), AvroError> {
+        let batch = match self.decoder.flush()? {
+            Some(batch) => parquet_batch(&batch, self.parquet_schema.clone())?,
+            None => return Ok(()),
+        };
+        self.flushed += batch.num_rows() as u64;
+        self.open()?;
+        self.writer.as_mut().expect("an open writer").write(&batch).map_err(parquet_error)
+    }
+
+    // The writer is made on first use so the builder methods can still
+    // change its properties. If making it fails, the output is gone with it.
+    fn open(&mut self) -> Result<(), AvroError> {
+        if self.writer.is_some() {
+            return Ok(())
+        }
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(self.row_group_size)
+            .set_compression(compression(self.codec)?)
+            .build();
+        let out = self.out.take()
+            .ok_or_else(|| AvroError{ reason: "parquet writer failed to start earlier and can't be used".into() })?;
+        let writer = ArrowWriter::try_new(out, self.parquet_schema.clone(), Some(properties)).map_err(parquet_error)?;
+        self.writer = Some(writer);
+        Ok(())
+    }
+}
+
+fn compression(codec: Codec) -> Result<Compression, AvroError> {
+    codec.ensure_available()?;
+    match codec {
+        Codec::Null => Ok(Compression::UNCOMPRESSED),
+        Codec::Deflate => Ok(Compression::GZIP(GzipLevel::default())),
+        Codec::Snappy => Ok(Compression::SNAPPY),
+        Codec::Zstandard => Ok(Compression::ZSTD(ZstdLevel::default())),
+        Codec::Bzip2 | Codec::Xz => Err(AvroError{ reason: format!("parquet files can't be compressed with {}", codec.name()) }),
+    }
+}
+
+fn parquet_error(err: parquet::errors::ParquetError) -> AvroError {
+    AvroError{ reason: format!("parquet: {}", err) }
+}
+
+/// The Arrow schema a `ParquetWriter` writes records from `schema` as
+pub fn parquet_schema(schema: &Schema) -> arrow_schema::Schema {
+    let fields = arrow_schema(schema).fields().iter()
+        .map(|field| match *field.data_type() {
+            DataType::Union(ref branches, _) => Field::new(field.name(), union_type(branches), true),
+            _ => (**field).clone(),
+        })
+        .collect::<Vec<_>>();
+    arrow_schema::Schema::new(fields)
+}
+
+fn union_type(branches: &UnionFields) -> DataType {
+    let mut widest = DataType::Null;
+    for (_, field) in branches.iter() {
+        widest = match (widest, field.data_type()) {
+            (widest, &DataType::Null) => widest,
+            (DataType::Null, &DataType::Int32) | (DataType::Null, &DataType::Int64) |
+            (DataType::Int64, &DataType::Int32) | (DataType::Int64, &DataType::Int64) => DataType::Int64,
+            (DataType::Null, number) | (DataType::Int64, number) | (DataType::Float64, number) if is_number(number) => DataType::Float64,
+            _ => return DataType::Struct(members(branches)),
+        };
+    }
+    widest
+}
+
+fn is_number(data_type: &DataType) -> bool {
+    matches!(*data_type, DataType::Int32 | DataType::Int64 | DataType::Float32 | DataType::Float64)
+}
+
+fn members(branches: &UnionFields) -> Fields {
+    branches.iter()
+        .filter(|&(_, field)| *field.data_type() != DataType::Null)
+        .map(|(_, field)| Field::new(field.name(), field.data_type().clone(), true))
+        .collect()
+}
+
+fn parquet_batch(batch: &RecordBatch, parquet_schema: SchemaRef) -> Result<RecordBatch, AvroError> {
+    let columns = batch.columns().iter().zip(parquet_schema.fields().iter())
+        .map(|(column, field)| match column.as_any().downcast_ref::<UnionArray>() {
+            Some(union) => flatten_union(union, field.data_type())
+                .map_err(|err| AvroError{ reason: format!("{}: {}", field.name(), err) }),
+            None => Ok(column.clone()),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let options = RecordBatchOptions::new().with_row_count(Some(batch.num_rows()));
+    RecordBatch::try_new_with_options(parquet_schema, columns, &options).map_err(|err| AvroError{ reason: err.to_string() })
+}
+
+fn flatten_union(union: &UnionArray, data_type: &DataType) -> Result<ArrayRef, ArrowError> {
+    let branches = match *union.data_type() {
+        DataType::Union(ref branches, _) => branches.clone(),
+        ref other => return Err(ArrowError::SchemaError(format!("{} isn't a union", other))),
+    };
+
+    if let DataType::Struct(ref members) = *data_type {
+        // one column per branch, null where a row took another branch
+        let mut arrays = Vec::with_capacity(members.len());
+        for (id, field) in branches.iter() {
+            if *field.data_type() == DataType::Null {
+                continue
+            }
+            let rows = (0..union.len())
+                .map(|row| if union.type_id(row) == id { Some(union.value_offset(row) as u32) } else { None })
+                .collect::<UInt32Array>();
+            arrays.push(take(union.child(id), &rows, None)?);
+        }
+        return Ok(Arc::new(StructArray::try_new(members.clone(), arrays, None)?))
+    }
+
+    // numbers all cast to the widest, with nulls from a one row null array
+    let mut values = Vec::with_capacity(branches.len() + 1);
+    let mut sources = Vec::with_capacity(branches.len());
+    for (id, field) in branches.iter() {
+        if *field.data_type() == DataType::Null {
+            sources.push(None);
+        } else {
+            sources.push(Some(values.len()));
+            values.push(cast(union.child(id), data_type)?);
+        }
+        debug_assert_eq!(sources.len() - 1, id as usize);
+    }
+    let null = values.len();
+    values.push(new_null_array(data_type, 1));
+
+    let indices = (0..union.len())
+        .map(|row| match sources[union.type_id(row) as usize] {
+            Some(source) => (source, union.value_offset(row)),
+            None => (null, 0),
+        })
+        .collect::<Vec<_>>();
+    let values = values.iter().map(|array| array.as_ref()).collect::<Vec<&dyn Array>>();
+    interleave(&values[..], &indices[..])
+}
@@ -0,0 +1,158 @@
+use super::super::*;
+
+#[cfg(feature = "deflate")]
+use flate2;
+#[cfg(feature = "snappy")]
+use snap;
+#[cfg(feature = "snappy")]
+use crc32fast;
+#[cfg(feature = "snappy")]
+use byteorder::{ BigEndian, ByteOrder, WriteBytesExt };
+#[cfg(feature = "zstandard")]
+use zstd;
+#[cfg(feature = "bzip2")]
+use bzip2;
+#[cfg(feature = "xz")]
+use xz2;
+
+#[cfg(any(feature = "deflate", feature = "bzip2", feature = "xz"))]
+use std::io::{ Read, Write };
+
+/// The `avro.codec` a container file's blocks are compressed with. Every
+/// codec but `null` needs the cargo feature named after its `avro.codec`
+/// value; `deflate` and `snappy` are on by default.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Codec {
+    Null,
+    Deflate,
+    Snappy,
+    Zstandard,
+    Bzip2,
+    Xz,
+}
+
+impl Codec {
+    /// Looks up an `avro.codec` value, failing for unknown codecs and for
+    /// codecs this build of avvy wasn't compiled with.
+    pub fn from_name(name: &str) -> Result<Codec, AvroError> {
+        let codec = match name {
+            "null" => Codec::Null,
+            "deflate" => Codec::Deflate,
+            "snappy" => Codec::Snappy,
+            "zstandard" => Codec::Zstandard,
+            "bzip2" => Codec::Bzip2,
+            "xz" => Codec::Xz,
+            _ => return Err(AvroError{ reason: format!("unknown codec {}", name) }),
+        };
+        codec.ensure_available()?;
+        Ok(codec)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Codec::Null => "null",
+            Codec::Deflate => "deflate",
+            Codec::Snappy => "snappy",
+            Codec::Zstandard => "zstandard",
+            Codec::Bzip2 => "bzip2",
+            Codec::Xz => "xz",
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        match *self {
+            Codec::Null => true,
+            Codec::Deflate => cfg!(feature = "deflate"),
+            Codec::Snappy => cfg!(feature = "snappy"),
+            Codec::Zstandard => cfg!(feature = "zstandard"),
+            Codec::Bzip2 => cfg!(feature = "bzip2"),
+            Codec::Xz => cfg!(feature = "xz"),
+        }
+    }
+
+    pub fn ensure_available(&self) -> Result<(), AvroError> {
+        if self.is_available() {
+            Ok(())
+        } else {
+            Err(AvroError{ reason: format!("codec {} wasn't compiled in, enable avvy's `{}` feature", self.name(), self.name()) })
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, AvroError> {
+        self.ensure_available()?;
+        match *self {
+            Codec::Null => Ok(data.to_owned()),
+            #[cfg(feature = "deflate")]
+            Codec::Deflate => {
+                let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            },
+            #[cfg(feature = "snappy")]
+            Codec::Snappy => {
+                let mut compressed = snap::raw::Encoder::new().compress_vec(data)
+                    .map_err(|err| AvroError{ reason: format!("snappy: {}", err) })?;
+                compressed.write_u32::<BigEndian>(crc32fast::hash(data))?;
+                Ok(compressed)
+            },
+            #[cfg(feature = "zstandard")]
+            Codec::Zstandard => Ok(zstd::stream::encode_all(data, 0)?),
+            #[cfg(feature = "bzip2")]
+            Codec::Bzip2 => {
+                let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            },
+            #[cfg(feature = "xz")]
+            Codec::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            },
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, AvroError> {
+        self.ensure_available()?;
+        match *self {
+            Codec::Null => Ok(data.to_owned()),
+            #[cfg(feature = "deflate")]
+            Codec::Deflate => {
+                let mut decompressed = Vec::new();
+                flate2::read::DeflateDecoder::new(data).read_to_end(&mut decompressed)?;
+                Ok(decompressed)
+            },
+            #[cfg(feature = "snappy")]
+            Codec::Snappy => {
+                if data.len() < 4 {
+                    return Err(AvroError{ reason: "snappy block is too short for its crc".into() })
+                }
+                let (compressed, crc) = data.split_at(data.len() - 4);
+                let decompressed = snap::raw::Decoder::new().decompress_vec(compressed)
+                    .map_err(|err| AvroError{ reason: format!("snappy: {}", err) })?;
+                if crc32fast::hash(&decompressed[..]) != BigEndian::read_u32(crc) {
+                    return Err(AvroError{ reason: "snappy block failed its crc check".into() })
+                }
+                Ok(decompressed)
+            },
+            #[cfg(feature = "zstandard")]
+            Codec::Zstandard => Ok(zstd::stream::decode_all(data)?),
+            #[cfg(feature = "bzip2")]
+            Codec::Bzip2 => {
+                let mut decompressed = Vec::new();
+                bzip2::read::BzDecoder::new(data).read_to_end(&mut decompressed)?;
+                Ok(decompressed)
+            },
+            #[cfg(feature = "xz")]
+            Codec::Xz => {
+                let mut decompressed = Vec::new();
+                xz2::read::XzDecoder::new(data).read_to_end(&mut decompressed)?;
+                Ok(decompressed)
+            },
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+    }
+}
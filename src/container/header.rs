@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::io::{ self, Read };
+
+use super::super::*;
+
+use integer_encoding::VarInt;
+
+pub const MAGIC: [u8; 4] = [b'O', b'b', b'j', 1];
+pub const SYNC_SIZE: usize = 16;
+
+/// The header of an object container file: magic, metadata map, sync marker
+pub struct Header {
+    pub schema: Schema,
+    pub codec: String,
+    pub metadata: HashMap<String, Vec<u8>>,
+    pub sync: [u8; SYNC_SIZE],
+}
+
+impl Header {
+    /// Reads a header off the start of a file, returning it with its length in bytes
+    pub fn read<R: Read>(reader: &mut R) -> Result<(Header, u64), AvroError> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(AvroError{ reason: format!("not an avro container file, magic was {:?}", magic) })
+        }
+        let mut len = magic.len() as u64;
+
+        let mut metadata = HashMap::new();
+        loop {
+            let (mut count, size) = read_long(reader)?;
+            len += size as u64;
+            if count == 0 {
+                break
+            }
+            if count < 0 {
+                count = -count;
+                len += read_long(reader)?.1 as u64;
+            }
+            for _ in 0..count {
+                let (key, key_size) = read_bytes(reader)?;
+                let (value, value_size) = read_bytes(reader)?;
+                len += (key_size + value_size) as u64;
+                let key = String::from_utf8(key)
+                    .map_err(|err| AvroError{ reason: format!("metadata key isn't utf-8: {}", err) })?;
+                metadata.insert(key, value);
+            }
+        }
+
+        let mut sync = [0u8; SYNC_SIZE];
+        reader.read_exact(&mut sync)?;
+        len += SYNC_SIZE as u64;
+
+        let schema = match metadata.get("avro.schema") {
+            Some(schema) => {
+                let schema = std::str::from_utf8(schema)
+                    .map_err(|err| AvroError{ reason: format!("avro.schema isn't utf-8: {}", err) })?;
+                Schema::from_str(schema)
+                    .map_err(|err| AvroError{ reason: format!("avro.schema doesn't parse: {}", err) })?
+            },
+            None => return Err(AvroError{ reason: "container file has no avro.schema".into() }),
+        };
+        let codec = match metadata.get("avro.codec") {
+            Some(codec) => String::from_utf8_lossy(codec).into_owned(),
+            None => "null".into(),
+        };
+
+        info!("read container header, codec {}, {} bytes", codec, len);
+        Ok((Header { schema, codec, metadata, sync }, len))
+    }
+}
+
+/// Reads a zigzag varint, returning it with its length in bytes
+pub(crate) fn read_long<R: Read>(reader: &mut R) -> io::Result<(i64, usize)> {
+    let mut buf = [0u8; 10];
+    for size in 1..buf.len() + 1 {
+        reader.read_exact(&mut buf[size - 1..size])?;
+        if buf[size - 1] & 0x80 == 0 {
+            let (val, _) = i64::decode_var(&buf[..size]);
+            return Ok((val, size))
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "unterminated varint"))
+}
+
+/// Reads length-prefixed bytes, returning them with their length in bytes including the prefix
+pub(crate) fn read_bytes<R: Read>(reader: &mut R) -> io::Result<(Vec<u8>, usize)> {
+    let (len, size) = read_long(reader)?;
+    if len < 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("negative length {}", len)))
+    }
+    // read through `take` so a corrupt length can't size the buffer
+    let mut buf = Vec::new();
+    reader.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len as u64 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("{} of {} bytes left", buf.len(), len)))
+    }
+    Ok((buf, size + len as usize))
+}
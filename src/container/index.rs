@@ -0,0 +1,169 @@
+use std::fs::File;
+use std::io::{ self, BufReader, BufWriter, Read, Seek, SeekFrom, Write };
+use std::path::{ Path, PathBuf };
+
+use super::super::*;
+use super::header::{ Header, SYNC_SIZE, read_long };
+
+const INDEX_MAGIC: [u8; 4] = *b"Aidx";
+
+/// Where one block of a container file starts and which records it holds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexEntry {
+    pub offset: u64,
+    /// The number of the block's first record, counting from 0
+    pub first_record: u64,
+    pub count: usize,
+}
+
+/// The block offsets and record counts of a container file, for jumping
+/// straight to a record without reading everything ahead of it.
+///
+/// An index can be kept next to its file as a sidecar. The sidecar records
+/// the file's sync marker and length so a stale one gets rebuilt, which
+/// means appending to the file invalidates it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockIndex {
+    sync: [u8; SYNC_SIZE],
+    len: u64,
+    entries: Vec<IndexEntry>,
+}
+
+impl BlockIndex {
+    /// Scans a file's block headers, seeking over the block data
+    pub fn build<R: Read + Seek>(file: &mut R) -> Result<Self, AvroError> {
+        file.seek(SeekFrom::Start(0))?;
+        let (header, mut offset) = Header::read(file)?;
+        let len = file.seek(SeekFrom::End(0))?;
+        file.seek(SeekFrom::Start(offset))?;
+
+        let mut entries = Vec::new();
+        let mut first_record = 0;
+        while offset < len {
+            let (count, count_size) = read_long(file)?;
+            let (size, size_size) = read_long(file)?;
+            if count < 0 || size < 0 {
+                return Err(AvroError{ reason: format!("block at offset {} has count {} and size {}", offset, count, size) })
+            }
+
+            file.seek(SeekFrom::Current(size))?;
+            let mut sync = [0u8; SYNC_SIZE];
+            file.read_exact(&mut sync)?;
+            if sync != header.sync {
+                return Err(AvroError{ reason: format!("block at offset {}: sync marker mismatch after block", offset) })
+            }
+
+            entries.push(IndexEntry { offset, first_record, count: count as usize });
+            first_record += count as u64;
+            offset += (count_size + size_size) as u64 + size as u64 + SYNC_SIZE as u64;
+        }
+
+        info!("indexed {} blocks, {} records", entries.len(), first_record);
+        Ok(BlockIndex { sync: header.sync, len, entries })
+    }
+
+    /// Loads the index from `path`'s sidecar, or builds it and writes the
+    /// sidecar when there isn't an up to date one. A sidecar that can't be
+    /// written is logged and left out.
+    pub fn load_or_build<P: AsRef<Path>>(path: P) -> Result<Self, AvroError> {
+        let path = path.as_ref();
+        let sidecar = BlockIndex::sidecar_path(path);
+        let mut file = BufReader::new(File::open(path)?);
+
+        if let Ok(index_file) = File::open(&sidecar) {
+            match BlockIndex::read(&mut BufReader::new(index_file)) {
+                Ok(index) => {
+                    let (header, _) = Header::read(&mut file)?;
+                    if index.sync == header.sync && index.len == file.seek(SeekFrom::End(0))? {
+                        return Ok(index)
+                    }
+                    info!("{} is stale, rebuilding", sidecar.display());
+                },
+                Err(err) => info!("{} is unreadable, rebuilding: {}", sidecar.display(), err),
+            }
+        }
+
+        let index = BlockIndex::build(&mut file)?;
+        // the index is still good without its sidecar, e.g. next to a read-only file
+        let written = File::create(&sidecar).map_err(AvroError::from).and_then(|out| {
+            let mut out = BufWriter::new(out);
+            index.write(&mut out)?;
+            out.flush()?;
+            Ok(())
+        });
+        if let Err(err) = written {
+            warn!("couldn't write {}: {}", sidecar.display(), err);
+        }
+        Ok(index)
+    }
+
+    /// `data.avro` keeps its index in `data.avro.idx`
+    pub fn sidecar_path(path: &Path) -> PathBuf {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(".idx");
+        PathBuf::from(sidecar)
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries[..]
+    }
+
+    /// The number of records in the file
+    pub fn records(&self) -> u64 {
+        self.entries.last().map_or(0, |entry| entry.first_record + entry.count as u64)
+    }
+
+    /// The block holding record number `record`
+    pub fn find(&self, record: u64) -> Option<&IndexEntry> {
+        let pos = match self.entries.binary_search_by_key(&record, |entry| entry.first_record) {
+            Ok(pos) => pos,
+            Err(0) => return None,
+            Err(pos) => pos - 1,
+        };
+        // empty blocks share their first record with the block after them
+        self.entries[pos..].iter().find(|entry| record < entry.first_record + entry.count as u64)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), AvroError> {
+        let mut buf = INDEX_MAGIC.to_vec();
+        buf.extend_from_slice(&self.sync);
+        write_long(self.len as i64, &mut buf);
+        write_long(self.entries.len() as i64, &mut buf);
+        for entry in &self.entries {
+            write_long(entry.offset as i64, &mut buf);
+            write_long(entry.count as i64, &mut buf);
+        }
+        writer.write_all(&buf[..])?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, AvroError> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != INDEX_MAGIC {
+            return Err(AvroError{ reason: format!("not a block index, magic was {:?}", magic) })
+        }
+        let mut sync = [0u8; SYNC_SIZE];
+        reader.read_exact(&mut sync)?;
+        let len = read_unsigned(reader)?;
+
+        let blocks = read_unsigned(reader)?;
+        let mut entries = Vec::new();
+        let mut first_record = 0;
+        for _ in 0..blocks {
+            let offset = read_unsigned(reader)?;
+            let count = read_unsigned(reader)? as usize;
+            entries.push(IndexEntry { offset, first_record, count });
+            first_record += count as u64;
+        }
+        Ok(BlockIndex { sync, len, entries })
+    }
+}
+
+fn read_unsigned<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let (val, _) = read_long(reader)?;
+    if val < 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("negative value {} in block index", val)))
+    }
+    Ok(val as u64)
+}
@@ -0,0 +1,28 @@
+mod header;
+pub use self::header::*;
+
+mod codec;
+pub use self::codec::*;
+
+mod reader;
+pub use self::reader::*;
+
+mod index;
+pub use self::index::*;
+
+mod parallel;
+pub use self::parallel::*;
+
+mod slice;
+pub use self::slice::*;
+
+#[cfg(feature = "async")]
+mod stream;
+#[cfg(feature = "async")]
+pub use self::stream::*;
+
+mod writer;
+pub use self::writer::*;
+
+mod recovery;
+pub use self::recovery::*;
@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::sync::{ Arc, Mutex };
+use std::sync::mpsc::{ self, Receiver, SyncSender };
+use std::thread::{ self, JoinHandle };
+use std::vec;
+
+use serde::de::DeserializeOwned;
+
+use super::super::*;
+use super::codec::Codec;
+use super::reader::{ Block, ContainerReader, RawBlock };
+use super::recovery::BadBlock;
+
+type Batch<T> = (u64, Result<Vec<T>, AvroError>);
+
+/// Decompresses and decodes a container file's blocks on a pool of worker
+/// threads. One more thread reads the file and hands out the blocks.
+///
+/// Records come out in file order by default. Unordered output hands over
+/// each block as soon as it's decoded, which keeps the workers busier when
+/// blocks take uneven time.
+pub struct ParallelReader<R> {
+    reader: ContainerReader<R>,
+    threads: usize,
+    ordered: bool,
+}
+
+impl<R: Read + Send + 'static> ParallelReader<R> {
+    /// Defaults to one worker per cpu, in order
+    pub fn new(reader: ContainerReader<R>) -> Self {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        ParallelReader { reader, threads, ordered: true }
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    pub fn records<T: DeserializeOwned + Send + 'static>(self) -> ParallelRecords<T> {
+        self.spawn(|de| T::deserialize(de))
+    }
+
+    pub fn values(self) -> ParallelRecords<Value> {
+        self.spawn(|de| DatumReader::new(de.schema).read(de))
+    }
+
+    fn spawn<T, F>(self, decode: F) -> ParallelRecords<T>
+        where T: Send + 'static, F: for<'de> Fn(&mut AvroDeserializer<'de>) -> Result<T, AvroError> + Send + Sync + 'static {
+        let ParallelReader { mut reader, threads, ordered } = self;
+        let schema = Arc::new(reader.schema().clone());
+        let codec = reader.codec();
+        let decode = Arc::new(decode);
+
+        // enough queued blocks to keep every worker busy, without reading far ahead
+        let (work_tx, work_rx) = mpsc::sync_channel::<(u64, RawBlock)>(threads * 2);
+        let (result_tx, result_rx) = mpsc::sync_channel::<Batch<T>>(threads * 2);
+        let work_rx = Arc::new(Mutex::new(work_rx));
+        let (bad_tx, bad_rx) = mpsc::channel();
+
+        let mut handles = Vec::with_capacity(threads + 1);
+        for _ in 0..threads {
+            let (work_rx, result_tx, schema, decode) = (work_rx.clone(), result_tx.clone(), schema.clone(), decode.clone());
+            handles.push(thread::spawn(move || work(&work_rx, &result_tx, &schema, codec, &*decode)));
+        }
+
+        handles.push(thread::spawn(move || {
+            let mut seq = 0;
+            loop {
+                let next = reader.next_raw_block();
+                // blocks skipped in recovery mode, before the results channel hangs up
+                for bad in reader.bad_blocks.drain(..) {
+                    let _ = bad_tx.send(bad);
+                }
+                match next {
+                    Ok(Some(raw)) => if work_tx.send((seq, raw)).is_err() { break },
+                    Ok(None) => break,
+                    Err(err) => {
+                        let _ = result_tx.send((seq, Err(err)));
+                        break
+                    },
+                }
+                seq += 1;
+            }
+            info!("read {} blocks for the workers", seq);
+        }));
+
+        ParallelRecords {
+            results: result_rx,
+            pending: BTreeMap::new(),
+            next: 0,
+            ordered,
+            current: Vec::new().into_iter(),
+            bad_rx,
+            bad_blocks: Vec::new(),
+            handles,
+        }
+    }
+}
+
+fn work<T, F>(work_rx: &Mutex<Receiver<(u64, RawBlock)>>, result_tx: &SyncSender<Batch<T>>, schema: &Schema, codec: Codec, decode: &F)
+    where F: for<'de> Fn(&mut AvroDeserializer<'de>) -> Result<T, AvroError> {
+    loop {
+        let next = work_rx.lock().unwrap().recv();
+        let (seq, raw) = match next {
+            Ok(next) => next,
+            Err(_) => return,
+        };
+
+        let decoded = raw.decompress(codec).and_then(|block| decode_block(schema, &block, decode));
+        if result_tx.send((seq, decoded)).is_err() {
+            return
+        }
+    }
+}
+
+fn decode_block<T, F>(schema: &Schema, block: &Block, decode: &F) -> Result<Vec<T>, AvroError>
+    where F: for<'de> Fn(&mut AvroDeserializer<'de>) -> Result<T, AvroError> {
+    let mut de = AvroDeserializer::from_slice(schema, &block.data[..]);
+    let mut records = Vec::with_capacity(block.count);
+    for _ in 0..block.count {
+        de.current_field_index = None;
+        records.push(decode(&mut de)?);
+    }
+    if !de.buf.is_empty() {
+        return Err(AvroError{ reason: format!("block at offset {} has {} bytes left after its {} records", block.offset, de.buf.len(), block.count) })
+    }
+    Ok(records)
+}
+
+/// Records decoded by a `ParallelReader`. An error for one block doesn't
+/// stop the blocks after it, unless it came from reading the file.
+pub struct ParallelRecords<T> {
+    results: Receiver<Batch<T>>,
+    pending: BTreeMap<u64, Result<Vec<T>, AvroError>>,
+    next: u64,
+    ordered: bool,
+    current: vec::IntoIter<T>,
+    bad_rx: Receiver<BadBlock>,
+    bad_blocks: Vec<BadBlock>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl<T> ParallelRecords<T> {
+    /// The blocks skipped so far by a reader in recovery mode, all of them
+    /// once the records have run out
+    pub fn bad_blocks(&mut self) -> &[BadBlock] {
+        self.bad_blocks.extend(self.bad_rx.try_iter());
+        &self.bad_blocks[..]
+    }
+
+    fn next_batch(&mut self) -> Option<Result<Vec<T>, AvroError>> {
+        if !self.ordered {
+            return self.results.recv().ok().map(|(_, batch)| batch)
+        }
+
+        while !self.pending.contains_key(&self.next) {
+            match self.results.recv() {
+                Ok((seq, batch)) => { self.pending.insert(seq, batch); },
+                Err(_) => return None,
+            }
+        }
+        let batch = self.pending.remove(&self.next);
+        self.next += 1;
+        batch
+    }
+}
+
+impl<T> Iterator for ParallelRecords<T> {
+    type Item = Result<T, AvroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.current.next() {
+                return Some(Ok(record))
+            }
+            match self.next_batch()? {
+                Ok(batch) => self.current = batch.into_iter(),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+impl<T> Drop for ParallelRecords<T> {
+    fn drop(&mut self) {
+        // hanging up the results channel winds the threads down
+        let (_, results) = mpsc::sync_channel(0);
+        self.results = results;
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
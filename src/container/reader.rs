@@ -0,0 +1,508 @@
+use std::io::{ self, Read, Seek, SeekFrom };
+use std::marker::PhantomData;
+
+use serde::de::{ Deserialize, DeserializeOwned };
+
+use super::super::*;
+use super::codec::Codec;
+use super::header::{ Header, SYNC_SIZE };
+use super::index::BlockIndex;
+use super::recovery::BadBlock;
+
+use integer_encoding::VarInt;
+
+/// A block as it sits in the file, payload still compressed
+pub struct RawBlock {
+    /// Where the block starts in the file
+    pub offset: u64,
+    pub count: usize,
+    pub payload: Vec<u8>,
+}
+
+impl RawBlock {
+    pub fn decompress(&self, codec: Codec) -> Result<Block, AvroError> {
+        let data = codec.decompress(&self.payload[..])
+            .map_err(|err| AvroError{ reason: format!("block at offset {}: {}", self.offset, err.reason) })?;
+        Ok(Block { offset: self.offset, count: self.count, data })
+    }
+}
+
+/// One block of a container file, decompressed but with its records still encoded
+pub struct Block {
+    /// Where the block starts in the file
+    pub offset: u64,
+    pub count: usize,
+    pub data: Vec<u8>,
+}
+
+impl Block {
+    /// Decodes the block's records, which may borrow from the block
+    pub fn records<'a, T: Deserialize<'a>>(&'a self, schema: &'a Schema) -> BlockRecords<'a, T> {
+        BlockRecords {
+            de: AvroDeserializer::from_slice(schema, &self.data[..]),
+            remaining: self.count,
+            failed: false,
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn values<'a>(&'a self, schema: &'a Schema) -> BlockValues<'a> {
+        BlockValues {
+            de: AvroDeserializer::from_slice(schema, &self.data[..]),
+            remaining: self.count,
+            failed: false,
+        }
+    }
+
+    /// Checks that the block holds exactly `count` well-formed records
+    pub fn validate(&self, schema: &Schema) -> Result<(), AvroError> {
+        let mut de = AvroDeserializer::from_slice(schema, &self.data[..]);
+        let reader = DatumReader::new(schema);
+        for _ in 0..self.count {
+            reader.read(&mut de)?;
+        }
+        if !de.buf.is_empty() {
+            return Err(AvroError{ reason: format!("block at offset {} has {} bytes left after its {} records", self.offset, de.buf.len(), self.count) })
+        }
+        Ok(())
+    }
+}
+
+/// A block's records. It stops after the first error, since the records
+/// after it would be read from the middle of the bad one.
+pub struct BlockRecords<'a, T> {
+    de: AvroDeserializer<'a>,
+    remaining: usize,
+    failed: bool,
+    phantom: PhantomData<T>,
+}
+
+impl<'a, T: Deserialize<'a>> Iterator for BlockRecords<'a, T> {
+    type Item = Result<T, AvroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 || self.failed {
+            return None
+        }
+        self.remaining -= 1;
+        self.de.current_field_index = None;
+        let record = T::deserialize(&mut self.de);
+        self.failed = record.is_err();
+        Some(record)
+    }
+}
+
+/// A block's records as values, stopping after the first error like `BlockRecords`
+pub struct BlockValues<'a> {
+    de: AvroDeserializer<'a>,
+    remaining: usize,
+    failed: bool,
+}
+
+impl<'a> Iterator for BlockValues<'a> {
+    type Item = Result<Value, AvroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 || self.failed {
+            return None
+        }
+        self.remaining -= 1;
+        let value = DatumReader::new(self.de.schema).read(&mut self.de);
+        self.failed = value.is_err();
+        Some(value)
+    }
+}
+
+// Hands back bytes that were read for a bad block before reading on, so the
+// scan for the next sync marker can look inside them.
+struct Pushback<R> {
+    pending: Vec<u8>,
+    pos: usize,
+    inner: R,
+}
+
+impl<R> Pushback<R> {
+    fn push_back(&mut self, bytes: &[u8]) {
+        let mut pending = bytes.to_owned();
+        pending.extend_from_slice(&self.pending[self.pos..]);
+        self.pending = pending;
+        self.pos = 0;
+    }
+}
+
+impl<R: Read> Read for Pushback<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.pending.len() {
+            return self.inner.read(buf)
+        }
+
+        let len = buf.len().min(self.pending.len() - self.pos);
+        buf[..len].copy_from_slice(&self.pending[self.pos..self.pos + len]);
+        self.pos += len;
+        if self.pos == self.pending.len() {
+            self.pending.clear();
+            self.pos = 0;
+        }
+        Ok(len)
+    }
+}
+
+struct Position {
+    block: Block,
+    pos: usize,
+    remaining: usize,
+}
+
+/// Reads an object container file block by block. Wrap files in a
+/// `BufReader`, varints are read a byte at a time.
+///
+/// In recovery mode a block that is truncated, fails its sync check, doesn't
+/// decompress or doesn't decode is reported in `bad_blocks` and skipped, and
+/// reading carries on after the next sync marker.
+pub struct ContainerReader<R> {
+    reader: Pushback<R>,
+    header: Header,
+    codec: Codec,
+    offset: u64,
+    current: Option<Position>,
+    recover: bool,
+    pub(crate) bad_blocks: Vec<BadBlock>,
+}
+
+impl<R: Read> ContainerReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, AvroError> {
+        let (header, offset) = Header::read(&mut reader)?;
+        let codec = Codec::from_name(&header.codec)?;
+
+        Ok(ContainerReader {
+            reader: Pushback { pending: Vec::new(), pos: 0, inner: reader },
+            header,
+            codec,
+            offset,
+            current: None,
+            recover: false,
+            bad_blocks: Vec::new(),
+        })
+    }
+
+    pub fn recovery(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.header.schema
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// The blocks skipped so far in recovery mode
+    pub fn bad_blocks(&self) -> &[BadBlock] {
+        &self.bad_blocks[..]
+    }
+
+    /// Reads the next block without decompressing it, dropping whatever is
+    /// left of the block `next_record`/`next_value` were working through.
+    pub fn next_raw_block(&mut self) -> Result<Option<RawBlock>, AvroError> {
+        self.current = None;
+        loop {
+            let offset = self.offset;
+            let mut raw = Vec::new();
+            let mut count = None;
+
+            let err = match self.read_raw(&mut raw, &mut count) {
+                Ok(None) => return Ok(None),
+                Ok(Some(payload_start)) => {
+                    self.offset += raw.len() as u64;
+                    raw.truncate(raw.len() - SYNC_SIZE);
+                    let payload = raw.split_off(payload_start);
+                    info!("block at offset {}: {:?} records, {} bytes", offset, count, payload.len());
+                    return Ok(Some(RawBlock { offset, count: count.unwrap(), payload }))
+                },
+                Err(err) => err,
+            };
+
+            if !self.recover {
+                return Err(err)
+            }
+            self.report(offset, count, err);
+
+            // the next sync marker may sit anywhere past this block's first byte
+            if raw.len() > 1 {
+                self.reader.push_back(&raw[1..]);
+            }
+            self.offset = offset + 1;
+            if !self.resync()? {
+                return Ok(None)
+            }
+        }
+    }
+
+    /// Reads and decompresses the next block, dropping whatever is left of
+    /// the block `next_record`/`next_value` were working through.
+    pub fn next_block(&mut self) -> Result<Option<Block>, AvroError> {
+        loop {
+            let raw = match self.next_raw_block()? {
+                Some(raw) => raw,
+                None => return Ok(None),
+            };
+            match raw.decompress(self.codec) {
+                Ok(block) => return Ok(Some(block)),
+                Err(err) => {
+                    if !self.recover {
+                        return Err(err)
+                    }
+                    self.report(raw.offset, Some(raw.count), err);
+                },
+            }
+        }
+    }
+
+    pub fn next_record<T: DeserializeOwned>(&mut self) -> Result<Option<T>, AvroError> {
+        self.next_datum(|de| T::deserialize(de))
+    }
+
+    pub fn next_value(&mut self) -> Result<Option<Value>, AvroError> {
+        self.next_datum(|de| DatumReader::new(de.schema).read(de))
+    }
+
+    pub fn records<T: DeserializeOwned>(self) -> Records<R, T> {
+        Records { reader: self, failed: false, phantom: PhantomData }
+    }
+
+    pub fn values(self) -> Values<R> {
+        Values { reader: self, failed: false }
+    }
+
+    fn next_datum<X, F>(&mut self, decode: F) -> Result<Option<X>, AvroError>
+        where F: for<'de> Fn(&mut AvroDeserializer<'de>) -> Result<X, AvroError> {
+        loop {
+            while self.current.as_ref().is_none_or(|position| position.remaining == 0) {
+                match self.next_block()? {
+                    Some(block) => self.current = Some(Position { remaining: block.count, pos: 0, block }),
+                    None => return Ok(None),
+                }
+            }
+
+            let decoded = {
+                let position = self.current.as_mut().unwrap();
+                let decoded = {
+                    let buf = &position.block.data[position.pos..];
+                    let mut de = AvroDeserializer::from_slice(&self.header.schema, buf);
+                    decode(&mut de).map(|datum| (datum, buf.len() - de.buf.len()))
+                };
+                decoded.and_then(|(datum, consumed)| {
+                    position.pos += consumed;
+                    position.remaining -= 1;
+                    if position.remaining == 0 && position.pos != position.block.data.len() {
+                        return Err(AvroError{ reason: format!("block at offset {} has {} bytes left after its {} records",
+                                                             position.block.offset, position.block.data.len() - position.pos, position.block.count) })
+                    }
+                    Ok(datum)
+                })
+            };
+
+            match decoded {
+                Ok(datum) => return Ok(Some(datum)),
+                Err(err) => {
+                    if !self.recover {
+                        return Err(err)
+                    }
+                    let position = self.current.take().unwrap();
+                    self.report(position.block.offset, Some(position.block.count), err);
+                },
+            }
+        }
+    }
+
+    // Reads one block's bytes into `raw`, returning where its payload starts
+    fn read_raw(&mut self, raw: &mut Vec<u8>, count: &mut Option<usize>) -> Result<Option<usize>, AvroError> {
+        let block_count = match read_long_into(&mut self.reader, raw) {
+            Ok(block_count) => block_count,
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof && raw.is_empty() => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        if block_count >= 0 {
+            *count = Some(block_count as usize);
+        }
+        let size = read_long_into(&mut self.reader, raw)?;
+        if block_count < 0 || size < 0 {
+            return Err(AvroError{ reason: format!("block has count {} and size {}", block_count, size) })
+        }
+
+        let payload_start = raw.len();
+        (&mut self.reader).take(size as u64).read_to_end(raw)?;
+        (&mut self.reader).take(SYNC_SIZE as u64).read_to_end(raw)?;
+        if raw.len() != payload_start + size as usize + SYNC_SIZE {
+            return Err(AvroError{ reason: format!("block is truncated, {} of {} bytes left", raw.len() - payload_start, size as usize + SYNC_SIZE) })
+        }
+        if raw[raw.len() - SYNC_SIZE..] != self.header.sync {
+            return Err(AvroError{ reason: "sync marker mismatch after block".into() })
+        }
+        Ok(Some(payload_start))
+    }
+
+    // Scans forward to just past the next sync marker, false if the file ends first
+    fn resync(&mut self) -> Result<bool, AvroError> {
+        let mut window = [0u8; SYNC_SIZE];
+        let mut scanned = 0;
+        let mut byte = [0u8];
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(0) => return Ok(false),
+                Ok(_) => {},
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+            self.offset += 1;
+            scanned += 1;
+            window.copy_within(1.., 0);
+            window[SYNC_SIZE - 1] = byte[0];
+            if scanned >= SYNC_SIZE && window == self.header.sync {
+                info!("resynced at offset {}", self.offset);
+                return Ok(true)
+            }
+        }
+    }
+
+    fn report(&mut self, offset: u64, count: Option<usize>, error: AvroError) {
+        warn!("skipping bad block at offset {}: {}", offset, error);
+        self.bad_blocks.push(BadBlock { offset, count, error });
+    }
+}
+
+impl<R: Read + Seek> ContainerReader<R> {
+    /// Indexes the file's blocks, leaving the reader where it was
+    pub fn build_index(&mut self) -> Result<BlockIndex, AvroError> {
+        let index = BlockIndex::build(&mut self.reader.inner);
+        let offset = self.offset;
+        self.seek_to(offset)?;
+        index
+    }
+
+    /// Moves to the block starting at `offset`, which has to come from an
+    /// index or a block's `offset`
+    pub fn seek_block(&mut self, offset: u64) -> Result<(), AvroError> {
+        self.seek_to(offset)?;
+        self.current = None;
+        Ok(())
+    }
+
+    /// Reads the one block starting at `offset`
+    pub fn read_block_at(&mut self, offset: u64) -> Result<Block, AvroError> {
+        self.seek_block(offset)?;
+        self.next_block()?
+            .ok_or_else(|| AvroError{ reason: format!("no block at offset {}", offset) })
+    }
+
+    /// Moves to record number `record`, counting from 0, so that it's the
+    /// next one `next_record`/`next_value` return
+    pub fn seek_record(&mut self, index: &BlockIndex, record: u64) -> Result<(), AvroError> {
+        let entry = *index.find(record)
+            .ok_or_else(|| AvroError{ reason: format!("record {} is past the end of the file's {} records", record, index.records()) })?;
+        let block = self.read_block_at(entry.offset)?;
+
+        let skip = (record - entry.first_record) as usize;
+        let pos = {
+            let mut de = AvroDeserializer::from_slice(&self.header.schema, &block.data[..]);
+            let reader = DatumReader::new(&self.header.schema);
+            for _ in 0..skip {
+                reader.read(&mut de)?;
+            }
+            block.data.len() - de.buf.len()
+        };
+        info!("seeked to record {}, {} into block at offset {}", record, skip, block.offset);
+        self.current = Some(Position { remaining: block.count - skip, pos, block });
+        Ok(())
+    }
+
+    fn seek_to(&mut self, offset: u64) -> Result<(), AvroError> {
+        self.reader.inner.seek(SeekFrom::Start(offset))?;
+        self.reader.pending.clear();
+        self.reader.pos = 0;
+        self.offset = offset;
+        Ok(())
+    }
+}
+
+fn read_long_into<R: Read>(reader: &mut R, raw: &mut Vec<u8>) -> io::Result<i64> {
+    let start = raw.len();
+    loop {
+        let mut byte = [0u8];
+        reader.read_exact(&mut byte)?;
+        raw.push(byte[0]);
+        if byte[0] & 0x80 == 0 {
+            return Ok(i64::decode_var(&raw[start..]).0)
+        }
+        if raw.len() - start == 10 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unterminated varint"))
+        }
+    }
+}
+
+/// Iterates over the file's records. Without recovery, it stops after the
+/// first error rather than failing on the same record again.
+pub struct Records<R, T> {
+    reader: ContainerReader<R>,
+    failed: bool,
+    phantom: PhantomData<T>,
+}
+
+impl<R, T> Records<R, T> {
+    pub fn reader(&self) -> &ContainerReader<R> {
+        &self.reader
+    }
+}
+
+impl<R: Read, T: DeserializeOwned> Iterator for Records<R, T> {
+    type Item = Result<T, AvroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None
+        }
+        match self.reader.next_record() {
+            Ok(Some(record)) => Some(Ok(record)),
+            Ok(None) => None,
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            },
+        }
+    }
+}
+
+pub struct Values<R> {
+    reader: ContainerReader<R>,
+    failed: bool,
+}
+
+impl<R> Values<R> {
+    pub fn reader(&self) -> &ContainerReader<R> {
+        &self.reader
+    }
+}
+
+impl<R: Read> Iterator for Values<R> {
+    type Item = Result<Value, AvroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None
+        }
+        match self.reader.next_value() {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => None,
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            },
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::io::{ Read, Write };
+
+use super::super::*;
+use super::reader::ContainerReader;
+use super::writer::ContainerWriter;
+
+/// A block a recovering reader gave up on
+#[derive(Debug)]
+pub struct BadBlock {
+    /// Where the block starts in the file
+    pub offset: u64,
+    /// The record count the block claimed, when it got that far
+    pub count: Option<usize>,
+    pub error: AvroError,
+}
+
+/// Copies the intact blocks of a damaged container file into a new one, with
+/// the same schema, codec and metadata. Returns the new file's writer and
+/// the blocks that were left out.
+pub fn repair<R: Read, W: Write>(input: R, output: W) -> Result<(W, Vec<BadBlock>), AvroError> {
+    let mut reader = ContainerReader::new(input)?.recovery(true);
+
+    let mut writer = ContainerWriter::with_codec(reader.schema(), reader.codec(), output);
+    // the schema text goes across as it was, not as `Schema` would write it
+    if let Some(json) = reader.header().metadata.get("avro.schema") {
+        writer = writer.schema_json(json);
+    }
+    for (key, value) in &reader.header().metadata {
+        writer = writer.metadata(key, value);
+    }
+
+    let mut invalid = Vec::new();
+    while let Some(block) = reader.next_block()? {
+        match block.validate(reader.schema()) {
+            Ok(()) => writer.write_block(block.count, &block.data[..])?,
+            Err(error) => {
+                warn!("leaving out block at offset {}: {}", block.offset, error);
+                invalid.push(BadBlock { offset: block.offset, count: Some(block.count), error });
+            },
+        }
+    }
+
+    let mut bad_blocks : Vec<BadBlock> = reader.bad_blocks.drain(..).chain(invalid).collect();
+    bad_blocks.sort_by_key(|bad| bad.offset);
+    Ok((writer.finish()?, bad_blocks))
+}
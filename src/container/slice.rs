@@ -0,0 +1,183 @@
+use std::marker::PhantomData;
+
+use serde::de::Deserialize;
+
+use super::super::*;
+use super::codec::Codec;
+use super::header::{ Header, SYNC_SIZE };
+
+#[cfg(feature = "mmap")]
+use std::fs::File;
+#[cfg(feature = "mmap")]
+use std::path::Path;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+
+/// A container file reader mapped into memory
+#[cfg(feature = "mmap")]
+pub type MmapReader = SliceReader<Mmap>;
+
+/// Reads an uncompressed container file that's already in memory. Records
+/// borrow their strings and bytes straight from the buffer, nothing is copied.
+pub struct SliceReader<B> {
+    buf: B,
+    header: Header,
+    data_start: usize,
+}
+
+impl<B: AsRef<[u8]>> SliceReader<B> {
+    pub fn new(buf: B) -> Result<Self, AvroError> {
+        let (header, data_start) = Header::read(&mut buf.as_ref())?;
+        let codec = Codec::from_name(&header.codec)?;
+        if codec != Codec::Null {
+            return Err(AvroError{ reason: format!("can't borrow records from {} compressed blocks, use a ContainerReader", codec.name()) })
+        }
+
+        Ok(SliceReader { buf, header, data_start: data_start as usize })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.header.schema
+    }
+
+    pub fn blocks(&self) -> SliceBlocks<'_> {
+        SliceBlocks {
+            buf: self.buf.as_ref(),
+            offset: self.data_start,
+            sync: &self.header.sync,
+            schema: &self.header.schema,
+        }
+    }
+
+    pub fn records<'a, T: Deserialize<'a>>(&'a self) -> SliceRecords<'a, T> {
+        SliceRecords {
+            blocks: self.blocks(),
+            current: None,
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn values(&self) -> SliceValues<'_> {
+        SliceValues {
+            records: SliceRecords { blocks: self.blocks(), current: None, phantom: PhantomData },
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl SliceReader<Mmap> {
+    /// Maps the file at `path`. The file mustn't be truncated or rewritten
+    /// while it's mapped, appending to it is fine.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, AvroError> {
+        let file = File::open(path)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        SliceReader::new(mmap)
+    }
+}
+
+/// A block borrowed from the buffer
+pub struct SliceBlock<'a> {
+    pub offset: u64,
+    pub count: usize,
+    pub data: &'a [u8],
+}
+
+pub struct SliceBlocks<'a> {
+    buf: &'a [u8],
+    offset: usize,
+    sync: &'a [u8; SYNC_SIZE],
+    schema: &'a Schema,
+}
+
+impl<'a> SliceBlocks<'a> {
+    fn read_block(&mut self) -> Result<SliceBlock<'a>, AvroError> {
+        let mut de = AvroDeserializer::from_slice(self.schema, &self.buf[self.offset..]);
+        let count = de.visit_long()?;
+        let size = de.visit_long()?;
+        if count < 0 || size < 0 {
+            return Err(AvroError{ reason: format!("block has count {} and size {}", count, size) })
+        }
+        let data = de.take(size as usize)?;
+        if de.take(SYNC_SIZE)? != &self.sync[..] {
+            return Err(AvroError{ reason: "sync marker mismatch after block".into() })
+        }
+
+        let offset = self.offset;
+        self.offset = self.buf.len() - de.buf.len();
+        Ok(SliceBlock { offset: offset as u64, count: count as usize, data })
+    }
+}
+
+impl<'a> Iterator for SliceBlocks<'a> {
+    type Item = Result<SliceBlock<'a>, AvroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset == self.buf.len() {
+            return None
+        }
+        let offset = self.offset;
+        match self.read_block() {
+            Ok(block) => Some(Ok(block)),
+            Err(err) => {
+                // don't go on reading from the middle of a broken block
+                self.offset = self.buf.len();
+                Some(Err(AvroError{ reason: format!("block at offset {}: {}", offset, err.reason) }))
+            },
+        }
+    }
+}
+
+pub struct SliceRecords<'a, T> {
+    blocks: SliceBlocks<'a>,
+    current: Option<(AvroDeserializer<'a>, u64, usize)>,
+    phantom: PhantomData<T>,
+}
+
+impl<'a, T> SliceRecords<'a, T> {
+    fn next_datum<X, F>(&mut self, decode: F) -> Option<Result<X, AvroError>>
+        where F: Fn(&mut AvroDeserializer<'a>) -> Result<X, AvroError> {
+        while self.current.as_ref().is_none_or(|current| current.2 == 0) {
+            match self.blocks.next()? {
+                Ok(block) => self.current = Some((AvroDeserializer::from_slice(self.blocks.schema, block.data), block.offset, block.count)),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+
+        let (ref mut de, offset, ref mut remaining) = *self.current.as_mut().unwrap();
+        *remaining -= 1;
+        de.current_field_index = None;
+        let datum = decode(de);
+        if datum.is_ok() && *remaining == 0 && !de.buf.is_empty() {
+            return Some(Err(AvroError{ reason: format!("block at offset {} has {} bytes left after its records", offset, de.buf.len()) }))
+        }
+        if datum.is_err() {
+            // the rest of the block can't be found without this record's length
+            *remaining = 0;
+        }
+        Some(datum)
+    }
+}
+
+impl<'a, T: Deserialize<'a>> Iterator for SliceRecords<'a, T> {
+    type Item = Result<T, AvroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_datum(|de| T::deserialize(de))
+    }
+}
+
+pub struct SliceValues<'a> {
+    records: SliceRecords<'a, Value>,
+}
+
+impl<'a> Iterator for SliceValues<'a> {
+    type Item = Result<Value, AvroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.records.next_datum(|de| DatumReader::new(de.schema).read(de))
+    }
+}
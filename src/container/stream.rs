@@ -0,0 +1,224 @@
+use std::io::{ self, Read };
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{ Context, Poll };
+
+use bytes::{ Buf, BytesMut };
+use futures_core::Stream;
+use serde::de::DeserializeOwned;
+use tokio::io::AsyncRead;
+use tokio_util::codec::{ Decoder, FramedRead };
+
+use super::super::*;
+use super::codec::Codec;
+use super::header::{ read_long, Header, SYNC_SIZE };
+use super::reader::{ Block, RawBlock };
+
+/// The longest block payload `ContainerCodec` waits for unless told otherwise
+pub const DEFAULT_MAX_BLOCK_LENGTH: usize = 64 * 1024 * 1024;
+
+/// A tokio decoder that turns the bytes of a container file into its
+/// header and then its blocks, decompressed. Waits for a whole block to
+/// arrive before handing it out.
+pub struct ContainerCodec {
+    header: Option<Header>,
+    codec: Codec,
+    offset: u64,
+    max_block_length: usize,
+}
+
+impl ContainerCodec {
+    pub fn new() -> Self {
+        ContainerCodec { header: None, codec: Codec::Null, offset: 0, max_block_length: DEFAULT_MAX_BLOCK_LENGTH }
+    }
+
+    /// Refuses blocks whose payload is longer than `len`, 64MB unless set
+    pub fn max_block_length(mut self, len: usize) -> Self {
+        self.max_block_length = len;
+        self
+    }
+
+    /// The header, once it's been read
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+}
+
+impl Default for ContainerCodec {
+    fn default() -> Self {
+        ContainerCodec::new()
+    }
+}
+
+impl Decoder for ContainerCodec {
+    type Item = Block;
+    type Error = AvroError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Block>, AvroError> {
+        if self.header.is_none() {
+            let mut buffered = Buffered { buf: &src[..], ran_out: false };
+            let (header, len) = match Header::read(&mut buffered) {
+                Ok(header) => header,
+                Err(_) if buffered.ran_out => return Ok(None),
+                Err(err) => return Err(err),
+            };
+            self.codec = Codec::from_name(&header.codec)?;
+            self.header = Some(header);
+            self.offset = len;
+            src.advance(len as usize);
+        }
+
+        let mut buffered = Buffered { buf: &src[..], ran_out: false };
+        let (count, size, prefix) = match block_prefix(&mut buffered) {
+            Ok(prefix) => prefix,
+            Err(_) if buffered.ran_out => return Ok(None),
+            Err(err) => return Err(AvroError{ reason: format!("block at offset {}: {}", self.offset, err) }),
+        };
+        if size > self.max_block_length {
+            return Err(AvroError{ reason: format!("block at offset {} is {} bytes, more than the {} allowed", self.offset, size, self.max_block_length) })
+        }
+        let len = prefix + size + SYNC_SIZE;
+        if src.len() < len {
+            src.reserve(len - src.len());
+            return Ok(None)
+        }
+
+        let block = src.split_to(len);
+        if block[prefix + size..] != self.header.as_ref().unwrap().sync[..] {
+            return Err(AvroError{ reason: format!("block at offset {}: sync marker mismatch after block", self.offset) })
+        }
+        let raw = RawBlock { offset: self.offset, count, payload: block[prefix..prefix + size].to_vec() };
+        self.offset += len as u64;
+        raw.decompress(self.codec).map(Some)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Block>, AvroError> {
+        match self.decode(src)? {
+            Some(block) => Ok(Some(block)),
+            None if src.is_empty() && self.header.is_some() => Ok(None),
+            None if src.is_empty() => Err(AvroError{ reason: "container file is empty".into() }),
+            None => Err(AvroError{ reason: format!("container file ends partway through the block at offset {}", self.offset) }),
+        }
+    }
+}
+
+// Reads a block's record count and size, returning them with how many bytes they took
+fn block_prefix<R: Read>(reader: &mut R) -> io::Result<(usize, usize, usize)> {
+    let (count, count_size) = read_long(reader)?;
+    let (size, size_size) = read_long(reader)?;
+    if count < 0 || size < 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("block has count {} and size {}", count, size)))
+    }
+    Ok((count as usize, size as usize, count_size + size_size))
+}
+
+// Reads what's arrived so far, noting when a read wanted more than that
+struct Buffered<'a> {
+    buf: &'a [u8],
+    ran_out: bool,
+}
+
+impl<'a> Read for Buffered<'a> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.buf.is_empty() && !out.is_empty() {
+            self.ran_out = true;
+        }
+        self.buf.read(out)
+    }
+}
+
+/// Reads a container file off a tokio `AsyncRead`. It's a stream of the
+/// file's blocks; `records` decodes them into owned records.
+pub struct AsyncContainerReader<R> {
+    frames: FramedRead<R, ContainerCodec>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncContainerReader<R> {
+    pub fn new(reader: R) -> Self {
+        AsyncContainerReader { frames: FramedRead::new(reader, ContainerCodec::new()) }
+    }
+
+    /// The header, once the stream has been polled far enough to read it
+    pub fn header(&self) -> Option<&Header> {
+        self.frames.decoder().header()
+    }
+
+    pub fn schema(&self) -> Option<&Schema> {
+        self.header().map(|header| &header.schema)
+    }
+
+    pub fn records<T: DeserializeOwned>(self) -> AsyncRecords<R, T> {
+        AsyncRecords { blocks: self, current: None, failed: false, phantom: PhantomData }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.frames.into_inner()
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for AsyncContainerReader<R> {
+    type Item = Result<Block, AvroError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.frames).poll_next(cx)
+    }
+}
+
+/// The records of a container file read off a tokio `AsyncRead`, stopping
+/// after the first error
+pub struct AsyncRecords<R, T> {
+    blocks: AsyncContainerReader<R>,
+    /// The block being worked through, how far into it and how many records are left
+    current: Option<(Block, usize, usize)>,
+    failed: bool,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<R: AsyncRead + Unpin, T: DeserializeOwned> AsyncRecords<R, T> {
+    pub fn reader(&self) -> &AsyncContainerReader<R> {
+        &self.blocks
+    }
+
+    fn next_record(&mut self) -> Option<Result<T, AvroError>> {
+        let schema = &self.blocks.header()?.schema;
+        let (ref block, ref mut pos, ref mut remaining) = *self.current.as_mut()?;
+        if *remaining == 0 {
+            return None
+        }
+        let mut de = AvroDeserializer::from_slice(schema, &block.data[*pos..]);
+        let record = T::deserialize(&mut de)
+            .map_err(|err| AvroError{ reason: format!("block at offset {}: {}", block.offset, err.reason) });
+        *pos = block.data.len() - de.buf.len();
+        *remaining -= 1;
+        Some(record)
+    }
+}
+
+impl<R: AsyncRead + Unpin, T: DeserializeOwned> Stream for AsyncRecords<R, T> {
+    type Item = Result<T, AvroError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if this.failed {
+            return Poll::Ready(None)
+        }
+        loop {
+            if let Some(record) = this.next_record() {
+                this.failed = record.is_err();
+                return Poll::Ready(Some(record))
+            }
+            match Pin::new(&mut this.blocks).poll_next(cx) {
+                Poll::Ready(Some(Ok(block))) => {
+                    let count = block.count;
+                    this.current = Some((block, 0, count));
+                },
+                Poll::Ready(Some(Err(err))) => {
+                    this.failed = true;
+                    return Poll::Ready(Some(Err(err)))
+                },
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::io::{ Read, Seek, SeekFrom, Write };
+
+use serde::ser::Serialize;
+
+use super::super::*;
+use super::codec::Codec;
+use super::header::{ Header, MAGIC, SYNC_SIZE };
+
+use rand;
+
+pub const DEFAULT_BLOCK_RECORDS: usize = 4000;
+pub const DEFAULT_BLOCK_BYTES: usize = 64 * 1024;
+
+/// Writes an object container file. Records are buffered into a block that
+/// goes out once it holds `block_records` records or `block_bytes` bytes.
+/// Call `finish` when done, a dropped writer loses its buffered block.
+pub struct ContainerWriter<W: Write> {
+    writer: W,
+    schema: Schema,
+    /// The `avro.schema` text to write instead of serializing `schema`
+    schema_json: Option<Vec<u8>>,
+    metadata: HashMap<String, Vec<u8>>,
+    codec: Codec,
+    sync: [u8; SYNC_SIZE],
+    block: Vec<u8>,
+    count: usize,
+    block_records: usize,
+    block_bytes: usize,
+    header_written: bool,
+}
+
+impl<W: Write> ContainerWriter<W> {
+    /// Starts a new file. Nothing is written until the first block is flushed.
+    pub fn new(schema: &Schema, writer: W) -> Self {
+        ContainerWriter::with_codec(schema, Codec::Null, writer)
+    }
+
+    /// Starts a new file whose blocks are compressed with `codec`, which has
+    /// to be compiled in. A file being appended to keeps the codec it has.
+    pub fn with_codec(schema: &Schema, codec: Codec, writer: W) -> Self {
+        ContainerWriter {
+            writer,
+            schema: schema.clone(),
+            schema_json: None,
+            metadata: HashMap::new(),
+            codec,
+            sync: rand::random(),
+            block: Vec::new(),
+            count: 0,
+            block_records: DEFAULT_BLOCK_RECORDS,
+            block_bytes: DEFAULT_BLOCK_BYTES,
+            header_written: false,
+        }
+    }
+
+    pub fn block_records(mut self, records: usize) -> Self {
+        self.block_records = records.max(1);
+        self
+    }
+
+    pub fn block_bytes(mut self, bytes: usize) -> Self {
+        self.block_bytes = bytes;
+        self
+    }
+
+    /// Adds a user metadata entry to the header, `avro.` keys are reserved
+    pub fn metadata(mut self, key: &str, value: &[u8]) -> Self {
+        self.metadata.insert(key.into(), value.to_owned());
+        self
+    }
+
+    // Writes `json`, which has to parse as the writer's schema, into the
+    // header as is, keeping what `Schema::to_json` would leave out
+    pub(crate) fn schema_json(mut self, json: &[u8]) -> Self {
+        self.schema_json = Some(json.to_owned());
+        self
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn sync_marker(&self) -> [u8; SYNC_SIZE] {
+        self.sync
+    }
+
+    pub fn write_record<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<(), AvroError> {
+        let len = self.block.len();
+        let written = {
+            let mut serializer = AvroSerializer::new(&self.schema, &mut self.block);
+            record.serialize(&mut serializer)
+        };
+        self.record_written(len, written)
+    }
+
+    pub fn write_value(&mut self, value: &Value) -> Result<(), AvroError> {
+        let len = self.block.len();
+        let written = DatumWriter::new(&self.schema).write(value, &mut self.block);
+        self.record_written(len, written)
+    }
+
+    fn record_written(&mut self, len: usize, written: Result<(), AvroError>) -> Result<(), AvroError> {
+        if let Err(err) = written {
+            // don't leave half a record in the block
+            self.block.truncate(len);
+            return Err(err)
+        }
+
+        self.count += 1;
+        if self.count >= self.block_records || self.block.len() >= self.block_bytes {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes `count` already encoded records out as a block of their own,
+    /// after whatever records were buffered ahead of them.
+    pub fn write_block(&mut self, count: usize, data: &[u8]) -> Result<(), AvroError> {
+        self.flush()?;
+        if count == 0 {
+            return Ok(())
+        }
+        self.block.extend_from_slice(data);
+        self.count = count;
+        self.flush()
+    }
+
+    /// Writes out the buffered block, if any, and flushes the underlying writer
+    pub fn flush(&mut self) -> Result<(), AvroError> {
+        if !self.header_written {
+            self.codec.ensure_available()?;
+            self.write_header()?;
+        }
+
+        if self.count > 0 {
+            info!("flushing block of {} records, {} bytes", self.count, self.block.len());
+            let data = self.codec.compress(&self.block[..])?;
+            let mut buf = Vec::with_capacity(data.len() + 20 + SYNC_SIZE);
+            write_long(self.count as i64, &mut buf);
+            write_long(data.len() as i64, &mut buf);
+            buf.extend_from_slice(&data[..]);
+            buf.extend_from_slice(&self.sync);
+            self.writer.write_all(&buf[..])?;
+
+            self.block.clear();
+            self.count = 0;
+        }
+
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Flushes the last block and hands back the underlying writer
+    pub fn finish(mut self) -> Result<W, AvroError> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_header(&mut self) -> Result<(), AvroError> {
+        let mut buf = MAGIC.to_vec();
+
+        let schema = match self.schema_json {
+            Some(ref json) => json.clone(),
+            None => self.schema.to_json().into_bytes(),
+        };
+        let mut entries : Vec<(&str, &[u8])> = vec![("avro.schema", &schema[..]), ("avro.codec", self.codec.name().as_bytes())];
+        for (key, value) in self.metadata.iter().filter(|entry| !entry.0.starts_with("avro.")) {
+            entries.push((&key[..], &value[..]));
+        }
+
+        write_long(entries.len() as i64, &mut buf);
+        for (key, value) in entries {
+            write_bytes(key.as_bytes(), &mut buf);
+            write_bytes(value, &mut buf);
+        }
+        write_long(0, &mut buf);
+        buf.extend_from_slice(&self.sync);
+
+        self.writer.write_all(&buf[..])?;
+        self.header_written = true;
+        Ok(())
+    }
+}
+
+impl<W: Read + Write + Seek> ContainerWriter<W> {
+    /// Reopens an existing file to add blocks after its last one, reusing its
+    /// schema and sync marker.
+    pub fn append(mut file: W) -> Result<Self, AvroError> {
+        file.seek(SeekFrom::Start(0))?;
+        let (header, _) = Header::read(&mut file)?;
+        let codec = Codec::from_name(&header.codec)?;
+        file.seek(SeekFrom::End(0))?;
+
+        Ok(ContainerWriter {
+            writer: file,
+            schema: header.schema,
+            schema_json: None,
+            metadata: header.metadata,
+            codec,
+            sync: header.sync,
+            block: Vec::new(),
+            count: 0,
+            block_records: DEFAULT_BLOCK_RECORDS,
+            block_bytes: DEFAULT_BLOCK_BYTES,
+            header_written: true,
+        })
+    }
+}
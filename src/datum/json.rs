@@ -0,0 +1,243 @@
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::{ self, Map, Number, Value as Json };
+
+use super::super::*;
+
+/// Writes datums in the JSON encoding the spec defines, for reading payloads
+/// and writing fixtures by hand. Unions other than null are wrapped in an
+/// object keyed by the branch's type name, `{"string": "a"}`, and bytes and
+/// fixed values are strings with one code point per byte. Floats that JSON
+/// can't hold are written as `"NaN"`, `"Infinity"` and `"-Infinity"`.
+/// Record fields and map entries come out sorted by name.
+pub struct JsonEncoder<'s> {
+    pub schema: &'s Schema,
+}
+
+impl<'s> JsonEncoder<'s> {
+    pub fn new(schema: &'s Schema) -> Self {
+        JsonEncoder { schema }
+    }
+
+    /// Encodes a value, checked against the schema as `DatumWriter` would
+    pub fn encode(&self, value: &Value) -> Result<Json, AvroError> {
+        let datum = DatumWriter::new(self.schema).to_vec(value)?;
+        self.encode_datum(&datum[..])
+    }
+
+    /// Re-encodes a datum in the binary encoding as JSON
+    pub fn encode_datum(&self, datum: &[u8]) -> Result<Json, AvroError> {
+        let record = DatumReader::new(self.schema).from_slice(datum)?;
+        let fields = match record {
+            Value::Record(fields) => fields,
+            _ => unreachable!(),
+        };
+
+        let mut object = Map::new();
+        for (field, (name, value)) in self.schema.fields.iter().zip(fields) {
+            let path = format!("{}.{}", self.schema.name, name);
+            object.insert(name, self.field_json(&field.types[..], value, &path)?);
+        }
+        Ok(Json::Object(object))
+    }
+
+    pub fn to_string(&self, value: &Value) -> Result<String, AvroError> {
+        Ok(self.encode(value)?.to_string())
+    }
+
+    // Values straight from `DatumReader`, so union fields are always `Value::Union`
+    fn field_json(&self, types: &[SchemaFieldType], value: Value, path: &str) -> Result<Json, AvroError> {
+        match value {
+            Value::Union(index, value) => {
+                let branch = &types[index];
+                if *branch == SchemaFieldType::Primitive(Primitive::Null) {
+                    return Ok(Json::Null)
+                }
+                let mut wrapped = Map::new();
+                wrapped.insert(branch_name(self.schema, branch), type_json(*value, path)?);
+                Ok(Json::Object(wrapped))
+            },
+            value => type_json(value, path),
+        }
+    }
+}
+
+fn type_json(value: Value, path: &str) -> Result<Json, AvroError> {
+    let json = match value {
+        Value::Null => Json::Null,
+        Value::Boolean(val) => Json::Bool(val),
+        Value::Int(val) => Json::Number(val.into()),
+        Value::Long(val) => Json::Number(val.into()),
+        Value::Float(val) => float_json(val as f64),
+        Value::Double(val) => float_json(val),
+        Value::Bytes(bytes) | Value::Fixed(bytes) => Json::String(bytes.iter().map(|&byte| byte as char).collect()),
+        Value::String(string) => Json::String(string),
+        Value::Map(entries) => {
+            let mut object = Map::new();
+            for (key, value) in entries {
+                let value = type_json(value, &format!("{}[{:?}]", path, key))?;
+                object.insert(key, value);
+            }
+            Json::Object(object)
+        },
+        Value::Array(values) => Json::Array(values.into_iter().enumerate()
+            .map(|(index, value)| type_json(value, &format!("{}[{}]", path, index)))
+            .collect::<Result<_, _>>()?),
+        other => return Err(AvroError{ reason: format!("{}: a {} can't be nested here", path, other.kind()) }),
+    };
+    Ok(json)
+}
+
+fn float_json(val: f64) -> Json {
+    match Number::from_f64(val) {
+        Some(number) => Json::Number(number),
+        None if val.is_nan() => Json::String("NaN".into()),
+        None if val > 0.0 => Json::String("Infinity".into()),
+        None => Json::String("-Infinity".into()),
+    }
+}
+
+/// What a union branch is called in the JSON encoding: the full name for a
+/// fixed, the type's name for anything else
+fn branch_name(schema: &Schema, branch: &SchemaFieldType) -> String {
+    match *branch {
+        SchemaFieldType::Complex(Complex::Fixed { ref name, .. }) => schema.qualify(name),
+        ref other => other.type_name().into(),
+    }
+}
+
+/// Reads datums back from the spec's JSON encoding, following the schema.
+/// Union branches may be named with or without the namespace.
+pub struct JsonDecoder<'s> {
+    pub schema: &'s Schema,
+}
+
+impl<'s> JsonDecoder<'s> {
+    pub fn new(schema: &'s Schema) -> Self {
+        JsonDecoder { schema }
+    }
+
+    pub fn decode(&self, json: &Json) -> Result<Value, AvroError> {
+        let name = &self.schema.name;
+        let object = match *json {
+            Json::Object(ref object) => object,
+            ref other => return Err(AvroError{ reason: format!("{}: expected a record, got {}", name, other) }),
+        };
+
+        for key in object.keys() {
+            if !self.schema.fields.iter().any(|field| field.name == *key) {
+                return Err(AvroError{ reason: format!("{}.{}: not a field of the schema", name, key) })
+            }
+        }
+
+        let mut fields = Vec::with_capacity(self.schema.fields.len());
+        for field in &self.schema.fields {
+            let path = format!("{}.{}", name, field.name);
+            let value = match object.get(&field.name) {
+                Some(value) => self.field_value(&field.types[..], value, &path)?,
+                None => return Err(AvroError{ reason: format!("{}: missing field", path) }),
+            };
+            fields.push((field.name.clone(), value));
+        }
+        Ok(Value::Record(fields))
+    }
+
+    pub fn from_str(&self, json: &str) -> Result<Value, AvroError> {
+        let json = serde_json::from_str(json).map_err(|err| AvroError{ reason: format!("not JSON: {}", err) })?;
+        self.decode(&json)
+    }
+
+    /// Decodes straight to the binary encoding
+    pub fn decode_datum(&self, json: &Json) -> Result<Vec<u8>, AvroError> {
+        DatumWriter::new(self.schema).to_vec(&self.decode(json)?)
+    }
+
+    fn field_value(&self, types: &[SchemaFieldType], json: &Json, path: &str) -> Result<Value, AvroError> {
+        if types.len() == 1 {
+            return type_value(&types[0], json, path)
+        }
+
+        let (index, json) = match *json {
+            Json::Null => match types.iter().position(|branch| *branch == SchemaFieldType::Primitive(Primitive::Null)) {
+                Some(index) => (index, json),
+                None => return Err(AvroError{ reason: format!("{}: null isn't a branch of the union", path) }),
+            },
+            Json::Object(ref wrapped) if wrapped.len() == 1 => {
+                let (name, json) = wrapped.iter().next().unwrap();
+                let index = types.iter()
+                    .position(|branch| branch_name(self.schema, branch) == *name || branch.type_name() == name)
+                    .ok_or_else(|| AvroError{ reason: format!("{}: {} isn't a branch of the union", path, name) })?;
+                (index, json)
+            },
+            ref other => return Err(AvroError{ reason: format!("{}: expected null or {{\"<branch>\": value}} for a union, got {}", path, other) }),
+        };
+        Ok(Value::Union(index, Box::new(type_value(&types[index], json, path)?)))
+    }
+}
+
+fn type_value(field_type: &SchemaFieldType, json: &Json, path: &str) -> Result<Value, AvroError> {
+    let mismatch = || AvroError{ reason: format!("{}: expected {}, got {}", path, field_type.type_name(), json) };
+    let value = match (field_type, json) {
+        (SchemaFieldType::Primitive(Primitive::Null), Json::Null) => Value::Null,
+        (SchemaFieldType::Primitive(Primitive::Boolean), Json::Bool(val)) => Value::Boolean(*val),
+        (SchemaFieldType::Primitive(Primitive::Int), Json::Number(val)) => {
+            let val = val.as_i64().filter(|val| *val >= i32::MIN as i64 && *val <= i32::MAX as i64).ok_or_else(mismatch)?;
+            Value::Int(val as i32)
+        },
+        (SchemaFieldType::Primitive(Primitive::Long), Json::Number(val)) => Value::Long(val.as_i64().ok_or_else(mismatch)?),
+        (SchemaFieldType::Primitive(Primitive::Float), _) => Value::Float(float_value(json).ok_or_else(mismatch)? as f32),
+        (SchemaFieldType::Primitive(Primitive::Double), _) => Value::Double(float_value(json).ok_or_else(mismatch)?),
+        (SchemaFieldType::Primitive(Primitive::Bytes), Json::String(val)) => Value::Bytes(code_points(val).map_err(|err| AvroError{ reason: format!("{}: {}", path, err.reason) })?),
+        (SchemaFieldType::Primitive(Primitive::String), Json::String(val)) => Value::String(val.clone()),
+        (SchemaFieldType::Primitive(Primitive::Uint64T), Json::String(val)) |
+        (SchemaFieldType::Primitive(Primitive::Int64T), Json::String(val)) |
+        (SchemaFieldType::Complex(Complex::Fixed { .. }), Json::String(val)) => {
+            let value = Value::Fixed(code_points(val).map_err(|err| AvroError{ reason: format!("{}: {}", path, err.reason) })?);
+            if !value.matches(field_type) {
+                return Err(AvroError{ reason: format!("{}: {:?} is the wrong size for {}", path, val, field_type.type_name()) })
+            }
+            value
+        },
+        (SchemaFieldType::Complex(Complex::Map { values }), Json::Object(entries)) => {
+            let values = SchemaFieldType::element(values).map_err(|err| AvroError{ reason: format!("{}: {}", path, err.reason) })?;
+            let entries = entries.iter()
+                .map(|(key, value)| Ok((key.clone(), type_value(&values, value, &format!("{}[{:?}]", path, key))?)))
+                .collect::<Result<_, AvroError>>()?;
+            Value::Map(entries)
+        },
+        (SchemaFieldType::Complex(Complex::Array { items }), Json::Array(values)) => {
+            let items = SchemaFieldType::element(items).map_err(|err| AvroError{ reason: format!("{}: {}", path, err.reason) })?;
+            let values = values.iter().enumerate()
+                .map(|(index, value)| type_value(&items, value, &format!("{}[{}]", path, index)))
+                .collect::<Result<_, _>>()?;
+            Value::Array(values)
+        },
+        _ => return Err(mismatch()),
+    };
+    Ok(value)
+}
+
+fn float_value(json: &Json) -> Option<f64> {
+    match *json {
+        Json::Number(ref val) => val.as_f64(),
+        Json::String(ref val) if val == "NaN" => Some(f64::NAN),
+        Json::String(ref val) if val == "Infinity" => Some(f64::INFINITY),
+        Json::String(ref val) if val == "-Infinity" => Some(f64::NEG_INFINITY),
+        _ => None,
+    }
+}
+
+/// Encodes `value` in the spec's JSON encoding
+pub fn to_avro_json<T: Serialize + ?Sized>(schema: &Schema, value: &T) -> Result<String, AvroError> {
+    let datum = to_vec(schema, value)?;
+    Ok(JsonEncoder::new(schema).encode_datum(&datum[..])?.to_string())
+}
+
+/// Decodes a `T` from the spec's JSON encoding. It goes through the binary
+/// encoding, so only owned types can come out of it.
+pub fn from_avro_json<T: DeserializeOwned>(schema: &Schema, json: &str) -> Result<T, AvroError> {
+    let decoder = JsonDecoder::new(schema);
+    let datum = DatumWriter::new(schema).to_vec(&decoder.from_str(json)?)?;
+    let mut de = AvroDeserializer::from_slice(schema, &datum[..]);
+    T::deserialize(&mut de)
+}
@@ -0,0 +1,14 @@
+mod value;
+pub use self::value::*;
+
+mod writer;
+pub use self::writer::*;
+
+mod reader;
+pub use self::reader::*;
+
+mod resolve;
+pub use self::resolve::*;
+
+mod json;
+pub use self::json::*;
@@ -0,0 +1,106 @@
+use super::super::*;
+
+/// The most items an `array<null>` may hold as a `Value`. Nulls take no
+/// bytes, so nothing else bounds how many a few bytes of input ask for.
+pub const MAX_NULL_ITEMS: usize = 1024 * 1024;
+
+/// Decodes dynamic `Value`s through an `AvroDeserializer`, following the
+/// deserializer's schema. Union fields come back as `Value::Union` so the
+/// branch survives a round trip through `DatumWriter`.
+pub struct DatumReader<'s> {
+    pub schema: &'s Schema,
+}
+
+impl<'s> DatumReader<'s> {
+    pub fn new(schema: &'s Schema) -> Self {
+        DatumReader { schema }
+    }
+
+    pub fn from_slice(&self, buf: &[u8]) -> Result<Value, AvroError> {
+        let mut de = AvroDeserializer::from_slice(self.schema, buf);
+        self.read(&mut de)
+    }
+
+    pub fn read(&self, de: &mut AvroDeserializer) -> Result<Value, AvroError> {
+        let mut fields = Vec::with_capacity(self.schema.fields.len());
+        for field in &self.schema.fields {
+            let value = read_field(&field.types[..], de)?;
+            fields.push((field.name.clone(), value));
+        }
+        Ok(Value::Record(fields))
+    }
+}
+
+pub(crate) fn read_field(types: &[SchemaFieldType], de: &mut AvroDeserializer) -> Result<Value, AvroError> {
+    if types.len() == 1 {
+        return read_type(&types[0], de)
+    }
+
+    let index = de.visit_long()?;
+    if index < 0 || index as usize >= types.len() {
+        return Err(AvroError{ reason: format!("union branch {} is out of scope, max is {}", index, types.len()) })
+    }
+    let value = read_type(&types[index as usize], de)?;
+    Ok(Value::Union(index as usize, Box::new(value)))
+}
+
+fn read_type(field_type: &SchemaFieldType, de: &mut AvroDeserializer) -> Result<Value, AvroError> {
+    let value = match *field_type {
+        SchemaFieldType::Primitive(ref primitive) => match *primitive {
+            Primitive::Null => Value::Null,
+            Primitive::Boolean => Value::Boolean(de.take(1)?[0] != 0),
+            Primitive::Int => Value::Int(de.visit_int()?),
+            Primitive::Long => Value::Long(de.visit_long()?),
+            Primitive::Float => Value::Float(de.visit_f32()?),
+            Primitive::Double => Value::Double(de.visit_f64()?),
+            Primitive::Bytes => Value::Bytes(de.visit_borrow_bytes()?.to_owned()),
+            Primitive::String => {
+                let bytes = de.visit_borrow_bytes()?.to_owned();
+                match String::from_utf8(bytes) {
+                    Ok(string) => Value::String(string),
+                    Err(err) => return Err(AvroError{ reason: format!("invalid utf-8 in string: {}", err) }),
+                }
+            },
+            Primitive::Uint64T | Primitive::Int64T => Value::Fixed(de.take(8)?.to_owned()),
+        },
+        SchemaFieldType::Complex(Complex::Fixed { size, .. }) => Value::Fixed(de.take(size)?.to_owned()),
+        SchemaFieldType::Complex(Complex::Map { ref values }) => {
+            let values = SchemaFieldType::element(values)?;
+            let mut entries = Vec::new();
+            let mut remaining = de.visit_block_len()?;
+            while remaining > 0 {
+                for _ in 0..remaining {
+                    let key = match read_type(&SchemaFieldType::Primitive(Primitive::String), de)? {
+                        Value::String(key) => key,
+                        _ => unreachable!(),
+                    };
+                    entries.push((key, read_type(&values, de)?));
+                }
+                remaining = de.visit_block_len()?;
+            }
+            Value::Map(entries)
+        },
+        SchemaFieldType::Complex(Complex::Array { ref items }) => {
+            let items = SchemaFieldType::element(items)?;
+            let mut values = Vec::new();
+            let mut remaining = de.visit_block_len()?;
+            while remaining > 0 {
+                if items == SchemaFieldType::Primitive(Primitive::Null) {
+                    // nulls take no bytes, so only the limit bounds a block of them
+                    let len = values.len().saturating_add(remaining);
+                    if len > MAX_NULL_ITEMS {
+                        return Err(AvroError{ reason: format!("array of {} nulls is over the limit of {}", len, MAX_NULL_ITEMS) })
+                    }
+                    values.resize(len, Value::Null);
+                } else {
+                    for _ in 0..remaining {
+                        values.push(read_type(&items, de)?);
+                    }
+                }
+                remaining = de.visit_block_len()?;
+            }
+            Value::Array(values)
+        },
+    };
+    Ok(value)
+}
@@ -0,0 +1,249 @@
+use std::convert::TryFrom;
+
+use serde_json;
+
+use super::super::*;
+
+enum Source {
+    /// The writer's field at this index
+    Field(usize),
+    Default(Value),
+}
+
+/// Turns values written with one schema into values of another, following
+/// the spec's resolution rules: fields are matched by name, writer-only
+/// fields are dropped, reader-only fields take their default, numbers are
+/// promoted (int to long, float or double, long to float or double, float
+/// to double) and strings and bytes convert into each other.
+pub struct Resolution {
+    pub writer: Schema,
+    pub reader: Schema,
+    sources: Vec<Source>,
+}
+
+impl Resolution {
+    /// Fails when a reader field is neither written nor has a default
+    pub fn new(writer: &Schema, reader: &Schema) -> Result<Self, AvroError> {
+        if writer.full_name() != reader.full_name() {
+            return Err(AvroError{ reason: format!("can't read {} records as {}", writer.full_name(), reader.full_name()) })
+        }
+
+        let mut sources = Vec::with_capacity(reader.fields.len());
+        for field in &reader.fields {
+            let source = match writer.fields.iter().position(|written| written.name == field.name) {
+                Some(idx) => Source::Field(idx),
+                None => match field.default {
+                    Some(ref default) => Source::Default(default_value(default, &field.types[..])
+                        .map_err(|err| AvroError{ reason: format!("{}.{}: bad default: {}", reader.name, field.name, err.reason) })?),
+                    None => return Err(AvroError{ reason: format!("{}.{}: not in the writer's schema and has no default", reader.name, field.name) }),
+                },
+            };
+            sources.push(source);
+        }
+
+        Ok(Resolution { writer: writer.clone(), reader: reader.clone(), sources })
+    }
+
+    /// Checks that every branch the writer's schema allows can be read, which
+    /// is how registries judge compatibility. `resolve` only fails on
+    /// branches that were actually written.
+    pub fn check(&self) -> Result<(), AvroError> {
+        for (field, source) in self.reader.fields.iter().zip(&self.sources) {
+            if let Source::Field(idx) = *source {
+                for written in &self.writer.fields[idx].types {
+                    let path = format!("{}.{}", self.reader.name, field.name);
+                    resolve_branch(written, &field.types[..], &path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves a record read with the writer's schema
+    pub fn resolve(&self, value: Value) -> Result<Value, AvroError> {
+        let mut written = match value {
+            Value::Record(fields) => fields,
+            other => return Err(AvroError{ reason: format!("{}: expected record, got {}", self.writer.name, other.kind()) }),
+        };
+        if written.len() != self.writer.fields.len() {
+            return Err(AvroError{ reason: format!("{}: expected {} fields, got {}", self.writer.name, self.writer.fields.len(), written.len()) })
+        }
+
+        let mut fields = Vec::with_capacity(self.sources.len());
+        for (field, source) in self.reader.fields.iter().zip(&self.sources) {
+            let value = match *source {
+                Source::Field(idx) => {
+                    let value = std::mem::replace(&mut written[idx].1, Value::Null);
+                    let path = format!("{}.{}", self.reader.name, field.name);
+                    resolve_field(&self.writer.fields[idx].types[..], &field.types[..], value, &path)?
+                },
+                Source::Default(ref value) => value.clone(),
+            };
+            fields.push((field.name.clone(), value));
+        }
+        Ok(Value::Record(fields))
+    }
+}
+
+fn resolve_field(writer: &[SchemaFieldType], reader: &[SchemaFieldType], value: Value, path: &str) -> Result<Value, AvroError> {
+    let (written, value) = match value {
+        Value::Union(idx, value) if writer.len() > 1 => (&writer[idx], *value),
+        value => (&writer[0], value),
+    };
+
+    let branch = resolve_branch(written, reader, path)?;
+    let value = convert(written, &reader[branch], value, path)?;
+    if reader.len() > 1 {
+        Ok(Value::Union(branch, Box::new(value)))
+    } else {
+        Ok(value)
+    }
+}
+
+fn resolve_branch(written: &SchemaFieldType, reader: &[SchemaFieldType], path: &str) -> Result<usize, AvroError> {
+    // an exact match beats a promotion
+    let branch = reader.iter().position(|read| same_type(written, read))
+        .or_else(|| reader.iter().position(|read| promotes(written, read)));
+    branch.ok_or_else(|| {
+        let names : Vec<&str> = reader.iter().map(|t| t.type_name()).collect();
+        AvroError{ reason: format!("{}: written as {}, which [{}] can't read", path, written.type_name(), names.join(", ")) }
+    })
+}
+
+fn same_type(writer: &SchemaFieldType, reader: &SchemaFieldType) -> bool {
+    match (writer, reader) {
+        (SchemaFieldType::Complex(Complex::Fixed { name: ref wname, size: wsize }), SchemaFieldType::Complex(Complex::Fixed { name: ref rname, size: rsize })) =>
+            unqualified(wname) == unqualified(rname) && wsize == rsize,
+        (SchemaFieldType::Complex(Complex::Map { values: ref written }), SchemaFieldType::Complex(Complex::Map { values: ref read })) |
+        (SchemaFieldType::Complex(Complex::Array { items: ref written }), SchemaFieldType::Complex(Complex::Array { items: ref read })) =>
+            match (SchemaFieldType::named(written), SchemaFieldType::named(read)) {
+                (Some(written), Some(read)) => same_type(&written, &read) || promotes(&written, &read),
+                _ => false,
+            },
+        // the named 8 byte fixeds can be spelled either way
+        (SchemaFieldType::Primitive(Primitive::Uint64T), SchemaFieldType::Complex(Complex::Fixed { ref name, size: 8 })) |
+        (SchemaFieldType::Complex(Complex::Fixed { ref name, size: 8 }), SchemaFieldType::Primitive(Primitive::Uint64T)) => unqualified(name) == "uint64_t",
+        (SchemaFieldType::Primitive(Primitive::Int64T), SchemaFieldType::Complex(Complex::Fixed { ref name, size: 8 })) |
+        (SchemaFieldType::Complex(Complex::Fixed { ref name, size: 8 }), SchemaFieldType::Primitive(Primitive::Int64T)) => unqualified(name) == "int64_t",
+        (writer, reader) => writer == reader,
+    }
+}
+
+fn promotes(writer: &SchemaFieldType, reader: &SchemaFieldType) -> bool {
+    use self::Primitive::*;
+
+    match (writer, reader) {
+        (SchemaFieldType::Primitive(writer), SchemaFieldType::Primitive(reader)) => matches!((writer, reader),
+            (Int, Long) | (Int, Float) | (Int, Double) |
+            (Long, Float) | (Long, Double) |
+            (Float, Double) |
+            (String, Bytes) | (Bytes, String)),
+        _ => false,
+    }
+}
+
+fn unqualified(name: &str) -> &str {
+    name.rsplit('.').next().unwrap()
+}
+
+fn convert(writer: &SchemaFieldType, reader: &SchemaFieldType, value: Value, path: &str) -> Result<Value, AvroError> {
+    let value = match (value, reader) {
+        (Value::Int(val), &SchemaFieldType::Primitive(Primitive::Long)) => Value::Long(val as i64),
+        (Value::Int(val), &SchemaFieldType::Primitive(Primitive::Float)) => Value::Float(val as f32),
+        (Value::Int(val), &SchemaFieldType::Primitive(Primitive::Double)) => Value::Double(val as f64),
+        (Value::Long(val), &SchemaFieldType::Primitive(Primitive::Float)) => Value::Float(val as f32),
+        (Value::Long(val), &SchemaFieldType::Primitive(Primitive::Double)) => Value::Double(val as f64),
+        (Value::Float(val), &SchemaFieldType::Primitive(Primitive::Double)) => Value::Double(val as f64),
+        (Value::String(val), &SchemaFieldType::Primitive(Primitive::Bytes)) => Value::Bytes(val.into_bytes()),
+        (Value::Bytes(val), &SchemaFieldType::Primitive(Primitive::String)) => Value::String(String::from_utf8(val)
+            .map_err(|err| AvroError{ reason: format!("{}: bytes aren't a utf-8 string: {}", path, err) })?),
+        (Value::Map(entries), &SchemaFieldType::Complex(Complex::Map { values: ref read })) => {
+            let (written, read) = element_types(writer, read, path)?;
+            let entries = entries.into_iter()
+                .map(|(key, value)| {
+                    let value = convert(&written, &read, value, &format!("{}[{:?}]", path, key))?;
+                    Ok((key, value))
+                })
+                .collect::<Result<_, AvroError>>()?;
+            Value::Map(entries)
+        },
+        (Value::Array(items), &SchemaFieldType::Complex(Complex::Array { items: ref read })) => {
+            let (written, read) = element_types(writer, read, path)?;
+            let items = items.into_iter().enumerate()
+                .map(|(idx, item)| convert(&written, &read, item, &format!("{}[{}]", path, idx)))
+                .collect::<Result<_, AvroError>>()?;
+            Value::Array(items)
+        },
+        (value, _) => value,
+    };
+    Ok(value)
+}
+
+fn element_types(writer: &SchemaFieldType, read: &str, path: &str) -> Result<(SchemaFieldType, SchemaFieldType), AvroError> {
+    let written = match *writer {
+        SchemaFieldType::Complex(Complex::Map { values: ref name }) | SchemaFieldType::Complex(Complex::Array { items: ref name }) => name,
+        _ => unreachable!(),
+    };
+    match (SchemaFieldType::named(written), SchemaFieldType::named(read)) {
+        (Some(written), Some(read)) => Ok((written, read)),
+        _ => Err(AvroError{ reason: format!("{}: element types {} and {} aren't supported", path, written, read) }),
+    }
+}
+
+/// A field default as a value, defaults of unions are for their first branch
+fn default_value(default: &serde_json::Value, types: &[SchemaFieldType]) -> Result<Value, AvroError> {
+    let value = default_for(default, &types[0])?;
+    if types.len() > 1 {
+        Ok(Value::Union(0, Box::new(value)))
+    } else {
+        Ok(value)
+    }
+}
+
+fn default_for(default: &serde_json::Value, field_type: &SchemaFieldType) -> Result<Value, AvroError> {
+    use serde_json::Value as Json;
+
+    let value = match (field_type, default) {
+        (SchemaFieldType::Primitive(Primitive::Null), Json::Null) => Value::Null,
+        (SchemaFieldType::Primitive(Primitive::Boolean), Json::Bool(val)) => Value::Boolean(*val),
+        (SchemaFieldType::Primitive(Primitive::Int), Json::Number(val)) if val.is_i64() => {
+            let val = val.as_i64().unwrap();
+            Value::Int(i32::try_from(val).map_err(|_| AvroError{ reason: format!("{} is out of range for an int", val) })?)
+        },
+        (SchemaFieldType::Primitive(Primitive::Long), Json::Number(val)) if val.is_i64() => Value::Long(val.as_i64().unwrap()),
+        (SchemaFieldType::Primitive(Primitive::Float), Json::Number(val)) => Value::Float(val.as_f64().unwrap() as f32),
+        (SchemaFieldType::Primitive(Primitive::Double), Json::Number(val)) => Value::Double(val.as_f64().unwrap()),
+        (SchemaFieldType::Primitive(Primitive::String), Json::String(val)) => Value::String(val.clone()),
+        // bytes and fixed defaults spell each byte as a code point
+        (SchemaFieldType::Primitive(Primitive::Bytes), Json::String(val)) => Value::Bytes(code_points(val)?),
+        (SchemaFieldType::Primitive(Primitive::Uint64T), Json::String(val)) |
+        (SchemaFieldType::Primitive(Primitive::Int64T), Json::String(val)) |
+        (SchemaFieldType::Complex(Complex::Fixed { .. }), Json::String(val)) => {
+            let bytes = code_points(val)?;
+            let value = Value::Fixed(bytes);
+            if !value.matches(field_type) {
+                return Err(AvroError{ reason: format!("default is the wrong size for {}", field_type.type_name()) })
+            }
+            value
+        },
+        (SchemaFieldType::Complex(Complex::Map { values }), Json::Object(entries)) => {
+            let values = SchemaFieldType::element(values)?;
+            let entries = entries.iter()
+                .map(|(key, value)| Ok((key.clone(), default_for(value, &values)?)))
+                .collect::<Result<_, AvroError>>()?;
+            Value::Map(entries)
+        },
+        (SchemaFieldType::Complex(Complex::Array { items }), Json::Array(values)) => {
+            let items = SchemaFieldType::element(items)?;
+            Value::Array(values.iter().map(|value| default_for(value, &items)).collect::<Result<_, _>>()?)
+        },
+        (field_type, default) => return Err(AvroError{ reason: format!("{} isn't a {}", default, field_type.type_name()) }),
+    };
+    Ok(value)
+}
+
+pub(crate) fn code_points(string: &str) -> Result<Vec<u8>, AvroError> {
+    string.chars()
+        .map(|c| if (c as u32) < 256 { Ok(c as u8) } else { Err(AvroError{ reason: format!("{:?} isn't a byte", c) }) })
+        .collect()
+}
@@ -0,0 +1,60 @@
+use super::super::*;
+
+/// A datum without a Rust struct behind it, for records assembled at runtime.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Bytes(Vec<u8>),
+    String(String),
+    Fixed(Vec<u8>),
+    Map(Vec<(String, Value)>),
+    Array(Vec<Value>),
+    /// Pins a union branch by index, for when the value alone is ambiguous
+    Union(usize, Box<Value>),
+    Record(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Value::Null => "null",
+            Value::Boolean(_) => "boolean",
+            Value::Int(_) => "int",
+            Value::Long(_) => "long",
+            Value::Float(_) => "float",
+            Value::Double(_) => "double",
+            Value::Bytes(_) => "bytes",
+            Value::String(_) => "string",
+            Value::Fixed(_) => "fixed",
+            Value::Map(_) => "map",
+            Value::Array(_) => "array",
+            Value::Union(_, _) => "union",
+            Value::Record(_) => "record",
+        }
+    }
+
+    /// Whether this value can be written as `field_type` without conversion
+    pub fn matches(&self, field_type: &SchemaFieldType) -> bool {
+        match (self, field_type) {
+            (Value::Null, SchemaFieldType::Primitive(Primitive::Null)) => true,
+            (Value::Boolean(_), SchemaFieldType::Primitive(Primitive::Boolean)) => true,
+            (Value::Int(_), SchemaFieldType::Primitive(Primitive::Int)) => true,
+            (Value::Long(_), SchemaFieldType::Primitive(Primitive::Long)) => true,
+            (Value::Float(_), SchemaFieldType::Primitive(Primitive::Float)) => true,
+            (Value::Double(_), SchemaFieldType::Primitive(Primitive::Double)) => true,
+            (Value::Bytes(_), SchemaFieldType::Primitive(Primitive::Bytes)) => true,
+            (Value::String(_), SchemaFieldType::Primitive(Primitive::String)) => true,
+            (Value::Fixed(bytes), SchemaFieldType::Primitive(Primitive::Uint64T)) |
+            (Value::Fixed(bytes), SchemaFieldType::Primitive(Primitive::Int64T)) => bytes.len() == 8,
+            (Value::Fixed(bytes), SchemaFieldType::Complex(Complex::Fixed { size, .. })) => bytes.len() == *size,
+            (Value::Map(_), SchemaFieldType::Complex(Complex::Map { .. })) => true,
+            (Value::Array(_), SchemaFieldType::Complex(Complex::Array { .. })) => true,
+            _ => false,
+        }
+    }
+}
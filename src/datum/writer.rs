@@ -0,0 +1,141 @@
+use std::io::Write;
+
+use super::super::*;
+
+use byteorder::{ LittleEndian, WriteBytesExt };
+
+/// Encodes dynamic `Value`s against a schema. Every value is checked against
+/// its field's type on the way, a mismatch comes back as an error naming the
+/// field path and nothing is written for that datum.
+pub struct DatumWriter<'s> {
+    pub schema: &'s Schema,
+}
+
+impl<'s> DatumWriter<'s> {
+    pub fn new(schema: &'s Schema) -> Self {
+        DatumWriter { schema }
+    }
+
+    pub fn write<W: Write>(&self, value: &Value, writer: &mut W) -> Result<(), AvroError> {
+        let buf = self.to_vec(value)?;
+        writer.write_all(&buf[..])?;
+        Ok(())
+    }
+
+    pub fn to_vec(&self, value: &Value) -> Result<Vec<u8>, AvroError> {
+        let mut buf = Vec::new();
+        self.write_record(value, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn write_record(&self, value: &Value, buf: &mut Vec<u8>) -> Result<(), AvroError> {
+        let name = &self.schema.name;
+        let fields = match *value {
+            Value::Record(ref fields) => fields,
+            ref other => return Err(mismatch(name, "record", other)),
+        };
+
+        for (field_name, _) in fields {
+            if !self.schema.fields.iter().any(|field| field.name == *field_name) {
+                return Err(AvroError{ reason: format!("{}.{}: not a field of the schema", name, field_name) })
+            }
+        }
+
+        for field in &self.schema.fields {
+            let path = format!("{}.{}", name, field.name);
+            match fields.iter().find(|entry| entry.0 == field.name) {
+                Some((_, value)) => write_field(&field.types[..], value, &path, buf)?,
+                None => return Err(AvroError{ reason: format!("{}: missing field", path) }),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_field(types: &[SchemaFieldType], value: &Value, path: &str, buf: &mut Vec<u8>) -> Result<(), AvroError> {
+    if types.len() == 1 {
+        return match *value {
+            Value::Union(_, _) => Err(AvroError{ reason: format!("{}: not a union", path) }),
+            _ => write_type(&types[0], value, path, buf),
+        }
+    }
+
+    let (index, value) = match *value {
+        Value::Union(index, ref inner) => {
+            if index >= types.len() {
+                return Err(AvroError{ reason: format!("{}: union branch {} is out of scope, max is {}", path, index, types.len()) })
+            }
+            (index, &**inner)
+        },
+        ref value => (resolve_branch(types, value, path)?, value),
+    };
+
+    write_long(index as i64, buf);
+    write_type(&types[index], value, path, buf)
+}
+
+/// Picks the only union branch that accepts `value` as is
+fn resolve_branch(types: &[SchemaFieldType], value: &Value, path: &str) -> Result<usize, AvroError> {
+    let mut candidates = types.iter().enumerate().filter(|&(_, t)| value.matches(t)).map(|(idx, _)| idx);
+
+    match (candidates.next(), candidates.next()) {
+        (Some(index), None) => Ok(index),
+        (None, _) => {
+            let names : Vec<&str> = types.iter().map(|t| t.type_name()).collect();
+            Err(AvroError{ reason: format!("{}: no branch of [{}] accepts a {}", path, names.join(", "), value.kind()) })
+        },
+        (Some(first), Some(second)) => Err(AvroError{ reason: format!("{}: {} is ambiguous between branches {} ({}) and {} ({}), pick one with Value::Union",
+                                                             path, value.kind(), first, types[first].type_name(), second, types[second].type_name()) }),
+    }
+}
+
+fn write_type(field_type: &SchemaFieldType, value: &Value, path: &str, buf: &mut Vec<u8>) -> Result<(), AvroError> {
+    if !value.matches(field_type) {
+        return Err(mismatch(path, field_type.type_name(), value))
+    }
+
+    match *value {
+        Value::Null => {},
+        Value::Boolean(val) => buf.push(val as u8),
+        Value::Int(val) => write_long(val as i64, buf),
+        Value::Long(val) => write_long(val, buf),
+        Value::Float(val) => buf.write_f32::<LittleEndian>(val)?,
+        Value::Double(val) => buf.write_f64::<LittleEndian>(val)?,
+        Value::Bytes(ref bytes) => write_bytes(bytes, buf),
+        Value::String(ref string) => write_bytes(string.as_bytes(), buf),
+        Value::Fixed(ref bytes) => buf.extend_from_slice(bytes),
+        Value::Map(ref entries) => {
+            let values = match *field_type {
+                SchemaFieldType::Complex(Complex::Map { ref values }) => SchemaFieldType::element(values).map_err(|err| AvroError{ reason: format!("{}: {}", path, err.reason) })?,
+                _ => unreachable!(),
+            };
+            if !entries.is_empty() {
+                write_long(entries.len() as i64, buf);
+            }
+            for (key, value) in entries {
+                write_bytes(key.as_bytes(), buf);
+                write_type(&values, value, &format!("{}[{:?}]", path, key), buf)?;
+            }
+            write_long(0, buf);
+        },
+        Value::Array(ref items) => {
+            let item_type = match *field_type {
+                SchemaFieldType::Complex(Complex::Array { ref items }) => SchemaFieldType::element(items).map_err(|err| AvroError{ reason: format!("{}: {}", path, err.reason) })?,
+                _ => unreachable!(),
+            };
+            if !items.is_empty() {
+                write_long(items.len() as i64, buf);
+            }
+            for (idx, item) in items.iter().enumerate() {
+                write_type(&item_type, item, &format!("{}[{}]", path, idx), buf)?;
+            }
+            write_long(0, buf);
+        },
+        Value::Union(_, _) | Value::Record(_) => unreachable!(),
+    }
+    Ok(())
+}
+
+fn mismatch(path: &str, expected: &str, value: &Value) -> AvroError {
+    AvroError{ reason: format!("{}: expected {}, got {}", path, expected, value.kind()) }
+}
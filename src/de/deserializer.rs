@@ -2,12 +2,13 @@ use serde::de::{ Deserializer, Visitor };
 
 use super::super::*;
 
-use byteorder::{ LittleEndian, ReadBytesExt };
+use byteorder::{ ByteOrder, LittleEndian };
 
 pub struct AvroDeserializer<'de> {
     pub buf: &'de [u8],
     pub schema: &'de Schema,
-    pub current_field_index: Option<usize>
+    pub current_field_index: Option<usize>,
+    pub plan: Option<&'de DecodePlan>,
 }
 
 impl<'de, 'a> Deserializer<'de> for &'a mut AvroDeserializer<'de> {
@@ -28,13 +29,13 @@ impl<'de, 'a> Deserializer<'de> for &'a mut AvroDeserializer<'de> {
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value,Self::Error> where V: Visitor<'de> {
         info!("deserialize_i32");
-        visitor.visit_i32(self.visit_i32())
+        visitor.visit_i32(self.visit_i32()?)
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value,Self::Error>
         where V: Visitor<'de> {
         info!("deserialize_i64");
-        visitor.visit_i64(self.visit_i64())
+        visitor.visit_i64(self.visit_i64()?)
     }
 
     fn deserialize_u8<V>(self, _: V) -> Result<V::Value,Self::Error> where V: Visitor<'de> {
@@ -46,22 +47,20 @@ impl<'de, 'a> Deserializer<'de> for &'a mut AvroDeserializer<'de> {
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value,Self::Error> where V: Visitor<'de> {
-        visitor.visit_u32(self.visit_u32())
+        visitor.visit_u32(self.visit_u32()?)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value,Self::Error> where V: Visitor<'de> {
-        visitor.visit_u64(self.visit_u64())
+        visitor.visit_u64(self.visit_u64()?)
     }
 
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value,Self::Error> where V: Visitor<'de> {
-        let val = self.buf.read_f32::<LittleEndian>().unwrap();
-        self.buf = &self.buf[4..];
-        info!("deserialize_f32: {}", val);
+        let val = self.visit_f32()?;
         visitor.visit_f32(val)
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value,Self::Error> where V: Visitor<'de> {
-        let val = self.visit_f64();
+        let val = self.visit_f64()?;
         visitor.visit_f64(val)
     }
 
@@ -69,22 +68,28 @@ impl<'de, 'a> Deserializer<'de> for &'a mut AvroDeserializer<'de> {
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where V: Visitor<'de> {
         info!("deserialize string...");
-        let string = String::from_utf8(self.visit_borrow_bytes().to_owned()).unwrap();
+        let string = String::from_utf8(self.visit_borrow_bytes()?.to_owned())
+            .map_err(|err| AvroError{ reason: format!("invalid utf-8 in string: {}", err) })?;
         visitor.visit_string(string)
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where V: Visitor<'de> {
         info!("deserialize bytes...");
-        let string = self.visit_borrow_bytes();
+        // fixed fields have no length prefix, their size is in the schema
+        if let Some(size) = self.fixed_size() {
+            return visitor.visit_borrowed_bytes(self.take(size)?)
+        }
+        let string = self.visit_borrow_bytes()?;
         visitor.visit_borrowed_bytes(string)
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where V: Visitor<'de> {
-        info!("deserialize bytes...");
-        let string = self.visit_borrow_bytes();
-        visitor.visit_borrowed_str(unsafe{ std::str::from_utf8_unchecked(string) })
+        info!("deserialize str...");
+        let string = std::str::from_utf8(self.visit_borrow_bytes()?)
+            .map_err(|err| AvroError{ reason: format!("invalid utf-8 in string: {}", err) })?;
+        visitor.visit_borrowed_str(string)
     }
 
 
@@ -92,11 +97,20 @@ impl<'de, 'a> Deserializer<'de> for &'a mut AvroDeserializer<'de> {
         where V: Visitor<'de> {
         info!("deserialize option...");
         let enum_variant = {
-            self.visit_int() as usize
+            self.visit_int()? as usize
         };
 
 //        info!("option variant: {}", enum_variant);
 
+        if let Some(field) = self.field_plan() {
+            return match field.null {
+                Some(null) if enum_variant == null => visitor.visit_none(),
+                Some(_) if enum_variant < 2 => visitor.visit_some(self),
+                Some(_) => Err(AvroError{reason: format!("option variant id for {} is out of scope, got {} but max is 2", self.current_field().name, enum_variant)}),
+                None => Err(AvroError{ reason: "this should be an option but the schema's union has no null".into() }),
+            }
+        }
+
         let current_field = self.current_field();
         if current_field.types.len() != 2 {
             return Err(AvroError{ reason: "this should be an option but the schema's union is too small".into() })
@@ -116,20 +130,21 @@ impl<'de, 'a> Deserializer<'de> for &'a mut AvroDeserializer<'de> {
 
     fn deserialize_map<V>(mut self, visitor: V) -> Result<V::Value,Self::Error>
         where V: Visitor<'de> {
-        let mut size = self.visit_long();
-        if size < 0 {
-            size *= -1;
-        }
+        let remaining = self.visit_block_len()?;
 
-        let map_visitor = AvroValueMapAccess{de: &mut self, blocks: size, entries: size*2};
-        info!("deserialize_map entries: {} => {}", size, size*2);
-        visitor.visit_map( map_visitor)
+        info!("deserialize_map entries: {}", remaining);
+        visitor.visit_map(AvroValueMapAccess{de: &mut self, remaining})
     }
 
     fn deserialize_struct<V>(mut self, _id: &'static str, fields: &'static[&'static str], visitor: V) -> Result<V::Value,Self::Error>
         where V: Visitor<'de> {
         info!("deserialize_struct -> map visitor");
 
+        if let Some(plan) = self.plan {
+            if plan.targets(fields) {
+                return visitor.visit_map(AvroPlannedMapAccess {de: self, plan})
+            }
+        }
         visitor.visit_map(AvroIdentifierMapVisitor {de: &mut self, count: 0, expected: fields.len()})
     }
 
@@ -144,6 +159,9 @@ impl<'de, 'a> Deserializer<'de> for &'a mut AvroDeserializer<'de> {
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where V: Visitor<'de> {
         self.next_field();
+        if self.current_field_index.unwrap() >= self.schema.fields.len() {
+            return Err(AvroError{ reason: format!("schema {} only has {} fields", self.schema.name, self.schema.fields.len()) })
+        }
         let current_field = self.current_field();
         info!("deserialize_identifier {}", current_field.name);
 
@@ -153,7 +171,7 @@ impl<'de, 'a> Deserializer<'de> for &'a mut AvroDeserializer<'de> {
     fn deserialize_seq<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
         where V: Visitor<'de> {
 
-        visitor.visit_seq(super::AvroSeqVisitor::new( &mut self))
+        visitor.visit_seq(super::AvroSeqVisitor::new( &mut self)?)
     }
 
     fn deserialize_tuple<V>(mut self, size: usize, visitor: V) -> Result<V::Value, Self::Error>
@@ -181,93 +199,148 @@ impl<'de> AvroDeserializer<'de> {
         self.buf[0]
     }
 
-    pub fn visit_u32(&mut self) -> u32 {
-        let (val,size) = integer_encoding::VarInt::decode_var(self.buf);
+    /// Splits `len` bytes off the front of the buffer
+    pub fn take(&mut self, len: usize) -> Result<&'de [u8], AvroError> {
+        if len > self.buf.len() {
+            return Err(eof(len - self.buf.len()))
+        }
+        let (taken, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(taken)
+    }
+
+    // Length of the varint at the front of the buffer, without trusting
+    // `decode_var` to notice a truncated or overlong one
+    fn varint_len(&self) -> Result<usize, AvroError> {
+        match self.buf.iter().take(10).position(|b| b & 0x80 == 0) {
+            Some(pos) => Ok(pos + 1),
+            None if self.buf.len() < 10 => Err(eof(1)),
+            None => Err(AvroError{ reason: "varint is longer than 10 bytes".into() }),
+        }
+    }
+
+    pub fn visit_u32(&mut self) -> Result<u32, AvroError> {
+        let size = self.varint_len()?;
+        let (val,_) = integer_encoding::VarInt::decode_var(self.buf);
         info!("visit_u32 val: {}, size: {}", val, size);
 
         self.buf = &self.buf[size..];
-        val
+        Ok(val)
     }
 
-    pub fn visit_u64(&mut self) -> u64 {
-        let (val,size) = integer_encoding::VarInt::decode_var(self.buf);
+    pub fn visit_u64(&mut self) -> Result<u64, AvroError> {
+        let size = self.varint_len()?;
+        let (val,_) = integer_encoding::VarInt::decode_var(self.buf);
         info!("val: {}, size: {}", val, size);
 
         self.buf = &self.buf[size..];
-        val
+        Ok(val)
     }
 
-    pub fn visit_i32(&mut self) -> i32 {
-        let (val,size) = integer_encoding::VarInt::decode_var(self.buf);
+    pub fn visit_i32(&mut self) -> Result<i32, AvroError> {
+        let size = self.varint_len()?;
+        let (val,_) = integer_encoding::VarInt::decode_var(self.buf);
         info!("val: {}, size: {}", val, size);
 
         self.buf = &self.buf[size..];
-        val
+        Ok(val)
     }
 
-    pub fn visit_i64(&mut self) -> i64 {
-        let (val,varsize) : (i64,usize) = integer_encoding::VarInt::decode_var(self.buf);
+    pub fn visit_i64(&mut self) -> Result<i64, AvroError> {
+        let varsize = self.varint_len()?;
+        let (val,_) : (i64,usize) = integer_encoding::VarInt::decode_var(self.buf);
         info!("visit_i64 val2: {}, varsize: {}", val, varsize);
 
         self.buf = &self.buf[varsize..];
-        val
+        Ok(val)
     }
 
-    pub fn visit_f32(&mut self) -> f32 {
-        let val = self.buf.read_f32::<LittleEndian>().unwrap();
+    pub fn visit_f32(&mut self) -> Result<f32, AvroError> {
+        let val = LittleEndian::read_f32(self.take(4)?);
         info!("deserialize_f32: {}", val);
-
-        self.buf = &self.buf[8..];
-        val
+        Ok(val)
     }
 
-    pub fn visit_f64(&mut self) -> f64 {
-        let val = self.buf.read_f64::<LittleEndian>().unwrap();
+    pub fn visit_f64(&mut self) -> Result<f64, AvroError> {
+        let val = LittleEndian::read_f64(self.take(8)?);
         info!("deserialize_f64: {}", val);
-
-        self.buf = &self.buf[8..];
-        val
+        Ok(val)
     }
 
-    pub fn visit_uint(&mut self) -> u32 {
-        let (int,varsize) = integer_encoding::VarInt::decode_var(self.buf);
+    pub fn visit_uint(&mut self) -> Result<u32, AvroError> {
+        let varsize = self.varint_len()?;
+        let (int,_) = integer_encoding::VarInt::decode_var(self.buf);
         self.buf = &self.buf[varsize..];
-        int
+        Ok(int)
     }
 
-    pub fn visit_int(&mut self) -> i32 {
-        let (int,varsize) = integer_encoding::VarInt::decode_var(self.buf);
+    pub fn visit_int(&mut self) -> Result<i32, AvroError> {
+        let varsize = self.varint_len()?;
+        let (int,_) = integer_encoding::VarInt::decode_var(self.buf);
         self.buf = &self.buf[varsize..];
         info!("visit_int: {}, size: {}", int, varsize);
-        int
+        Ok(int)
     }
 
-    pub fn visit_long(&mut self) -> i64 {
-        let (int,varsize) = integer_encoding::VarInt::decode_var(self.buf);
+    pub fn visit_long(&mut self) -> Result<i64, AvroError> {
+        let varsize = self.varint_len()?;
+        let (int,_) = integer_encoding::VarInt::decode_var(self.buf);
         self.buf = &self.buf[varsize..];
         info!("visit_long: {}, size: {}", int, varsize);
-        int
+        Ok(int)
     }
 
-    pub fn visit_borrow_bytes(&mut self) -> &'de [u8] {
-        let strlen = self.visit_long();
-        info!("strlen: {}", strlen);
+    /// Reads an array/map block header. A negative count is followed by the
+    /// block's size in bytes, which we don't need.
+    pub fn visit_block_len(&mut self) -> Result<usize, AvroError> {
+        let count = self.visit_long()?;
+        if count < 0 {
+            self.visit_long()?;
+            Ok(count.unsigned_abs() as usize)
+        } else {
+            Ok(count as usize)
+        }
+    }
 
-        let rstr = &self.buf[..strlen as usize];
-        info!("rstr: {}", String::from_utf8(rstr.to_owned()).unwrap());
+    pub fn visit_borrow_bytes(&mut self) -> Result<&'de [u8], AvroError> {
+        let strlen = self.visit_long()?;
+        info!("strlen: {}", strlen);
+        if strlen < 0 {
+            return Err(AvroError{ reason: format!("negative length {}", strlen) })
+        }
 
-        self.buf = &self.buf[strlen as usize..];
+        let rstr = self.take(strlen as usize)?;
+        info!("rstr: {}", String::from_utf8_lossy(rstr));
 
-        rstr
+        Ok(rstr)
     }
 }
 
+/// The error for a buffer that ran out `needed` bytes short
+pub(crate) fn eof(needed: usize) -> AvroError {
+    AvroError{ reason: format!("unexpected end of buffer, needed {} more bytes", needed) }
+}
+
 impl<'de> AvroDeserializer<'de> {
+    /// Decodes by looking fields up in `schema` as they come. A hot loop over
+    /// one type can compile a `DecodePlan` once and use `with_plan` instead.
     pub fn from_slice(schema: &'de Schema, buf: &'de [u8]) -> Self {
         AvroDeserializer {
             buf,
             schema,
             current_field_index: None,
+            plan: None,
+        }
+    }
+
+    /// Decodes into the type `plan` was compiled for, following the plan
+    /// rather than looking fields up in the schema
+    pub fn with_plan(plan: &'de DecodePlan, buf: &'de [u8]) -> Self {
+        AvroDeserializer {
+            buf,
+            schema: plan.schema(),
+            current_field_index: None,
+            plan: Some(plan),
         }
     }
 
@@ -280,6 +353,23 @@ impl<'de> AvroDeserializer<'de> {
         info!("done with field, now on current_field_index {:?}", self.current_field_index);
     }
 
+    /// The plan for the field being decoded, if there's a plan
+    pub(crate) fn field_plan(&self) -> Option<&'de FieldPlan> {
+        self.plan?.fields.get(self.current_field_index?)
+    }
+
+    fn fixed_size(&self) -> Option<usize> {
+        if self.plan.is_some() {
+            return self.field_plan()?.fixed
+        }
+        let field = self.schema.fields.get(self.current_field_index?)?;
+        match field.types[..] {
+            [SchemaFieldType::Complex(Complex::Fixed { size, .. })] => Some(size),
+            [SchemaFieldType::Primitive(Primitive::Uint64T)] | [SchemaFieldType::Primitive(Primitive::Int64T)] => Some(8),
+            _ => None,
+        }
+    }
+
     fn current_field(&self) -> &SchemaField {
         debug!("current_field index: {:?}", self.current_field_index);
         &self.schema.fields[self.current_field_index.unwrap()]
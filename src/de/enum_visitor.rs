@@ -26,10 +26,15 @@ impl<'de, 'a> EnumAccess<'de> for AvroEnumVisitor<'a, 'de> {
             V: DeserializeSeed<'de>,
     {
         // This is the index in to the timestamp enum
-        let variant = self.de.visit_int();
+        let variant = self.de.visit_int()?;
         info!("EnumAccess::variant_seed: {}", variant);
+        if let Some(field) = self.de.field_plan() {
+            if variant < 0 || variant as usize >= field.branches {
+                return Err(AvroError{ reason: format!("union branch {} is out of scope, max is {}", variant, field.branches) })
+            }
+        }
 
-        let val = seed.deserialize((variant as u32).into_deserializer())?;
+        let val = seed.deserialize(IntoDeserializer::<AvroError>::into_deserializer(variant as u32))?;
 
         Ok((val,self))
     }
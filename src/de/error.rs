@@ -1,6 +1,8 @@
 use std::fmt::{ Display, Formatter, Error as FmtError };
 use std::error::Error;
+use std::io;
 use serde::de::{Error as SerdeError};
+use serde::ser::{Error as SerdeSerError};
 
 #[derive(Debug)]
 pub struct AvroError {
@@ -15,6 +17,22 @@ impl SerdeError for AvroError {
     }
 }
 
+impl SerdeSerError for AvroError {
+    fn custom<T: Display>(input: T) -> Self {
+        AvroError{
+            reason: format!("serde sez {}", input)
+        }
+    }
+}
+
+impl From<io::Error> for AvroError {
+    fn from(err: io::Error) -> Self {
+        AvroError{
+            reason: format!("io error: {}", err)
+        }
+    }
+}
+
 impl Error for AvroError {
 
 }
@@ -23,4 +41,4 @@ impl Display for AvroError {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), FmtError> {
         write!(fmt, "{}", self.reason)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,200 @@
+use std::str;
+
+use super::super::*;
+
+/// One token of a datum, in the order it's encoded. Strings and bytes
+/// borrow from the buffer and names from the schema.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Event<'de> {
+    RecordStart(&'de str),
+    Field(&'de str),
+    UnionBranch(usize),
+    /// An array whose first block holds this many items
+    ArrayStart(usize),
+    /// A map whose first block holds this many entries
+    MapStart(usize),
+    /// Another block of the array or map, with this many more items or entries
+    Block(usize),
+    Key(&'de str),
+    Null,
+    Boolean(bool),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Bytes(&'de [u8]),
+    Str(&'de str),
+    Fixed(&'de [u8]),
+    /// Closes the innermost record, array or map
+    End,
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Start,
+    /// About to name this field, or close the record past the last one
+    Field(usize),
+    /// About to read the field's union branch, if it has a union
+    Value(usize),
+    /// About to read the value on this branch of the field
+    Branch(usize, usize),
+    Array { field: usize, branch: usize, remaining: usize },
+    Map { field: usize, branch: usize, remaining: usize, key_read: bool },
+    /// About to close the field's array or map
+    Close(usize),
+    Done,
+}
+
+/// A pull parser over one datum: the schema drives it across an
+/// `AvroDeserializer`'s buffer and it hands out `Event`s without serde and
+/// without allocating. Stops after the record's closing `End` or the first error.
+pub struct EventReader<'de> {
+    de: AvroDeserializer<'de>,
+    state: State,
+}
+
+impl<'de> EventReader<'de> {
+    pub fn new(de: AvroDeserializer<'de>) -> Self {
+        EventReader { de, state: State::Start }
+    }
+
+    pub fn from_slice(schema: &'de Schema, buf: &'de [u8]) -> Self {
+        EventReader::new(AvroDeserializer::from_slice(schema, buf))
+    }
+
+    /// The deserializer, whose buffer holds what's left after the events so far
+    pub fn deserializer(&self) -> &AvroDeserializer<'de> {
+        &self.de
+    }
+
+    pub fn into_inner(self) -> AvroDeserializer<'de> {
+        self.de
+    }
+
+    fn step(&mut self) -> Result<Option<Event<'de>>, AvroError> {
+        let schema = self.de.schema;
+        let event = match self.state {
+            State::Start => {
+                self.state = State::Field(0);
+                Event::RecordStart(&schema.name[..])
+            },
+            State::Field(field) if field == schema.fields.len() => {
+                self.state = State::Done;
+                Event::End
+            },
+            State::Field(field) => {
+                self.state = State::Value(field);
+                Event::Field(&schema.fields[field].name[..])
+            },
+            State::Value(field) => {
+                let types = &schema.fields[field].types;
+                if types.len() == 1 {
+                    return self.value(field, 0).map(Some)
+                }
+                let branch = self.de.visit_long()?;
+                if branch < 0 || branch as usize >= types.len() {
+                    return Err(AvroError{ reason: format!("{}: union branch {} is out of scope, max is {}", schema.fields[field].name, branch, types.len()) })
+                }
+                self.state = State::Branch(field, branch as usize);
+                Event::UnionBranch(branch as usize)
+            },
+            State::Branch(field, branch) => return self.value(field, branch).map(Some),
+            State::Array { field, branch, remaining: 0 } | State::Map { field, branch, remaining: 0, .. } => {
+                let count = self.de.visit_block_len()?;
+                if count == 0 {
+                    self.state = State::Field(field + 1);
+                    return Ok(Some(Event::End))
+                }
+                self.state = match self.state {
+                    State::Array { .. } => State::Array { field, branch, remaining: count },
+                    _ => State::Map { field, branch, remaining: count, key_read: false },
+                };
+                Event::Block(count)
+            },
+            State::Array { field, branch, remaining } => {
+                self.state = State::Array { field, branch, remaining: remaining - 1 };
+                let items = match schema.fields[field].types[branch] {
+                    SchemaFieldType::Complex(Complex::Array { ref items }) => items,
+                    _ => unreachable!(),
+                };
+                self.primitive(Primitive::element(items)?)?
+            },
+            State::Map { field, branch, remaining, key_read: false } => {
+                self.state = State::Map { field, branch, remaining, key_read: true };
+                Event::Key(self.str()?)
+            },
+            State::Map { field, branch, remaining, key_read: true } => {
+                self.state = State::Map { field, branch, remaining: remaining - 1, key_read: false };
+                let values = match schema.fields[field].types[branch] {
+                    SchemaFieldType::Complex(Complex::Map { ref values }) => values,
+                    _ => unreachable!(),
+                };
+                self.primitive(Primitive::element(values)?)?
+            },
+            State::Close(field) => {
+                self.state = State::Field(field + 1);
+                Event::End
+            },
+            State::Done => return Ok(None),
+        };
+        Ok(Some(event))
+    }
+
+    fn value(&mut self, field: usize, branch: usize) -> Result<Event<'de>, AvroError> {
+        let event = match self.de.schema.fields[field].types[branch] {
+            SchemaFieldType::Primitive(ref primitive) => {
+                self.state = State::Field(field + 1);
+                self.primitive(primitive.clone())?
+            },
+            SchemaFieldType::Complex(Complex::Fixed { size, .. }) => {
+                self.state = State::Field(field + 1);
+                Event::Fixed(self.de.take(size)?)
+            },
+            SchemaFieldType::Complex(Complex::Array { .. }) => {
+                let count = self.de.visit_block_len()?;
+                self.state = if count == 0 { State::Close(field) } else { State::Array { field, branch, remaining: count } };
+                Event::ArrayStart(count)
+            },
+            SchemaFieldType::Complex(Complex::Map { .. }) => {
+                let count = self.de.visit_block_len()?;
+                self.state = if count == 0 { State::Close(field) } else { State::Map { field, branch, remaining: count, key_read: false } };
+                Event::MapStart(count)
+            },
+        };
+        Ok(event)
+    }
+
+    fn primitive(&mut self, primitive: Primitive) -> Result<Event<'de>, AvroError> {
+        let event = match primitive {
+            Primitive::Null => Event::Null,
+            Primitive::Boolean => Event::Boolean(self.de.take(1)?[0] != 0),
+            Primitive::Int => Event::Int(self.de.visit_int()?),
+            Primitive::Long => Event::Long(self.de.visit_long()?),
+            Primitive::Float => Event::Float(self.de.visit_f32()?),
+            Primitive::Double => Event::Double(self.de.visit_f64()?),
+            Primitive::Bytes => Event::Bytes(self.de.visit_borrow_bytes()?),
+            Primitive::String => Event::Str(self.str()?),
+            Primitive::Uint64T | Primitive::Int64T => Event::Fixed(self.de.take(8)?),
+        };
+        Ok(event)
+    }
+
+    fn str(&mut self) -> Result<&'de str, AvroError> {
+        str::from_utf8(self.de.visit_borrow_bytes()?)
+            .map_err(|err| AvroError{ reason: format!("invalid utf-8 in string: {}", err) })
+    }
+}
+
+impl<'de> Iterator for EventReader<'de> {
+    type Item = Result<Event<'de>, AvroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.step() {
+            Ok(event) => event.map(Ok),
+            Err(err) => {
+                self.state = State::Done;
+                Some(Err(err))
+            },
+        }
+    }
+}
@@ -4,8 +4,7 @@ use super::*;
 
 pub struct AvroValueMapAccess<'a, 'de: 'a> {
     pub de: &'a mut AvroDeserializer<'de>,
-    pub blocks: i64,
-    pub entries: i64,
+    pub remaining: usize,
 }
 
 impl<'a, 'de> MapAccess<'de> for AvroValueMapAccess<'a, 'de> {
@@ -18,15 +17,11 @@ impl<'a, 'de> MapAccess<'de> for AvroValueMapAccess<'a, 'de> {
     /// `MapAccess::next_key` or `MapAccess::next_entry` instead.
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
         where K: DeserializeSeed<'de> {
-        info!("next_value_seed (entry {})", self.entries);
-        if self.de.peek() == 0 {
-            Ok(None)
-        } else if self.entries <= 0 {
+        info!("next_key_seed (remaining {})", self.remaining);
+        if self.remaining == 0 {
             Ok(None)
         } else {
-            let val = seed.deserialize(&mut *self.de).map(Some);
-            self.entries -= 1;
-            val
+            seed.deserialize(&mut *self.de).map(Some)
         }
     }
 
@@ -43,12 +38,18 @@ impl<'a, 'de> MapAccess<'de> for AvroValueMapAccess<'a, 'de> {
         where
             V: DeserializeSeed<'de> {
         info!("next_value_seed");
-        seed.deserialize(&mut *self.de)
+        let val = seed.deserialize(&mut *self.de)?;
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            // Block exhausted, pick up the next block's count (0 terminates)
+            self.remaining = self.de.visit_block_len()?;
+        }
+        Ok(val)
     }
 
     /// Returns the number of entries remaining in the map, if known.
     #[inline]
     fn size_hint(&self) -> Option<usize> {
-        Some(self.blocks as usize)
+        Some(self.remaining)
     }
 }
\ No newline at end of file
@@ -17,4 +17,24 @@ mod seq_visitor;
 pub use self::seq_visitor::*;
 
 mod tuple_visitor;
-pub use self::tuple_visitor::*;
\ No newline at end of file
+pub use self::tuple_visitor::*;
+
+mod stream;
+pub use self::stream::*;
+
+mod walk;
+
+mod read;
+pub use self::read::*;
+
+mod partial;
+pub use self::partial::*;
+
+mod events;
+pub use self::events::*;
+
+mod view;
+pub use self::view::*;
+
+mod plan;
+pub use self::plan::*;
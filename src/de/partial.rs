@@ -0,0 +1,75 @@
+use serde::de::Deserialize;
+
+use super::super::*;
+use super::walk::{ Stop, Walk };
+
+/// What decoding a record off the front of a buffer came to
+#[derive(Debug, PartialEq)]
+pub enum Partial<T> {
+    /// The record and how many bytes it took
+    Complete(T, usize),
+    /// The buffer ends partway through the record. At least this many more
+    /// bytes are needed; more may turn out to be once they're there, since a
+    /// length or count that hasn't arrived yet can't be counted.
+    Incomplete(usize),
+}
+
+impl<T> Partial<T> {
+    pub fn is_complete(&self) -> bool {
+        matches!(*self, Partial::Complete(..))
+    }
+
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Partial<U> {
+        match self {
+            Partial::Complete(record, len) => Partial::Complete(f(record), len),
+            Partial::Incomplete(needed) => Partial::Incomplete(needed),
+        }
+    }
+}
+
+/// Decodes records that may not have fully arrived yet, for non-blocking
+/// reads where the last record of a read straddles the next one. Nothing is
+/// consumed on `Incomplete`, so once more bytes are in the caller just tries
+/// again with the longer buffer. The record is only decoded once its bytes
+/// are all there, so a half record never reaches `Deserialize`.
+pub struct PartialDecoder<'s> {
+    schema: &'s Schema,
+}
+
+impl<'s> PartialDecoder<'s> {
+    pub fn new(schema: &'s Schema) -> Self {
+        PartialDecoder { schema }
+    }
+
+    pub fn schema(&self) -> &'s Schema {
+        self.schema
+    }
+
+    /// Finds the length of the record at the front of `buf` without decoding it
+    pub fn record_len(&self, buf: &[u8]) -> Result<Partial<()>, AvroError> {
+        let mut walk = Walk::new(buf);
+        match walk.record(self.schema) {
+            Ok(()) => Ok(Partial::Complete((), walk.pos)),
+            Err(Stop::Short(needed)) => Ok(Partial::Incomplete(needed)),
+            Err(Stop::Bad(err)) => Err(err),
+        }
+    }
+
+    pub fn decode<'de, T: Deserialize<'de>>(&self, buf: &'de [u8]) -> Result<Partial<T>, AvroError>
+        where 's: 'de {
+        match self.record_len(buf)? {
+            Partial::Complete((), len) => {
+                let mut de = AvroDeserializer::from_slice(self.schema, &buf[..len]);
+                Ok(Partial::Complete(T::deserialize(&mut de)?, len))
+            },
+            Partial::Incomplete(needed) => Ok(Partial::Incomplete(needed)),
+        }
+    }
+
+    pub fn decode_value(&self, buf: &[u8]) -> Result<Partial<Value>, AvroError> {
+        match self.record_len(buf)? {
+            Partial::Complete((), len) => Ok(Partial::Complete(DatumReader::new(self.schema).from_slice(&buf[..len])?, len)),
+            Partial::Incomplete(needed) => Ok(Partial::Incomplete(needed)),
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::cell::Cell;
+
+use serde::de::{ Deserialize, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, Visitor };
+
+use super::super::*;
+
+/// A schema compiled against the struct it'll be decoded into. Every schema
+/// field knows up front which struct field it fills, so identifiers go to
+/// serde by index rather than by name, and fields the struct doesn't have are
+/// skipped by a precomputed program instead of being decoded. Union branch
+/// tables and fixed sizes are worked out once too, rather than by comparing
+/// `SchemaFieldType`s on every record.
+///
+/// Plans are opt in: `AvroDeserializer::from_slice` and `from_reader` don't
+/// build one and keep looking fields up in the schema. Build one per schema
+/// and type, keep it as long as that type is being decoded, and decode with
+/// `AvroDeserializer::with_plan`.
+pub struct DecodePlan {
+    schema: Schema,
+    target: &'static [&'static str],
+    pub(crate) fields: Vec<FieldPlan>,
+}
+
+pub(crate) struct FieldPlan {
+    /// Where the field goes in the target struct, `None` to skip it
+    pub target: Option<usize>,
+    /// How many branches its union has, 1 if it isn't a union
+    pub branches: usize,
+    /// The null branch of a two branch union, for options
+    pub null: Option<usize>,
+    /// The size of a fixed field, which has no length prefix
+    pub fixed: Option<usize>,
+    pub skip: Skip,
+}
+
+/// How to get past a value without decoding it
+pub(crate) enum Skip {
+    Nothing,
+    Bytes(usize),
+    Varint,
+    LengthPrefixed,
+    Union(Vec<Skip>),
+    Array(Box<Skip>),
+    Map(Box<Skip>),
+}
+
+impl DecodePlan {
+    /// Compiles `schema` for decoding into `T`, whose field list is found by
+    /// starting to deserialize one
+    pub fn new<'de, T: Deserialize<'de>>(schema: &Schema) -> Result<Self, AvroError> {
+        let fields = Cell::new(None);
+        let _ = T::deserialize(FieldsProbe { fields: &fields });
+        match fields.get() {
+            Some(fields) => DecodePlan::with_fields(schema, fields),
+            None => Err(AvroError{ reason: "only structs can be decoded with a plan".into() }),
+        }
+    }
+
+    /// Compiles `schema` for decoding into a struct with these fields, as
+    /// serde passes them to `deserialize_struct`
+    pub fn with_fields(schema: &Schema, target: &'static [&'static str]) -> Result<Self, AvroError> {
+        let fields = schema.fields.iter()
+            .map(|field| FieldPlan::compile(field, target))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(DecodePlan { schema: schema.clone(), target, fields })
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Whether serde's field list for a struct is the one this plan was compiled for
+    pub(crate) fn targets(&self, fields: &'static [&'static str]) -> bool {
+        std::ptr::eq(self.target, fields) || self.target == fields
+    }
+}
+
+impl FieldPlan {
+    fn compile(field: &SchemaField, target: &[&str]) -> Result<Self, AvroError> {
+        let fail = |err: AvroError| AvroError{ reason: format!("{}: {}", field.name, err.reason) };
+        let null = match field.types[..] {
+            [SchemaFieldType::Primitive(Primitive::Null), _] => Some(0),
+            [_, SchemaFieldType::Primitive(Primitive::Null)] => Some(1),
+            _ => None,
+        };
+        let fixed = match field.types[..] {
+            [SchemaFieldType::Complex(Complex::Fixed { size, .. })] => Some(size),
+            [SchemaFieldType::Primitive(Primitive::Uint64T)] | [SchemaFieldType::Primitive(Primitive::Int64T)] => Some(8),
+            _ => None,
+        };
+        let skip = if field.types.len() == 1 {
+            Skip::compile(&field.types[0]).map_err(fail)?
+        } else {
+            Skip::Union(field.types.iter().map(Skip::compile).collect::<Result<Vec<_>, _>>().map_err(fail)?)
+        };
+
+        Ok(FieldPlan {
+            target: target.iter().position(|name| *name == field.name),
+            branches: field.types.len(),
+            null,
+            fixed,
+            skip,
+        })
+    }
+}
+
+impl Skip {
+    fn compile(field_type: &SchemaFieldType) -> Result<Self, AvroError> {
+        let skip = match *field_type {
+            SchemaFieldType::Primitive(ref primitive) => Skip::primitive(primitive),
+            SchemaFieldType::Complex(Complex::Fixed { size, .. }) => Skip::Bytes(size),
+            SchemaFieldType::Complex(Complex::Array { ref items }) => Skip::Array(Box::new(Skip::primitive(&Primitive::element(items)?))),
+            SchemaFieldType::Complex(Complex::Map { ref values }) => Skip::Map(Box::new(Skip::primitive(&Primitive::element(values)?))),
+        };
+        Ok(skip)
+    }
+
+    fn primitive(primitive: &Primitive) -> Self {
+        match *primitive {
+            Primitive::Null => Skip::Nothing,
+            Primitive::Boolean => Skip::Bytes(1),
+            Primitive::Int | Primitive::Long => Skip::Varint,
+            Primitive::Float => Skip::Bytes(4),
+            Primitive::Double | Primitive::Uint64T | Primitive::Int64T => Skip::Bytes(8),
+            Primitive::Bytes | Primitive::String => Skip::LengthPrefixed,
+        }
+    }
+
+    pub fn skip(&self, de: &mut AvroDeserializer) -> Result<(), AvroError> {
+        match *self {
+            Skip::Nothing => {},
+            Skip::Bytes(len) => { de.take(len)?; },
+            Skip::Varint => { de.visit_long()?; },
+            Skip::LengthPrefixed => { de.visit_borrow_bytes()?; },
+            Skip::Union(ref branches) => {
+                let branch = de.visit_long()?;
+                match branches.get(branch as usize) {
+                    Some(skip) if branch >= 0 => skip.skip(de)?,
+                    _ => return Err(AvroError{ reason: format!("union branch {} is out of scope, max is {}", branch, branches.len()) }),
+                }
+            },
+            Skip::Array(ref item) => {
+                let mut remaining = de.visit_block_len()?;
+                while remaining > 0 {
+                    // a block of nulls is only its count
+                    if !matches!(**item, Skip::Nothing) {
+                        for _ in 0..remaining {
+                            item.skip(de)?;
+                        }
+                    }
+                    remaining = de.visit_block_len()?;
+                }
+            },
+            Skip::Map(ref value) => {
+                let mut remaining = de.visit_block_len()?;
+                while remaining > 0 {
+                    for _ in 0..remaining {
+                        de.visit_borrow_bytes()?;
+                        value.skip(de)?;
+                    }
+                    remaining = de.visit_block_len()?;
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Hands a planned record's fields to serde by their index in the target
+/// struct, skipping the ones it doesn't have
+pub struct AvroPlannedMapAccess<'a, 'de: 'a> {
+    pub de: &'a mut AvroDeserializer<'de>,
+    pub plan: &'de DecodePlan,
+}
+
+impl<'de, 'a> MapAccess<'de> for AvroPlannedMapAccess<'a, 'de> {
+    type Error = AvroError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+        where K: DeserializeSeed<'de> {
+        loop {
+            let next = self.de.current_field_index.map_or(0, |index| index + 1);
+            let field = match self.plan.fields.get(next) {
+                Some(field) => field,
+                None => return Ok(None),
+            };
+            self.de.current_field_index = Some(next);
+            match field.target {
+                Some(target) => return seed.deserialize(IntoDeserializer::<AvroError>::into_deserializer(target as u64)).map(Some),
+                None => field.skip.skip(self.de)?,
+            }
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+        where V: DeserializeSeed<'de> {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+// Catches the field list serde passes to `deserialize_struct`, then bails
+struct FieldsProbe<'a> {
+    fields: &'a Cell<Option<&'static [&'static str]>>,
+}
+
+impl<'de, 'a> Deserializer<'de> for FieldsProbe<'a> {
+    type Error = AvroError;
+
+    fn deserialize_any<V>(self, _: V) -> Result<V::Value, Self::Error> where V: Visitor<'de> {
+        Err(AvroError{ reason: "not a struct".into() })
+    }
+
+    fn deserialize_struct<V>(self, _name: &'static str, fields: &'static [&'static str], _: V) -> Result<V::Value, Self::Error>
+        where V: Visitor<'de> {
+        self.fields.set(Some(fields));
+        Err(AvroError{ reason: "probed".into() })
+    }
+
+    forward_to_deserialize_any!{
+        <V: Visitor<'de>>
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option unit unit_struct
+        newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
@@ -0,0 +1,135 @@
+use std::io::{ self, Read };
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+
+use super::super::*;
+use super::walk::{ ReadSource, Stop, Walk };
+
+/// Decodes records straight off an `io::Read` such as a socket, a pipe or a
+/// decompressor. Each record's bytes are pulled as the schema asks for them
+/// and owned types are deserialized from those, so nothing beyond the record
+/// in hand is read. Borrowing from a slice with `AvroDeserializer` stays the
+/// faster path when the whole buffer is already in memory.
+pub struct ReadDeserializer<'s, R> {
+    reader: R,
+    schema: &'s Schema,
+    scratch: Vec<u8>,
+    offset: u64,
+}
+
+impl<'s, R: Read> ReadDeserializer<'s, R> {
+    pub fn new(schema: &'s Schema, reader: R) -> Self {
+        ReadDeserializer {
+            reader,
+            schema,
+            scratch: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    pub fn schema(&self) -> &'s Schema {
+        self.schema
+    }
+
+    /// How many bytes the records so far took off the reader
+    pub fn byte_offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads the next record, or `None` if the reader ended cleanly before
+    /// one started. Ending partway through a record is an error.
+    pub fn next_record<T: DeserializeOwned>(&mut self) -> Result<Option<T>, AvroError> {
+        if !self.fill()? {
+            return Ok(None)
+        }
+        let mut de = AvroDeserializer::from_slice(self.schema, &self.scratch[..]);
+        T::deserialize(&mut de).map(Some)
+    }
+
+    pub fn next_value(&mut self) -> Result<Option<Value>, AvroError> {
+        if !self.fill()? {
+            return Ok(None)
+        }
+        DatumReader::new(self.schema).from_slice(&self.scratch[..]).map(Some)
+    }
+
+    /// Iterates over the rest of the records, stopping after the first error
+    pub fn records<T: DeserializeOwned>(self) -> ReadRecords<'s, R, T> {
+        ReadRecords { de: self, failed: false, phantom: PhantomData }
+    }
+
+    // Pulls the next record's bytes into the scratch buffer, false if the
+    // reader is already at its end
+    fn fill(&mut self) -> Result<bool, AvroError> {
+        self.scratch.clear();
+        let mut first = [0u8];
+        loop {
+            match self.reader.read(&mut first) {
+                Ok(0) => return Ok(false),
+                Ok(_) => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        self.scratch.push(first[0]);
+
+        let walked = Walk::new(ReadSource { reader: &mut self.reader, buf: &mut self.scratch }).record(self.schema);
+        let reason = match walked {
+            Ok(()) => None,
+            Err(Stop::Short(missing)) => Some(format!("reader ended, needed {} more bytes", missing)),
+            Err(Stop::Bad(err)) => Some(err.reason),
+        };
+        if let Some(reason) = reason {
+            return Err(AvroError{ reason: format!("record at offset {}: {}", self.offset, reason) })
+        }
+        self.offset += self.scratch.len() as u64;
+        Ok(true)
+    }
+}
+
+pub struct ReadRecords<'s, R, T> {
+    de: ReadDeserializer<'s, R>,
+    failed: bool,
+    phantom: PhantomData<T>,
+}
+
+impl<'s, R: Read, T: DeserializeOwned> ReadRecords<'s, R, T> {
+    pub fn deserializer(&self) -> &ReadDeserializer<'s, R> {
+        &self.de
+    }
+}
+
+impl<'s, R: Read, T: DeserializeOwned> Iterator for ReadRecords<'s, R, T> {
+    type Item = Result<T, AvroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None
+        }
+        match self.de.next_record() {
+            Ok(record) => record.map(Ok),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            },
+        }
+    }
+}
+
+/// Decodes exactly one record from `reader`
+pub fn from_reader<T: DeserializeOwned, R: Read>(schema: &Schema, reader: R) -> Result<T, AvroError> {
+    match ReadDeserializer::new(schema, reader).next_record()? {
+        Some(record) => Ok(record),
+        None => Err(AvroError{ reason: "reader ended before the record started".into() }),
+    }
+}
+
@@ -4,16 +4,13 @@ use super::*;
 
 pub struct AvroSeqVisitor<'a, 'de: 'a> {
     de: &'a mut AvroDeserializer<'de>,
-    blocks: usize,
+    remaining: usize,
 }
 
 impl<'a, 'de> AvroSeqVisitor<'a, 'de> {
-    pub fn new(de: &'a mut AvroDeserializer<'de>) -> Self {
-        let mut blocks = de.visit_long();
-        if blocks < 0 {
-            blocks *= -1;
-        }
-        Self { de, blocks: blocks as usize }
+    pub fn new(de: &'a mut AvroDeserializer<'de>) -> Result<Self, AvroError> {
+        let remaining = de.visit_block_len()?;
+        Ok(Self { de, remaining })
     }
 }
 
@@ -22,14 +19,20 @@ impl<'a, 'de> SeqAccess<'de> for AvroSeqVisitor<'a, 'de> {
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
         where T: DeserializeSeed<'de> {
-        if self.de.peek() == 0 {
+        if self.remaining == 0 {
             return Ok(None)
         }
 
-        seed.deserialize(&mut *self.de).map(Some)
+        let val = seed.deserialize(&mut *self.de)?;
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            // Block exhausted, pick up the next block's count (0 terminates)
+            self.remaining = self.de.visit_block_len()?;
+        }
+        Ok(Some(val))
     }
 
     fn size_hint(&self) -> Option<usize> {
-        Some(self.blocks)
+        Some(self.remaining)
     }
-}
\ No newline at end of file
+}
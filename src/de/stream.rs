@@ -0,0 +1,80 @@
+use std::marker::PhantomData;
+
+use serde::de::Deserialize;
+
+use super::super::*;
+
+/// Decodes records laid back to back in one buffer with nothing between
+/// them, like `serde_json::StreamDeserializer`. Iteration stops at the end
+/// of the buffer; bytes that don't make a whole record are an error, after
+/// which the iterator is done. So is a record that takes no bytes, since the
+/// rest of the buffer would never be read.
+pub struct StreamDeserializer<'de, T> {
+    de: AvroDeserializer<'de>,
+    len: usize,
+    failed: bool,
+    phantom: PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de>> StreamDeserializer<'de, T> {
+    pub fn new(schema: &'de Schema, buf: &'de [u8]) -> Self {
+        StreamDeserializer {
+            de: AvroDeserializer::from_slice(schema, buf),
+            len: buf.len(),
+            failed: false,
+            phantom: PhantomData,
+        }
+    }
+
+    /// How far into the buffer the records so far went, which is where the
+    /// next one starts
+    pub fn byte_offset(&self) -> usize {
+        self.len - self.de.buf.len()
+    }
+
+    /// Pairs each record with the offset it starts at
+    pub fn with_offsets(self) -> WithOffsets<'de, T> {
+        WithOffsets { stream: self }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Iterator for StreamDeserializer<'de, T> {
+    type Item = Result<T, AvroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.de.buf.is_empty() {
+            return None
+        }
+
+        let offset = self.byte_offset();
+        let rest = self.de.buf;
+        self.de.current_field_index = None;
+        match T::deserialize(&mut self.de) {
+            // a schema whose records take no bytes would never get through the buffer
+            Ok(_) if self.de.buf.len() == rest.len() => {
+                self.failed = true;
+                Some(Err(AvroError{ reason: format!("{} bytes at offset {} aren't a record: the record took no bytes", rest.len(), offset) }))
+            },
+            Ok(record) => Some(Ok(record)),
+            Err(err) => {
+                self.failed = true;
+                // leave the offset at the start of the bad bytes
+                self.de.buf = rest;
+                Some(Err(AvroError{ reason: format!("{} bytes at offset {} aren't a record: {}", rest.len(), offset, err.reason) }))
+            },
+        }
+    }
+}
+
+pub struct WithOffsets<'de, T> {
+    stream: StreamDeserializer<'de, T>,
+}
+
+impl<'de, T: Deserialize<'de>> Iterator for WithOffsets<'de, T> {
+    type Item = Result<(usize, T), AvroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.stream.byte_offset();
+        self.stream.next().map(|record| record.map(|record| (offset, record)))
+    }
+}
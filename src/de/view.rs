@@ -0,0 +1,195 @@
+use std::str;
+
+use serde::de::Deserialize;
+
+use super::super::*;
+use super::deserializer::eof;
+use super::walk::{ Stop, Walk };
+
+/// A record left encoded in its buffer. Fields are found on demand by
+/// skipping over the ones ahead of them, and handed out as `FieldView`s that
+/// borrow from the buffer, so a filter can look at one field and decide
+/// whether the rest are worth decoding. Where fields start is remembered,
+/// asking again or for a later field doesn't skip from the top.
+pub struct RecordView<'de> {
+    schema: &'de Schema,
+    buf: &'de [u8],
+    /// Where each field found so far starts, plus where the last one ends
+    offsets: Vec<usize>,
+}
+
+impl<'de> RecordView<'de> {
+    pub fn new(schema: &'de Schema, buf: &'de [u8]) -> Self {
+        RecordView { schema, buf, offsets: vec![0] }
+    }
+
+    pub fn schema(&self) -> &'de Schema {
+        self.schema
+    }
+
+    pub fn field(&mut self, index: usize) -> Result<FieldView<'de>, AvroError> {
+        if index >= self.schema.fields.len() {
+            return Err(AvroError{ reason: format!("schema {} only has {} fields", self.schema.name, self.schema.fields.len()) })
+        }
+        self.find(index + 1)?;
+        let bytes = &self.buf[self.offsets[index]..self.offsets[index + 1]];
+        Ok(FieldView { schema: self.schema, index, bytes })
+    }
+
+    pub fn field_named(&mut self, name: &str) -> Result<FieldView<'de>, AvroError> {
+        match self.schema.fields.iter().position(|field| field.name == name) {
+            Some(index) => self.field(index),
+            None => Err(AvroError{ reason: format!("schema {} has no field {}", self.schema.name, name) }),
+        }
+    }
+
+    /// The length of the whole record, which is where the next one starts
+    /// if they're back to back
+    pub fn record_len(&mut self) -> Result<usize, AvroError> {
+        let fields = self.schema.fields.len();
+        self.find(fields)?;
+        Ok(self.offsets[fields])
+    }
+
+    /// Decodes the whole record
+    pub fn decode<T: Deserialize<'de>>(&mut self) -> Result<T, AvroError> {
+        let len = self.record_len()?;
+        T::deserialize(&mut AvroDeserializer::from_slice(self.schema, &self.buf[..len]))
+    }
+
+    // Skips along until `offsets` has an entry for `index`
+    fn find(&mut self, index: usize) -> Result<(), AvroError> {
+        while self.offsets.len() <= index {
+            let field = self.offsets.len() - 1;
+            let mut walk = Walk { src: self.buf, pos: self.offsets[field] };
+            match walk.field(&self.schema.fields[field].types[..]) {
+                Ok(()) => self.offsets.push(walk.pos),
+                Err(Stop::Short(needed)) => return Err(field_error(self.schema, field, eof(needed))),
+                Err(Stop::Bad(err)) => return Err(field_error(self.schema, field, err)),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn field_error(schema: &Schema, field: usize, err: AvroError) -> AvroError {
+    AvroError{ reason: format!("{}.{}: {}", schema.name, schema.fields[field].name, err.reason) }
+}
+
+/// One field of a `RecordView`, still encoded
+#[derive(Debug, Clone, Copy)]
+pub struct FieldView<'de> {
+    schema: &'de Schema,
+    index: usize,
+    bytes: &'de [u8],
+}
+
+impl<'de> FieldView<'de> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn name(&self) -> &'de str {
+        &self.schema.fields[self.index].name[..]
+    }
+
+    /// The field's encoding, union branch included
+    pub fn bytes(&self) -> &'de [u8] {
+        self.bytes
+    }
+
+    pub fn value(&self) -> Result<Value, AvroError> {
+        read_field(&self.schema.fields[self.index].types[..], &mut self.deserializer())
+    }
+
+    /// Deserializes the field on its own, as it would be as a struct member
+    pub fn decode<T: Deserialize<'de>>(&self) -> Result<T, AvroError> {
+        T::deserialize(&mut self.deserializer())
+    }
+
+    /// The union branch the field took, `None` if it isn't a union
+    pub fn branch(&self) -> Result<Option<usize>, AvroError> {
+        if self.schema.fields[self.index].types.len() == 1 {
+            return Ok(None)
+        }
+        let branch = self.deserializer().visit_long()?;
+        if branch < 0 {
+            return Err(AvroError{ reason: format!("negative union branch {}", branch) })
+        }
+        Ok(Some(branch as usize))
+    }
+
+    pub fn is_null(&self) -> Result<bool, AvroError> {
+        Ok(matches!(self.primitive()?, Some((Primitive::Null, _))))
+    }
+
+    /// The string, if the field holds one
+    pub fn as_str(&self) -> Result<Option<&'de str>, AvroError> {
+        match self.primitive()? {
+            Some((Primitive::String, mut de)) => str::from_utf8(de.visit_borrow_bytes()?)
+                .map(Some)
+                .map_err(|err| AvroError{ reason: format!("invalid utf-8 in string: {}", err) }),
+            _ => Ok(None),
+        }
+    }
+
+    /// The bytes of a bytes or fixed field
+    pub fn as_bytes(&self) -> Result<Option<&'de [u8]>, AvroError> {
+        let mut de = self.branch_deserializer()?;
+        let bytes = match *self.branch_type()? {
+            SchemaFieldType::Primitive(Primitive::Bytes) => de.visit_borrow_bytes()?,
+            SchemaFieldType::Primitive(Primitive::Uint64T) | SchemaFieldType::Primitive(Primitive::Int64T) => de.buf,
+            SchemaFieldType::Complex(Complex::Fixed { .. }) => de.buf,
+            _ => return Ok(None),
+        };
+        Ok(Some(bytes))
+    }
+
+    /// The number, if the field holds an int or a long
+    pub fn as_long(&self) -> Result<Option<i64>, AvroError> {
+        match self.primitive()? {
+            Some((Primitive::Int, mut de)) | Some((Primitive::Long, mut de)) => Ok(Some(de.visit_long()?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// The number, if the field holds any kind of number
+    pub fn as_double(&self) -> Result<Option<f64>, AvroError> {
+        match self.primitive()? {
+            Some((Primitive::Int, mut de)) | Some((Primitive::Long, mut de)) => Ok(Some(de.visit_long()? as f64)),
+            Some((Primitive::Float, mut de)) => Ok(Some(de.visit_f32()? as f64)),
+            Some((Primitive::Double, mut de)) => Ok(Some(de.visit_f64()?)),
+            _ => Ok(None),
+        }
+    }
+
+    fn deserializer(&self) -> AvroDeserializer<'de> {
+        AvroDeserializer { buf: self.bytes, schema: self.schema, current_field_index: Some(self.index), plan: None }
+    }
+
+    // The type of the branch the field took
+    fn branch_type(&self) -> Result<&'de SchemaFieldType, AvroError> {
+        let types = &self.schema.fields[self.index].types;
+        match self.branch()? {
+            None => Ok(&types[0]),
+            Some(branch) => types.get(branch)
+                .ok_or_else(|| AvroError{ reason: format!("union branch {} is out of scope, max is {}", branch, types.len()) }),
+        }
+    }
+
+    // A deserializer past the union branch, if there is one
+    fn branch_deserializer(&self) -> Result<AvroDeserializer<'de>, AvroError> {
+        let mut de = self.deserializer();
+        if self.schema.fields[self.index].types.len() > 1 {
+            de.visit_long()?;
+        }
+        Ok(de)
+    }
+
+    fn primitive(&self) -> Result<Option<(Primitive, AvroDeserializer<'de>)>, AvroError> {
+        match *self.branch_type()? {
+            SchemaFieldType::Primitive(ref primitive) => Ok(Some((primitive.clone(), self.branch_deserializer()?))),
+            _ => Ok(None),
+        }
+    }
+}
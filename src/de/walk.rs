@@ -0,0 +1,176 @@
+use std::io::{ self, Read };
+
+use super::super::*;
+
+/// Why a walk over a record stopped short of its end
+pub(crate) enum Stop {
+    /// The bytes ran out this many short, as far as the walk got
+    Short(usize),
+    Bad(AvroError),
+}
+
+impl From<AvroError> for Stop {
+    fn from(err: AvroError) -> Self {
+        Stop::Bad(err)
+    }
+}
+
+impl From<io::Error> for Stop {
+    fn from(err: io::Error) -> Self {
+        Stop::Bad(err.into())
+    }
+}
+
+/// Where a walk gets its bytes from
+pub(crate) trait Source {
+    /// Makes the first `end` bytes available
+    fn need(&mut self, end: usize) -> Result<(), Stop>;
+    fn bytes(&self) -> &[u8];
+}
+
+impl Source for &[u8] {
+    fn need(&mut self, end: usize) -> Result<(), Stop> {
+        if end > self.len() {
+            return Err(Stop::Short(end - self.len()))
+        }
+        Ok(())
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+/// Pulls bytes off a reader onto the end of `buf` as the walk asks for them
+pub(crate) struct ReadSource<'a, R: 'a> {
+    pub reader: &'a mut R,
+    pub buf: &'a mut Vec<u8>,
+}
+
+impl<'a, R: Read> Source for ReadSource<'a, R> {
+    fn need(&mut self, end: usize) -> Result<(), Stop> {
+        if end > self.buf.len() {
+            let missing = (end - self.buf.len()) as u64;
+            // read through `take` so a corrupt length can't make us allocate it up front
+            let got = self.reader.by_ref().take(missing).read_to_end(self.buf)? as u64;
+            if got < missing {
+                return Err(Stop::Short((missing - got) as usize))
+            }
+        }
+        Ok(())
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.buf
+    }
+}
+
+/// Walks a record's encoding following the schema without decoding it,
+/// to find where it ends
+pub(crate) struct Walk<S> {
+    pub src: S,
+    pub pos: usize,
+}
+
+impl<S: Source> Walk<S> {
+    pub fn new(src: S) -> Self {
+        Walk { src, pos: 0 }
+    }
+
+    pub fn record(&mut self, schema: &Schema) -> Result<(), Stop> {
+        schema.fields.iter().try_for_each(|field| self.field(&field.types[..]))
+    }
+
+    fn need(&mut self, len: usize) -> Result<(), Stop> {
+        self.src.need(self.pos + len)?;
+        self.pos += len;
+        Ok(())
+    }
+
+    fn long(&mut self) -> Result<i64, Stop> {
+        let start = self.pos;
+        loop {
+            self.need(1)?;
+            if self.src.bytes()[self.pos - 1] & 0x80 == 0 {
+                break
+            }
+            if self.pos - start >= 10 {
+                return Err(AvroError{ reason: "varint is longer than 10 bytes".into() }.into())
+            }
+        }
+        let (val, _) = integer_encoding::VarInt::decode_var(&self.src.bytes()[start..self.pos]);
+        Ok(val)
+    }
+
+    fn len(&mut self) -> Result<usize, Stop> {
+        let len = self.long()?;
+        if len < 0 {
+            return Err(AvroError{ reason: format!("negative length {}", len) }.into())
+        }
+        Ok(len as usize)
+    }
+
+    fn block_len(&mut self) -> Result<usize, Stop> {
+        let count = self.long()?;
+        if count < 0 {
+            self.long()?;
+        }
+        Ok(count.unsigned_abs() as usize)
+    }
+
+    pub fn field(&mut self, types: &[SchemaFieldType]) -> Result<(), Stop> {
+        if types.len() == 1 {
+            return self.value(&types[0])
+        }
+        let index = self.long()?;
+        match types.get(index as usize) {
+            Some(branch) if index >= 0 => self.value(branch),
+            _ => Err(AvroError{ reason: format!("union branch {} is out of scope, max is {}", index, types.len()) }.into()),
+        }
+    }
+
+    fn value(&mut self, field_type: &SchemaFieldType) -> Result<(), Stop> {
+        match *field_type {
+            SchemaFieldType::Primitive(ref primitive) => match *primitive {
+                Primitive::Null => Ok(()),
+                Primitive::Boolean => self.need(1),
+                Primitive::Int | Primitive::Long => self.long().map(|_| ()),
+                Primitive::Float => self.need(4),
+                Primitive::Double | Primitive::Uint64T | Primitive::Int64T => self.need(8),
+                Primitive::Bytes | Primitive::String => {
+                    let len = self.len()?;
+                    self.need(len)
+                },
+            },
+            SchemaFieldType::Complex(Complex::Fixed { size, .. }) => self.need(size),
+            SchemaFieldType::Complex(Complex::Map { ref values }) => {
+                let values = SchemaFieldType::element(values)?;
+                let mut remaining = self.block_len()?;
+                while remaining > 0 {
+                    for _ in 0..remaining {
+                        let len = self.len()?;
+                        self.need(len)?;
+                        self.value(&values)?;
+                    }
+                    remaining = self.block_len()?;
+                }
+                Ok(())
+            },
+            SchemaFieldType::Complex(Complex::Array { ref items }) => {
+                let items = SchemaFieldType::element(items)?;
+                // nulls take no bytes, so a block of them is only its count
+                let nulls = items == SchemaFieldType::Primitive(Primitive::Null);
+                let mut remaining = self.block_len()?;
+                while remaining > 0 {
+                    if !nulls {
+                        for _ in 0..remaining {
+                            self.value(&items)?;
+                        }
+                    }
+                    remaining = self.block_len()?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
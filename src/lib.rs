@@ -8,16 +8,50 @@ extern crate byteorder;
 #[macro_use] extern crate log;
 extern crate test;
 
-extern crate serde_json;
+#[macro_use] extern crate serde_json;
 
 extern crate integer_encoding;
 
+extern crate rand;
+
+#[cfg(feature = "deflate")] extern crate flate2;
+#[cfg(feature = "snappy")] extern crate snap;
+#[cfg(feature = "snappy")] extern crate crc32fast;
+#[cfg(feature = "zstandard")] extern crate zstd;
+#[cfg(feature = "bzip2")] extern crate bzip2;
+#[cfg(feature = "xz")] extern crate xz2;
+#[cfg(feature = "mmap")] extern crate memmap2;
+#[cfg(feature = "async")] extern crate tokio;
+#[cfg(feature = "async")] extern crate tokio_util;
+#[cfg(feature = "async")] extern crate bytes;
+#[cfg(feature = "async")] extern crate futures_core;
+#[cfg(feature = "arrow")] extern crate arrow_array;
+#[cfg(feature = "arrow")] extern crate arrow_buffer;
+#[cfg(feature = "arrow")] extern crate arrow_schema;
+#[cfg(feature = "parquet")] extern crate arrow_cast;
+#[cfg(feature = "parquet")] extern crate arrow_select;
+#[cfg(feature = "parquet")] extern crate parquet;
+
 mod schema;
 pub use schema::*;
 
 mod de;
 pub use de::*;
 
+mod ser;
+pub use ser::*;
+
+mod datum;
+pub use datum::*;
+
+pub mod container;
+
+pub mod columnar;
+
+pub mod message;
+
+pub mod registry;
+
 
 // Temporary while I'm on the plane!
 pub mod cdr;
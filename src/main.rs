@@ -149,8 +149,8 @@ fn main() {
     let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
 
     for _ in 1..1000000000 {
-        let mut deserializer = avvy::AvroDeserializer{ buf: test, current_field_index: None, schema: &schema};
-        deserializer.skip(5);
+        let mut deserializer = avvy::AvroDeserializer::from_slice(&schema, test);
+        deserializer.read_confluent_header().unwrap();
         UT::deserialize(&mut deserializer).unwrap();
     }
 }
@@ -0,0 +1,115 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use bytes::{ Bytes, BytesMut };
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use tokio_util::codec::{ Decoder, Encoder, LengthDelimitedCodec };
+
+use super::super::*;
+use super::confluent::ConfluentEncoder;
+use super::decoder::{ ConfluentDecoder, ConfluentError };
+use super::single_object::decode_datum;
+
+/// A tokio codec for bare datums of one schema, each behind a 4 byte big
+/// endian length, the framing `LengthDelimitedCodec` uses by default.
+pub struct DatumCodec<T> {
+    schema: Schema,
+    frames: LengthDelimitedCodec,
+    phantom: PhantomData<T>,
+}
+
+impl<T> DatumCodec<T> {
+    pub fn new(schema: &Schema) -> Self {
+        DatumCodec { schema: schema.clone(), frames: LengthDelimitedCodec::new(), phantom: PhantomData }
+    }
+
+    /// Refuses frames longer than `len`, 8MB unless set
+    pub fn max_frame_length(mut self, len: usize) -> Self {
+        self.frames.set_max_frame_length(len);
+        self
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+impl<T: DeserializeOwned> Decoder for DatumCodec<T> {
+    type Item = T;
+    type Error = AvroError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>, AvroError> {
+        match self.frames.decode(src)? {
+            Some(frame) => decode_datum(&self.schema, &frame[..], |de| T::deserialize(de)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T: Serialize> Encoder<T> for DatumCodec<T> {
+    type Error = AvroError;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), AvroError> {
+        let datum = to_vec(&self.schema, &item)?;
+        self.frames.encode(Bytes::from(datum), dst)?;
+        Ok(())
+    }
+}
+
+/// A tokio codec for Confluent framed messages behind a 4 byte big endian
+/// length. Decoding goes through a `ConfluentDecoder`, which connections
+/// can share; encoding needs the writer schema and its id.
+pub struct ConfluentCodec<T> {
+    decoder: Arc<ConfluentDecoder>,
+    writer: Option<(Schema, u32)>,
+    frames: LengthDelimitedCodec,
+    phantom: PhantomData<T>,
+}
+
+impl<T> ConfluentCodec<T> {
+    pub fn new(decoder: Arc<ConfluentDecoder>) -> Self {
+        ConfluentCodec { decoder, writer: None, frames: LengthDelimitedCodec::new(), phantom: PhantomData }
+    }
+
+    /// Encodes with `schema`, registered under `schema_id`
+    pub fn writer(mut self, schema: &Schema, schema_id: u32) -> Self {
+        self.writer = Some((schema.clone(), schema_id));
+        self
+    }
+
+    /// Refuses frames longer than `len`, 8MB unless set
+    pub fn max_frame_length(mut self, len: usize) -> Self {
+        self.frames.set_max_frame_length(len);
+        self
+    }
+
+    pub fn decoder(&self) -> &ConfluentDecoder {
+        &self.decoder
+    }
+}
+
+impl<T: DeserializeOwned> Decoder for ConfluentCodec<T> {
+    type Item = T;
+    type Error = ConfluentError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>, ConfluentError> {
+        match self.frames.decode(src)? {
+            Some(frame) => self.decoder.decode(&frame[..]).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T: Serialize> Encoder<T> for ConfluentCodec<T> {
+    type Error = ConfluentError;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), ConfluentError> {
+        let message = match self.writer {
+            Some((ref schema, schema_id)) => ConfluentEncoder::new(schema, schema_id).encode(&item)?,
+            None => return Err(AvroError{ reason: "no writer schema to encode with".into() }.into()),
+        };
+        self.frames.encode(Bytes::from(message), dst)?;
+        Ok(())
+    }
+}
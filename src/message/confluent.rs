@@ -0,0 +1,96 @@
+use serde::ser::Serialize;
+
+use super::super::*;
+use super::super::container::read_long;
+
+use byteorder::{ BigEndian, ByteOrder };
+
+pub const CONFLUENT_MAGIC: u8 = 0;
+pub const CONFLUENT_HEADER_SIZE: usize = 5;
+
+/// Splits a Confluent framed message into its schema id and payload
+pub fn read_confluent_header(buf: &[u8]) -> Result<(u32, &[u8]), AvroError> {
+    if buf.len() < CONFLUENT_HEADER_SIZE {
+        return Err(AvroError{ reason: format!("confluent header needs {} bytes, got {}", CONFLUENT_HEADER_SIZE, buf.len()) })
+    }
+    if buf[0] != CONFLUENT_MAGIC {
+        return Err(AvroError{ reason: format!("not a confluent framed message, magic byte was {}", buf[0]) })
+    }
+    Ok((BigEndian::read_u32(&buf[1..CONFLUENT_HEADER_SIZE]), &buf[CONFLUENT_HEADER_SIZE..]))
+}
+
+pub fn write_confluent_header(schema_id: u32, buf: &mut Vec<u8>) {
+    let mut header = [CONFLUENT_MAGIC; CONFLUENT_HEADER_SIZE];
+    BigEndian::write_u32(&mut header[1..], schema_id);
+    buf.extend_from_slice(&header);
+}
+
+/// Reads the message indexes Protobuf payloads carry after the header: a
+/// count and that many indexes into the schema's nested message types. A
+/// lone 0 is short for `[0]`, the first message type.
+pub fn read_message_indexes(buf: &[u8]) -> Result<(Vec<i32>, &[u8]), AvroError> {
+    let mut rest = buf;
+    let (count, _) = read_long(&mut rest)?;
+    if count == 0 {
+        return Ok((vec![0], rest))
+    }
+    if count < 0 || count as usize > rest.len() {
+        return Err(AvroError{ reason: format!("bad message index count {}", count) })
+    }
+
+    let mut indexes = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (index, _) = read_long(&mut rest)?;
+        indexes.push(index as i32);
+    }
+    Ok((indexes, rest))
+}
+
+pub fn write_message_indexes(indexes: &[i32], buf: &mut Vec<u8>) {
+    if indexes == [0] {
+        return write_long(0, buf)
+    }
+    write_long(indexes.len() as i64, buf);
+    for &index in indexes {
+        write_long(index as i64, buf);
+    }
+}
+
+impl<'de> AvroDeserializer<'de> {
+    /// Checks and skips the Confluent header, returning the schema id
+    pub fn read_confluent_header(&mut self) -> Result<u32, AvroError> {
+        let (schema_id, rest) = read_confluent_header(self.buf)?;
+        self.buf = rest;
+        Ok(schema_id)
+    }
+}
+
+/// Writes Confluent framed messages for a schema registered under `schema_id`
+pub struct ConfluentEncoder<'s> {
+    schema: &'s Schema,
+    schema_id: u32,
+}
+
+impl<'s> ConfluentEncoder<'s> {
+    pub fn new(schema: &'s Schema, schema_id: u32) -> Self {
+        ConfluentEncoder { schema, schema_id }
+    }
+
+    pub fn schema_id(&self) -> u32 {
+        self.schema_id
+    }
+
+    pub fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, AvroError> {
+        let mut buf = Vec::new();
+        write_confluent_header(self.schema_id, &mut buf);
+        value.serialize(&mut AvroSerializer::new(self.schema, &mut buf))?;
+        Ok(buf)
+    }
+
+    pub fn encode_value(&self, value: &Value) -> Result<Vec<u8>, AvroError> {
+        let mut buf = Vec::new();
+        write_confluent_header(self.schema_id, &mut buf);
+        DatumWriter::new(self.schema).write(value, &mut buf)?;
+        Ok(buf)
+    }
+}
@@ -0,0 +1,198 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{ Display, Formatter, Error as FmtError };
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{ Arc, RwLock };
+
+use serde::de::{ Deserialize, DeserializeOwned };
+
+use super::super::*;
+use super::confluent::read_confluent_header;
+use super::single_object::{ decode_datum, decode_resolved };
+
+/// Why a `ConfluentDecoder` couldn't decode a message. An unknown schema id
+/// is told apart from bad data since the fix is usually to load the schema.
+#[derive(Debug)]
+pub enum ConfluentError {
+    UnknownSchemaId(u32),
+    Avro(AvroError),
+}
+
+impl From<AvroError> for ConfluentError {
+    fn from(err: AvroError) -> Self {
+        ConfluentError::Avro(err)
+    }
+}
+
+impl From<io::Error> for ConfluentError {
+    fn from(err: io::Error) -> Self {
+        ConfluentError::Avro(err.into())
+    }
+}
+
+impl From<ConfluentError> for AvroError {
+    fn from(err: ConfluentError) -> Self {
+        match err {
+            ConfluentError::Avro(err) => err,
+            err => AvroError{ reason: err.to_string() },
+        }
+    }
+}
+
+impl Error for ConfluentError {
+
+}
+
+impl Display for ConfluentError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), FmtError> {
+        match *self {
+            ConfluentError::UnknownSchemaId(id) => write!(fmt, "unknown schema id {}", id),
+            ConfluentError::Avro(ref err) => write!(fmt, "{}", err),
+        }
+    }
+}
+
+/// Somewhere to fetch writer schemas a `ConfluentDecoder` doesn't have yet,
+/// such as a schema registry
+pub trait SchemaSource {
+    /// `None` when the source has never heard of `id`
+    fn schema_by_id(&self, id: u32) -> Result<Option<Schema>, AvroError>;
+}
+
+enum Writer {
+    /// Written with the reader schema itself
+    Reader,
+    Resolve(Box<Resolution>),
+}
+
+/// Decodes Confluent framed messages written with any of several versions
+/// of a schema, picking the writer schema by the id in the header and
+/// resolving every message to one reader schema. With a `SchemaSource`,
+/// schemas for ids it hasn't seen are fetched on first use.
+pub struct ConfluentDecoder {
+    reader: Schema,
+    writers: RwLock<HashMap<u32, Arc<Writer>>>,
+    source: Option<Box<dyn SchemaSource + Send + Sync>>,
+}
+
+impl ConfluentDecoder {
+    pub fn new(reader: &Schema) -> Self {
+        ConfluentDecoder { reader: reader.clone(), writers: RwLock::new(HashMap::new()), source: None }
+    }
+
+    pub fn source<S: SchemaSource + Send + Sync + 'static>(mut self, source: S) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// Registers the schema messages with `id` were written with. Fails when
+    /// it can't be resolved to the reader schema.
+    pub fn add_schema(&mut self, id: u32, writer: &Schema) -> Result<(), AvroError> {
+        let entry = self.writer_for(id, writer)?;
+        self.writers.get_mut().unwrap().insert(id, entry);
+        Ok(())
+    }
+
+    fn writer_for(&self, id: u32, writer: &Schema) -> Result<Arc<Writer>, AvroError> {
+        if writer.fingerprint() == self.reader.fingerprint() {
+            return Ok(Arc::new(Writer::Reader))
+        }
+        let resolution = Resolution::new(writer, &self.reader)
+            .map_err(|err| AvroError{ reason: format!("schema {}: {}", id, err.reason) })?;
+        Ok(Arc::new(Writer::Resolve(Box::new(resolution))))
+    }
+
+    /// Adds every `<id>.avsc` file in `dir`, returning how many there were
+    pub fn load_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<usize, AvroError> {
+        let mut loaded = 0;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "avsc") {
+                continue
+            }
+            let id = match path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse().ok()) {
+                Some(id) => id,
+                None => {
+                    info!("skipping {}, it isn't named after a schema id", path.display());
+                    continue
+                },
+            };
+
+            let schema = Schema::from_str(&fs::read_to_string(&path)?)
+                .map_err(|err| AvroError{ reason: format!("{} doesn't parse: {}", path.display(), err) })?;
+            self.add_schema(id, &schema)?;
+            loaded += 1;
+        }
+        info!("loaded {} schemas", loaded);
+        Ok(loaded)
+    }
+
+    pub fn reader_schema(&self) -> &Schema {
+        &self.reader
+    }
+
+    pub fn schema_ids(&self) -> Vec<u32> {
+        let mut ids : Vec<u32> = self.writers.read().unwrap().keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn decode_value(&self, buf: &[u8]) -> Result<Value, ConfluentError> {
+        let (id, datum) = read_confluent_header(buf)?;
+        let value = match *self.writer(id)? {
+            Writer::Reader => decode_datum(&self.reader, datum, |de| DatumReader::new(&self.reader).read(de))?,
+            Writer::Resolve(ref resolution) => {
+                let writer = &resolution.writer;
+                resolution.resolve(decode_datum(writer, datum, |de| DatumReader::new(writer).read(de))?)?
+            },
+        };
+        Ok(value)
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, buf: &[u8]) -> Result<T, ConfluentError> {
+        let (id, datum) = read_confluent_header(buf)?;
+        let decoded = match *self.writer(id)? {
+            Writer::Reader => decode_datum(&self.reader, datum, |de| T::deserialize(de))?,
+            Writer::Resolve(ref resolution) => decode_resolved(resolution, datum)?,
+        };
+        Ok(decoded)
+    }
+
+    /// Decodes into a type that borrows from `buf`, which only works for
+    /// messages written with the reader schema itself.
+    pub fn decode_borrowed<'a, T: Deserialize<'a>>(&'a self, buf: &'a [u8]) -> Result<T, ConfluentError> {
+        let (id, datum) = read_confluent_header(buf)?;
+        match *self.writer(id)? {
+            Writer::Reader => Ok(decode_datum(&self.reader, datum, |de| T::deserialize(de))?),
+            Writer::Resolve(_) => Err(AvroError{ reason: format!("schema {} needs resolving, use decode", id) }.into()),
+        }
+    }
+
+    /// The message's datum as the reader schema encodes it, which is the
+    /// message itself unless it has to be resolved from another schema
+    pub fn reader_datum<'a>(&self, buf: &'a [u8]) -> Result<Cow<'a, [u8]>, ConfluentError> {
+        let (id, datum) = read_confluent_header(buf)?;
+        match *self.writer(id)? {
+            Writer::Reader => Ok(Cow::Borrowed(datum)),
+            Writer::Resolve(_) => Ok(Cow::Owned(DatumWriter::new(&self.reader).to_vec(&self.decode_value(buf)?)?)),
+        }
+    }
+
+    fn writer(&self, id: u32) -> Result<Arc<Writer>, ConfluentError> {
+        if let Some(writer) = self.writers.read().unwrap().get(&id) {
+            return Ok(writer.clone())
+        }
+
+        let schema = match self.source {
+            Some(ref source) => source.schema_by_id(id)?,
+            None => None,
+        };
+        let writer = self.writer_for(id, &schema.ok_or(ConfluentError::UnknownSchemaId(id))?)?;
+        info!("fetched schema {}", id);
+        self.writers.write().unwrap().insert(id, writer.clone());
+        Ok(writer)
+    }
+}
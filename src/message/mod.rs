@@ -0,0 +1,13 @@
+mod single_object;
+pub use self::single_object::*;
+
+mod confluent;
+pub use self::confluent::*;
+
+mod decoder;
+pub use self::decoder::*;
+
+#[cfg(feature = "async")]
+mod codec;
+#[cfg(feature = "async")]
+pub use self::codec::*;
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex };
+
+use serde::de::{ Deserialize, DeserializeOwned };
+use serde::ser::Serialize;
+
+use super::super::*;
+
+use byteorder::{ ByteOrder, LittleEndian };
+
+pub const SINGLE_OBJECT_MARKER: [u8; 2] = [0xC3, 0x01];
+pub const SINGLE_OBJECT_HEADER_SIZE: usize = 10;
+
+/// Where a `SingleObjectDecoder` looks up writer schemas by fingerprint
+pub trait SchemaStore {
+    fn schema_by_fingerprint(&self, fingerprint: u64) -> Option<&Schema>;
+}
+
+impl SchemaStore for HashMap<u64, Schema> {
+    fn schema_by_fingerprint(&self, fingerprint: u64) -> Option<&Schema> {
+        self.get(&fingerprint)
+    }
+}
+
+/// Splits a single-object encoded message into the writer schema's
+/// fingerprint and the datum
+pub fn read_single_object_header(buf: &[u8]) -> Result<(u64, &[u8]), AvroError> {
+    if buf.len() < SINGLE_OBJECT_HEADER_SIZE {
+        return Err(AvroError{ reason: format!("single object header needs {} bytes, got {}", SINGLE_OBJECT_HEADER_SIZE, buf.len()) })
+    }
+    if buf[..2] != SINGLE_OBJECT_MARKER {
+        return Err(AvroError{ reason: format!("not a single object encoded message, marker was {:?}", &buf[..2]) })
+    }
+    Ok((LittleEndian::read_u64(&buf[2..SINGLE_OBJECT_HEADER_SIZE]), &buf[SINGLE_OBJECT_HEADER_SIZE..]))
+}
+
+/// Decodes single-object encoded messages, finding the writer schema in a
+/// `SchemaStore`. Given a reader schema, data written with another schema
+/// is resolved to it.
+pub struct SingleObjectDecoder<S> {
+    store: S,
+    reader: Option<(Schema, u64)>,
+    resolutions: Mutex<HashMap<u64, Arc<Resolution>>>,
+}
+
+impl<S: SchemaStore> SingleObjectDecoder<S> {
+    pub fn new(store: S) -> Self {
+        SingleObjectDecoder {
+            store,
+            reader: None,
+            resolutions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn reader_schema(mut self, schema: &Schema) -> Self {
+        self.reader = Some((schema.clone(), schema.fingerprint()));
+        self
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn decode_value(&self, buf: &[u8]) -> Result<Value, AvroError> {
+        let (fingerprint, datum) = read_single_object_header(buf)?;
+        let writer = self.writer_schema(fingerprint)?;
+        let value = decode_datum(writer, datum, |de| DatumReader::new(writer).read(de))?;
+
+        match self.resolution(fingerprint)? {
+            Some(resolution) => resolution.resolve(value),
+            None => Ok(value),
+        }
+    }
+
+    /// Decodes into an owned type, resolving to the reader schema if needed
+    pub fn decode<T: DeserializeOwned>(&self, buf: &[u8]) -> Result<T, AvroError> {
+        let (fingerprint, datum) = read_single_object_header(buf)?;
+        let writer = self.writer_schema(fingerprint)?;
+
+        match self.resolution(fingerprint)? {
+            Some(resolution) => decode_resolved(&resolution, datum),
+            None => decode_datum(writer, datum, |de| T::deserialize(de)),
+        }
+    }
+
+    /// Decodes into a type that borrows from `buf`, which only works when the
+    /// message was written with the reader schema, or there's no reader schema.
+    pub fn decode_borrowed<'a, T: Deserialize<'a>>(&'a self, buf: &'a [u8]) -> Result<T, AvroError> {
+        let (fingerprint, datum) = read_single_object_header(buf)?;
+        let writer = self.writer_schema(fingerprint)?;
+        if self.resolution(fingerprint)?.is_some() {
+            return Err(AvroError{ reason: format!("message was written with schema {:016x}, which needs resolving, use decode", fingerprint) })
+        }
+        decode_datum(writer, datum, |de| T::deserialize(de))
+    }
+
+    fn writer_schema(&self, fingerprint: u64) -> Result<&Schema, AvroError> {
+        self.store.schema_by_fingerprint(fingerprint)
+            .ok_or_else(|| AvroError{ reason: format!("no schema with fingerprint {:016x} in the store", fingerprint) })
+    }
+
+    fn resolution(&self, fingerprint: u64) -> Result<Option<Arc<Resolution>>, AvroError> {
+        let reader = match self.reader {
+            Some((_, reader_fingerprint)) if reader_fingerprint == fingerprint => return Ok(None),
+            Some((ref reader, _)) => reader,
+            None => return Ok(None),
+        };
+
+        let mut resolutions = self.resolutions.lock().unwrap();
+        if let Some(resolution) = resolutions.get(&fingerprint) {
+            return Ok(Some(resolution.clone()))
+        }
+        let resolution = Arc::new(Resolution::new(self.writer_schema(fingerprint)?, reader)?);
+        info!("resolving schema {:016x} to {:016x}", fingerprint, reader.fingerprint());
+        resolutions.insert(fingerprint, resolution.clone());
+        Ok(Some(resolution))
+    }
+}
+
+// A message holds exactly one datum
+pub(crate) fn decode_datum<'a, X, F>(schema: &'a Schema, datum: &'a [u8], decode: F) -> Result<X, AvroError>
+    where F: FnOnce(&mut AvroDeserializer<'a>) -> Result<X, AvroError> {
+    let mut de = AvroDeserializer::from_slice(schema, datum);
+    let decoded = decode(&mut de)?;
+    if !de.buf.is_empty() {
+        return Err(AvroError{ reason: format!("{} bytes left after the datum", de.buf.len()) })
+    }
+    Ok(decoded)
+}
+
+// Typed data with a resolution in between goes through a `Value` and back
+// to bytes in the reader's schema
+pub(crate) fn decode_resolved<T: DeserializeOwned>(resolution: &Resolution, datum: &[u8]) -> Result<T, AvroError> {
+    let writer = &resolution.writer;
+    let value = decode_datum(writer, datum, |de| DatumReader::new(writer).read(de))?;
+    let resolved = DatumWriter::new(&resolution.reader).to_vec(&resolution.resolve(value)?)?;
+    decode_datum(&resolution.reader, &resolved[..], |de| T::deserialize(de))
+}
+
+/// Writes single-object encoded messages for one schema
+pub struct SingleObjectEncoder<'s> {
+    schema: &'s Schema,
+    header: [u8; SINGLE_OBJECT_HEADER_SIZE],
+}
+
+impl<'s> SingleObjectEncoder<'s> {
+    pub fn new(schema: &'s Schema) -> Self {
+        let mut header = [0u8; SINGLE_OBJECT_HEADER_SIZE];
+        header[..2].copy_from_slice(&SINGLE_OBJECT_MARKER);
+        LittleEndian::write_u64(&mut header[2..], schema.fingerprint());
+        SingleObjectEncoder { schema, header }
+    }
+
+    pub fn fingerprint(&self) -> u64 {
+        LittleEndian::read_u64(&self.header[2..])
+    }
+
+    pub fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, AvroError> {
+        let mut buf = self.header.to_vec();
+        value.serialize(&mut AvroSerializer::new(self.schema, &mut buf))?;
+        Ok(buf)
+    }
+
+    pub fn encode_value(&self, value: &Value) -> Result<Vec<u8>, AvroError> {
+        let mut buf = self.header.to_vec();
+        DatumWriter::new(self.schema).write(value, &mut buf)?;
+        Ok(buf)
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{ Path, PathBuf };
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde_json;
+
+use super::super::*;
+use super::super::message::SchemaSource;
+use super::http::{ Endpoint, percent_encode };
+
+/// A schema registered under a subject
+#[derive(Debug, Clone)]
+pub struct SubjectVersion {
+    pub subject: String,
+    pub version: u32,
+    pub id: u32,
+    pub schema: Schema,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SchemaBody {
+    schema: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VersionBody {
+    subject: String,
+    version: u32,
+    id: u32,
+    schema: String,
+}
+
+#[derive(Deserialize)]
+struct IdBody {
+    id: u32,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error_code: u32,
+    message: String,
+}
+
+/// Talks to a Confluent compatible schema registry. Schemas are immutable
+/// once registered, so whatever is fetched is kept in memory and, given a
+/// cache directory, on disk, where it's found again when the registry
+/// can't be reached.
+///
+/// Only plain http is supported.
+pub struct RegistryClient {
+    endpoint: Endpoint,
+    timeout: Duration,
+    cache_dir: Option<PathBuf>,
+    ids: Mutex<HashMap<u32, Schema>>,
+    versions: Mutex<HashMap<(String, u32), SubjectVersion>>,
+}
+
+impl RegistryClient {
+    pub fn new(url: &str) -> Result<Self, AvroError> {
+        Ok(RegistryClient {
+            endpoint: Endpoint::parse(url)?,
+            timeout: Duration::from_secs(10),
+            cache_dir: None,
+            ids: Mutex::new(HashMap::new()),
+            versions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Keeps fetched schemas under `dir` as well as in memory
+    pub fn cache_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.cache_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// `GET /schemas/ids/{id}`, `None` if the registry doesn't know the id
+    pub fn schema_by_id(&self, id: u32) -> Result<Option<Schema>, AvroError> {
+        if let Some(schema) = self.ids.lock().unwrap().get(&id) {
+            return Ok(Some(schema.clone()))
+        }
+        let cached = self.cache_path(&["ids", &format!("{}.avsc", id)]);
+        if let Some(schema) = cached.as_ref().and_then(|path| fs::read_to_string(path).ok()) {
+            let schema = parse_schema(&schema)?;
+            self.ids.lock().unwrap().insert(id, schema.clone());
+            return Ok(Some(schema))
+        }
+
+        let body : SchemaBody = match self.get(&format!("/schemas/ids/{}", id))? {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        let schema = parse_schema(&body.schema)?;
+        self.remember(id, &body.schema, &schema);
+        Ok(Some(schema))
+    }
+
+    /// `GET /subjects/{subject}/versions/{version}`, `None` if there's no such
+    /// version. A numbered version is cached, the latest one is always
+    /// fetched and the last one fetched is only used when the registry can't
+    /// be reached.
+    pub fn subject_version(&self, subject: &str, version: Option<u32>) -> Result<Option<SubjectVersion>, AvroError> {
+        let segment = version.map_or("latest".to_owned(), |version| version.to_string());
+        let cached = self.cache_path(&["subjects", &percent_encode(subject), &format!("{}.json", segment)]);
+
+        if let Some(version) = version {
+            if let Some(found) = self.versions.lock().unwrap().get(&(subject.to_owned(), version)) {
+                return Ok(Some(found.clone()))
+            }
+            if let Some(body) = cached.as_ref().and_then(|path| fs::read(path).ok()) {
+                return Ok(Some(self.subject_version_from(parse_json(&body)?, None)?))
+            }
+        }
+
+        let body = match self.get::<VersionBody>(&format!("/subjects/{}/versions/{}", percent_encode(subject), segment)) {
+            Ok(Some(body)) => body,
+            Ok(None) => return Ok(None),
+            Err(err) => match cached.as_ref().and_then(|path| fs::read(path).ok()) {
+                Some(body) => {
+                    info!("registry unreachable, using the cached {}/{}: {}", subject, segment, err);
+                    return Ok(Some(self.subject_version_from(parse_json(&body)?, None)?))
+                },
+                None => return Err(err),
+            },
+        };
+        Ok(Some(self.subject_version_from(body, cached.as_ref())?))
+    }
+
+    /// `POST /subjects/{subject}/versions`, returns the schema's id, which is
+    /// the existing one if it was registered before. `schema` is sent as
+    /// written, so the registry keeps the same text as the `.avsc`.
+    pub fn register(&self, subject: &str, schema: &str) -> Result<u32, AvroError> {
+        let parsed = Schema::from_str(schema).map_err(|err| AvroError{ reason: format!("schema doesn't parse: {}", err) })?;
+        let body = serde_json::to_vec(&SchemaBody { schema: schema.to_owned() }).unwrap();
+        let (status, response) = self.endpoint.request("POST", &format!("/subjects/{}/versions", percent_encode(subject)), Some(&body[..]), self.timeout)?;
+        if status != 200 {
+            return Err(registry_error(status, &response))
+        }
+        let id = parse_json::<IdBody>(&response)?.id;
+        self.remember(id, schema, &parsed);
+        Ok(id)
+    }
+
+    /// `GET /subjects`
+    pub fn subjects(&self) -> Result<Vec<String>, AvroError> {
+        Ok(self.get("/subjects")?.unwrap_or_default())
+    }
+
+    /// `GET /subjects/{subject}/versions`, empty for an unknown subject
+    pub fn versions(&self, subject: &str) -> Result<Vec<u32>, AvroError> {
+        Ok(self.get(&format!("/subjects/{}/versions", percent_encode(subject)))?.unwrap_or_default())
+    }
+
+    /// `POST /compatibility/subjects/{subject}/versions/latest`, whether the
+    /// registry would accept `schema`, sent as written, as the subject's
+    /// next version
+    pub fn is_compatible(&self, subject: &str, schema: &str) -> Result<bool, AvroError> {
+        #[derive(Deserialize)]
+        struct Compatibility {
+            is_compatible: bool,
+        }
+
+        Schema::from_str(schema).map_err(|err| AvroError{ reason: format!("schema doesn't parse: {}", err) })?;
+        let body = serde_json::to_vec(&SchemaBody { schema: schema.to_owned() }).unwrap();
+        let path = format!("/compatibility/subjects/{}/versions/latest", percent_encode(subject));
+        let (status, response) = self.endpoint.request("POST", &path, Some(&body[..]), self.timeout)?;
+        match status {
+            200 => Ok(parse_json::<Compatibility>(&response)?.is_compatible),
+            // nothing to be incompatible with
+            404 => Ok(true),
+            status => Err(registry_error(status, &response)),
+        }
+    }
+
+    // `cache` is where to keep a version that came from the registry, `None`
+    // for one that came out of the cache
+    fn subject_version_from(&self, body: VersionBody, cache: Option<&PathBuf>) -> Result<SubjectVersion, AvroError> {
+        let schema = parse_schema(&body.schema)?;
+        match cache {
+            Some(path) => {
+                write_cache(path, &serde_json::to_vec(&body).unwrap());
+                self.remember(body.id, &body.schema, &schema);
+            },
+            None => { self.ids.lock().unwrap().insert(body.id, schema.clone()); },
+        }
+        let version = SubjectVersion { subject: body.subject, version: body.version, id: body.id, schema };
+        self.versions.lock().unwrap().insert((version.subject.clone(), version.version), version.clone());
+        Ok(version)
+    }
+
+    // Keeps a schema the registry has, caching the text it has for it
+    fn remember(&self, id: u32, text: &str, schema: &Schema) {
+        if let Some(path) = self.cache_path(&["ids", &format!("{}.avsc", id)]) {
+            write_cache(&path, text.as_bytes());
+        }
+        self.ids.lock().unwrap().insert(id, schema.clone());
+    }
+
+    fn get<T: ::serde::de::DeserializeOwned>(&self, path: &str) -> Result<Option<T>, AvroError> {
+        let (status, body) = self.endpoint.request("GET", path, None, self.timeout)?;
+        match status {
+            200 => Ok(Some(parse_json(&body)?)),
+            404 => Ok(None),
+            status => Err(registry_error(status, &body)),
+        }
+    }
+
+    fn cache_path(&self, parts: &[&str]) -> Option<PathBuf> {
+        let mut path = self.cache_dir.clone()?;
+        for part in parts {
+            path.push(part);
+        }
+        Some(path)
+    }
+}
+
+impl SchemaSource for RegistryClient {
+    fn schema_by_id(&self, id: u32) -> Result<Option<Schema>, AvroError> {
+        RegistryClient::schema_by_id(self, id)
+    }
+}
+
+fn parse_schema(schema: &str) -> Result<Schema, AvroError> {
+    Schema::from_str(schema).map_err(|err| AvroError{ reason: format!("registry schema doesn't parse: {}", err) })
+}
+
+fn parse_json<T: ::serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, AvroError> {
+    serde_json::from_slice(body).map_err(|err| AvroError{ reason: format!("bad registry response: {}", err) })
+}
+
+fn registry_error(status: u16, body: &[u8]) -> AvroError {
+    match serde_json::from_slice::<ErrorBody>(body) {
+        Ok(error) => AvroError{ reason: format!("registry returned {}: {} ({})", status, error.message, error.error_code) },
+        Err(_) => AvroError{ reason: format!("registry returned {}: {}", status, String::from_utf8_lossy(body)) },
+    }
+}
+
+// written to the side and renamed, so a reader never sees half a file. A
+// cache that can't be written only costs a fetch later, so it's just logged.
+fn write_cache(path: &Path, contents: &[u8]) {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let written = fs::create_dir_all(path.parent().unwrap())
+        .and_then(|_| fs::write(&partial, contents))
+        .and_then(|_| fs::rename(&partial, path));
+    if let Err(err) = written {
+        warn!("couldn't cache {}: {}", path.display(), err);
+    }
+}
@@ -0,0 +1,216 @@
+use std::io::{ Read, Write };
+use std::net::{ TcpStream, ToSocketAddrs };
+use std::time::Duration;
+
+use super::super::*;
+
+/// Where a registry lives: `host:port` plus any path the API sits under
+#[derive(Debug, Clone)]
+pub(crate) struct Endpoint {
+    pub host: String,
+    pub prefix: String,
+}
+
+impl Endpoint {
+    /// Only plain `http://` urls, there's no TLS in here
+    pub fn parse(url: &str) -> Result<Self, AvroError> {
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            None => return Err(AvroError{ reason: format!("only http:// registries are supported, got {}", url) }),
+        };
+        let (host, prefix) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], rest[slash..].trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        if host.is_empty() {
+            return Err(AvroError{ reason: format!("no host in {}", url) })
+        }
+
+        let host = if host.contains(':') { host.to_owned() } else { format!("{}:80", host) };
+        Ok(Endpoint { host, prefix: prefix.to_owned() })
+    }
+
+    /// Sends one request on a fresh connection, returning the status and body
+    pub fn request(&self, method: &str, path: &str, body: Option<&[u8]>, timeout: Duration) -> Result<(u16, Vec<u8>), AvroError> {
+        let url = format!("http://{}{}{}", self.host, self.prefix, path);
+        info!("{} {}", method, url);
+        self.send(method, path, body, timeout)
+            .map_err(|err| AvroError{ reason: format!("{} {}: {}", method, url, err.reason) })
+    }
+
+    fn send(&self, method: &str, path: &str, body: Option<&[u8]>, timeout: Duration) -> Result<(u16, Vec<u8>), AvroError> {
+        let addr = self.host.to_socket_addrs()?.next()
+            .ok_or_else(|| AvroError{ reason: format!("{} doesn't resolve", self.host) })?;
+        let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let body = body.unwrap_or(&[]);
+        let mut request = format!("{} {}{} HTTP/1.1\r\nHost: {}\r\nAccept: {}\r\nConnection: close\r\n",
+                                  method, self.prefix, path, self.host, CONTENT_TYPE).into_bytes();
+        if method != "GET" {
+            request.extend(format!("Content-Type: {}\r\nContent-Length: {}\r\n", CONTENT_TYPE, body.len()).into_bytes());
+        }
+        request.extend_from_slice(b"\r\n");
+        request.extend_from_slice(body);
+        stream.write_all(&request[..])?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        parse_response(&response[..])
+    }
+}
+
+pub(crate) const CONTENT_TYPE: &str = "application/vnd.schemaregistry.v1+json";
+
+/// Header names are lower-cased
+pub(crate) type Headers = Vec<(String, String)>;
+
+/// Splits an HTTP message into its start line, headers and body
+pub(crate) fn split_message(message: &[u8]) -> Result<(String, Headers, &[u8]), AvroError> {
+    let end = message.windows(4).position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| AvroError{ reason: "http message has no end of headers".into() })?;
+    let head = std::str::from_utf8(&message[..end])
+        .map_err(|_| AvroError{ reason: "http headers aren't utf-8".into() })?;
+
+    let mut lines = head.split("\r\n");
+    let start = lines.next().unwrap_or("").to_owned();
+    let headers = lines
+        .filter_map(|line| {
+            let colon = line.find(':')?;
+            Some((line[..colon].trim().to_lowercase(), line[colon + 1..].trim().to_owned()))
+        })
+        .collect();
+    Ok((start, headers, &message[end + 4..]))
+}
+
+pub(crate) fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|header| header.0 == name).map(|header| &header.1[..])
+}
+
+fn parse_response(response: &[u8]) -> Result<(u16, Vec<u8>), AvroError> {
+    let (status_line, headers, body) = split_message(response)?;
+    let status = status_line.split(' ').nth(1).and_then(|status| status.parse().ok())
+        .ok_or_else(|| AvroError{ reason: format!("bad http status line {:?}", status_line) })?;
+
+    let body = if header(&headers, "transfer-encoding").is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked")) {
+        dechunk(body)?
+    } else {
+        match header(&headers, "content-length").and_then(|len| len.parse::<usize>().ok()) {
+            Some(len) if len <= body.len() => body[..len].to_owned(),
+            Some(len) => return Err(AvroError{ reason: format!("http body is {} bytes, expected {}", body.len(), len) }),
+            None => body.to_owned(),
+        }
+    };
+    Ok((status, body))
+}
+
+fn dechunk(mut body: &[u8]) -> Result<Vec<u8>, AvroError> {
+    let mut out = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|window| window == b"\r\n")
+            .ok_or_else(|| AvroError{ reason: "truncated http chunk".into() })?;
+        let size = std::str::from_utf8(&body[..line_end]).ok()
+            .and_then(|line| usize::from_str_radix(line.split(';').next().unwrap().trim(), 16).ok())
+            .ok_or_else(|| AvroError{ reason: "bad http chunk size".into() })?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(out)
+        }
+        if body.len() < size + 2 {
+            return Err(AvroError{ reason: "truncated http chunk".into() })
+        }
+        out.extend_from_slice(&body[..size]);
+        body = &body[size + 2..];
+    }
+}
+
+/// Escapes a subject for use as a path segment, in urls and on disk
+pub(crate) fn percent_encode(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+pub(crate) fn percent_decode(segment: &str) -> Result<String, AvroError> {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'%' {
+            let byte = segment.get(idx + 1..idx + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| AvroError{ reason: format!("bad escape in {:?}", segment) })?;
+            out.push(byte);
+            idx += 3;
+        } else {
+            out.push(bytes[idx]);
+            idx += 1;
+        }
+    }
+    String::from_utf8(out).map_err(|_| AvroError{ reason: format!("{:?} isn't utf-8", segment) })
+}
+
+/// An HTTP request as the embedded registry sees it
+pub(crate) struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: Vec<u8>,
+}
+
+// requests beyond this are refused rather than buffered
+const MAX_REQUEST: usize = 16 * 1024 * 1024;
+
+pub(crate) fn read_request<R: Read>(reader: &mut R) -> Result<Request, AvroError> {
+    let mut message = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            return Err(AvroError{ reason: "connection closed mid request".into() })
+        }
+        message.extend_from_slice(&buf[..read]);
+        if message.len() > MAX_REQUEST {
+            return Err(AvroError{ reason: "request is too large".into() })
+        }
+
+        if !message.windows(4).any(|window| window == b"\r\n\r\n") {
+            continue
+        }
+        let (start, headers, body) = split_message(&message[..])?;
+        let len = header(&headers, "content-length").and_then(|len| len.parse().ok()).unwrap_or(0);
+        if len > MAX_REQUEST {
+            return Err(AvroError{ reason: "request is too large".into() })
+        }
+        if body.len() < len {
+            continue
+        }
+
+        let mut parts = start.split(' ');
+        let (method, path) = match (parts.next(), parts.next()) {
+            (Some(method), Some(path)) => (method.to_owned(), path.to_owned()),
+            _ => return Err(AvroError{ reason: format!("bad request line {:?}", start) }),
+        };
+        return Ok(Request { method, path, body: body[..len].to_owned() })
+    }
+}
+
+pub(crate) fn write_response<W: Write>(writer: &mut W, status: u16, body: &[u8]) -> Result<(), AvroError> {
+    let reason = match status {
+        200 => "OK",
+        404 => "Not Found",
+        409 => "Conflict",
+        422 => "Unprocessable Entity",
+        _ => "Error",
+    };
+    let head = format!("HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                       status, reason, CONTENT_TYPE, body.len());
+    writer.write_all(head.as_bytes())?;
+    writer.write_all(body)?;
+    writer.flush()?;
+    Ok(())
+}
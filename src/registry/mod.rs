@@ -0,0 +1,7 @@
+mod http;
+
+mod client;
+pub use self::client::*;
+
+mod server;
+pub use self::server::*;
@@ -0,0 +1,311 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::{ SocketAddr, TcpListener, TcpStream, ToSocketAddrs };
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, Mutex };
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::thread::{ self, JoinHandle };
+use std::time::Duration;
+
+use serde_json;
+
+use super::super::*;
+use super::http::{ Request, percent_decode, percent_encode, read_request, write_response };
+
+const IDS_FILE: &str = "ids.json";
+
+#[derive(Serialize, Deserialize)]
+struct IdEntry {
+    id: u32,
+    subject: String,
+    version: u32,
+}
+
+// (status, error code, message), the registry's error body
+type Failure = (u16, u32, String);
+
+/// The registry's contents: `<dir>/<subject>/<version>.avsc` files, with the
+/// ids they were given kept in `<dir>/ids.json`
+struct Store {
+    dir: PathBuf,
+    /// subject -> version -> id
+    subjects: BTreeMap<String, BTreeMap<u32, u32>>,
+    schemas: BTreeMap<u32, Schema>,
+}
+
+impl Store {
+    fn open(dir: &Path) -> Result<Self, AvroError> {
+        fs::create_dir_all(dir)?;
+        let mapping : Vec<IdEntry> = match fs::read(dir.join(IDS_FILE)) {
+            Ok(ids) => serde_json::from_slice(&ids)
+                .map_err(|err| AvroError{ reason: format!("{} doesn't parse: {}", dir.join(IDS_FILE).display(), err) })?,
+            Err(_) => Vec::new(),
+        };
+
+        let mut found = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let subject_dir = entry?.path();
+            if !subject_dir.is_dir() {
+                continue
+            }
+            let subject = percent_decode(&subject_dir.file_name().unwrap().to_string_lossy())?;
+            for entry in fs::read_dir(&subject_dir)? {
+                let path = entry?.path();
+                let version = match path.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_suffix(".avsc")).and_then(|version| version.parse().ok()) {
+                    Some(version) => version,
+                    None => continue,
+                };
+                let schema = Schema::from_str(&fs::read_to_string(&path)?)
+                    .map_err(|err| AvroError{ reason: format!("{} doesn't parse: {}", path.display(), err) })?;
+                found.push((subject.clone(), version, schema));
+            }
+        }
+        found.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+
+        let mut store = Store { dir: dir.to_owned(), subjects: BTreeMap::new(), schemas: BTreeMap::new() };
+        // ids from the mapping first, so new files can't take them
+        let mut unmapped = Vec::new();
+        for (subject, version, schema) in found {
+            match mapping.iter().find(|entry| entry.subject == subject && entry.version == version) {
+                Some(entry) => {
+                    if store.schemas.get(&entry.id).is_some_and(|known| known.fingerprint() != schema.fingerprint()) {
+                        return Err(AvroError{ reason: format!("{} gives id {} to two different schemas", IDS_FILE, entry.id) })
+                    }
+                    store.add(&subject, version, entry.id, schema);
+                },
+                None => unmapped.push((subject, version, schema)),
+            }
+        }
+        for (subject, version, schema) in unmapped {
+            let id = store.id_for(&schema);
+            store.add(&subject, version, id, schema);
+        }
+
+        store.save_ids()?;
+        info!("registry in {} has {} subjects, {} schemas", dir.display(), store.subjects.len(), store.schemas.len());
+        Ok(store)
+    }
+
+    fn add(&mut self, subject: &str, version: u32, id: u32, schema: Schema) {
+        self.subjects.entry(subject.to_owned()).or_default().insert(version, id);
+        self.schemas.insert(id, schema);
+    }
+
+    // the same schema gets the same id, whichever subject it's under
+    fn id_for(&self, schema: &Schema) -> u32 {
+        let fingerprint = schema.fingerprint();
+        match self.schemas.iter().find(|(_, known)| known.fingerprint() == fingerprint) {
+            Some((&id, _)) => id,
+            None => self.schemas.keys().next_back().map_or(1, |id| id + 1),
+        }
+    }
+
+    fn version(&self, subject: &str, version: &str) -> Result<(u32, u32), Failure> {
+        let versions = self.subjects.get(subject).ok_or_else(|| subject_not_found(subject))?;
+        let found = if version == "latest" {
+            versions.iter().next_back()
+        } else {
+            version.parse().ok().and_then(|version| versions.get_key_value(&version))
+        };
+        found.map(|(&version, &id)| (version, id))
+            .ok_or_else(|| (404, 40402, format!("Version {} not found.", version)))
+    }
+
+    fn register(&mut self, subject: &str, schema: Schema) -> Result<u32, Failure> {
+        // the subject names a directory under `dir`, which these would leave
+        if subject.is_empty() || subject == "." || subject == ".." {
+            return Err((422, 422, format!("Subject {:?} isn't a valid subject name", subject)))
+        }
+        let fingerprint = schema.fingerprint();
+        if let Some(versions) = self.subjects.get(subject) {
+            if let Some(&id) = versions.values().find(|id| self.schemas[id].fingerprint() == fingerprint) {
+                return Ok(id)
+            }
+            if let Some(&latest) = versions.values().next_back() {
+                if !compatible(&self.schemas[&latest], &schema) {
+                    return Err((409, 409, "Schema being registered is incompatible with an earlier schema".into()))
+                }
+            }
+        }
+
+        let version = self.subjects.get(subject).and_then(|versions| versions.keys().next_back()).map_or(1, |version| version + 1);
+        let id = self.id_for(&schema);
+        let path = self.dir.join(percent_encode(subject)).join(format!("{}.avsc", version));
+        write_file(&path, schema.to_json().as_bytes()).map_err(internal)?;
+        self.add(subject, version, id, schema);
+        self.save_ids().map_err(internal)?;
+        info!("registered {} version {} as id {}", subject, version, id);
+        Ok(id)
+    }
+
+    fn save_ids(&self) -> Result<(), AvroError> {
+        let mut entries = Vec::new();
+        for (subject, versions) in &self.subjects {
+            for (&version, &id) in versions {
+                entries.push(IdEntry { id, subject: subject.clone(), version });
+            }
+        }
+        write_file(&self.dir.join(IDS_FILE), &serde_json::to_vec_pretty(&entries).unwrap())
+    }
+}
+
+/// Backward compatibility, the registry default: the new schema has to
+/// read everything the old one wrote
+fn compatible(old: &Schema, new: &Schema) -> bool {
+    Resolution::new(old, new).and_then(|resolution| resolution.check()).is_ok()
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> Result<(), AvroError> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    fs::write(&partial, contents)?;
+    fs::rename(&partial, path)?;
+    Ok(())
+}
+
+fn subject_not_found(subject: &str) -> Failure {
+    (404, 40401, format!("Subject '{}' not found.", subject))
+}
+
+fn internal(err: AvroError) -> Failure {
+    (500, 50001, err.reason)
+}
+
+/// A small Confluent compatible schema registry serving a directory, for
+/// tests and places without a real registry. It supports the subject,
+/// version, id and compatibility endpoints, with backward compatibility
+/// checked on registration.
+pub struct RegistryServer {
+    store: Arc<Mutex<Store>>,
+}
+
+impl RegistryServer {
+    /// Loads the registry in `dir`, giving ids to any schema files that
+    /// don't have one yet
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, AvroError> {
+        Ok(RegistryServer { store: Arc::new(Mutex::new(Store::open(dir.as_ref())?)) })
+    }
+
+    /// Starts serving on a background thread, use port 0 for any free port
+    pub fn serve<A: ToSocketAddrs>(self, addr: A) -> Result<RunningRegistry, AvroError> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let (store, stopped) = (self.store, stop.clone());
+        let handle = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped.load(Ordering::SeqCst) {
+                    break
+                }
+                match stream {
+                    Ok(stream) => {
+                        let store = store.clone();
+                        thread::spawn(move || serve_connection(stream, &store));
+                    },
+                    Err(err) => warn!("registry accept failed: {}", err),
+                }
+            }
+        });
+        info!("registry listening on {}", addr);
+
+        Ok(RunningRegistry { addr, stop, handle: Some(handle) })
+    }
+}
+
+/// A registry being served, it stops when dropped
+pub struct RunningRegistry {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl RunningRegistry {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+
+impl Drop for RunningRegistry {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // wake the accept loop so it sees the flag
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn serve_connection(mut stream: TcpStream, store: &Mutex<Store>) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+    let (status, body) = match read_request(&mut stream) {
+        Ok(request) => {
+            info!("{} {}", request.method, request.path);
+            match handle(store, &request) {
+                Ok(body) => (200, body),
+                Err(failure) => failure_body(failure),
+            }
+        },
+        Err(err) => failure_body((400, 400, err.reason)),
+    };
+    if let Err(err) = write_response(&mut stream, status, &body) {
+        info!("registry response failed: {}", err);
+    }
+}
+
+fn failure_body((status, code, message): Failure) -> (u16, Vec<u8>) {
+    (status, serde_json::to_vec(&json!({ "error_code": code, "message": message })).unwrap())
+}
+
+fn handle(store: &Mutex<Store>, request: &Request) -> Result<Vec<u8>, Failure> {
+    let path = request.path.split('?').next().unwrap();
+    let segments : Vec<String> = path.trim_matches('/').split('/').map(percent_decode).collect::<Result<_, _>>()
+        .map_err(|err| (400, 400, err.reason))?;
+    let segments : Vec<&str> = segments.iter().map(|segment| &segment[..]).collect();
+    let mut store = store.lock().unwrap();
+
+    let body = match (&request.method[..], &segments[..]) {
+        ("GET", ["subjects"]) => json!(store.subjects.keys().collect::<Vec<_>>()),
+        ("GET", ["subjects", subject, "versions"]) => {
+            let versions = store.subjects.get(*subject).ok_or_else(|| subject_not_found(subject))?;
+            json!(versions.keys().collect::<Vec<_>>())
+        },
+        ("GET", ["subjects", subject, "versions", version]) => {
+            let (version, id) = store.version(subject, version)?;
+            json!({ "subject": subject, "version": version, "id": id, "schema": store.schemas[&id].to_json() })
+        },
+        ("GET", ["schemas", "ids", id]) => {
+            let schema = id.parse().ok().and_then(|id: u32| store.schemas.get(&id))
+                .ok_or_else(|| (404, 40403, "Schema not found".to_owned()))?;
+            json!({ "schema": schema.to_json() })
+        },
+        ("POST", ["subjects", subject, "versions"]) => {
+            let schema = posted_schema(&request.body)?;
+            json!({ "id": store.register(subject, schema)? })
+        },
+        ("POST", ["compatibility", "subjects", subject, "versions", version]) => {
+            let schema = posted_schema(&request.body)?;
+            let (_, id) = store.version(subject, version)?;
+            json!({ "is_compatible": compatible(&store.schemas[&id], &schema) })
+        },
+        _ => return Err((404, 404, "HTTP 404 Not Found".into())),
+    };
+    Ok(serde_json::to_vec(&body).unwrap())
+}
+
+fn posted_schema(body: &[u8]) -> Result<Schema, Failure> {
+    #[derive(Deserialize)]
+    struct Posted {
+        schema: String,
+    }
+
+    serde_json::from_slice::<Posted>(body).map_err(|err| err.to_string())
+        .and_then(|posted| Schema::from_str(&posted.schema).map_err(|err| err.to_string()))
+        .map_err(|err| (422, 42201, format!("Invalid schema: {}", err)))
+}
@@ -1,12 +1,14 @@
-use serde::{self, Deserialize};
+use serde::{self, Deserialize, Serialize};
 use serde_json;
 
-#[derive(Serialize, Deserialize, Debug)]
+use super::AvroError;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Schema {
     #[serde(rename = "type")]
     schema_type: String,
-    name: String,
-    namespace: String,
+    pub name: String,
+    pub namespace: String,
     pub fields: Vec<SchemaField>,
 }
 
@@ -14,23 +16,244 @@ impl Schema {
     pub fn from_str(schema: &str) -> serde_json::Result<Self> {
         serde_json::from_str(schema)
     }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// The namespace qualified name
+    pub fn full_name(&self) -> String {
+        self.qualify(&self.name)
+    }
+
+    /// The schema in Parsing Canonical Form: full names, no namespaces or
+    /// defaults, attributes in the order the spec gives, no whitespace.
+    pub fn canonical_form(&self) -> String {
+        let mut out = format!("{{\"name\":{},\"type\":\"record\",\"fields\":[", json_string(&self.full_name()));
+        for (idx, field) in self.fields.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            out.push_str(&format!("{{\"name\":{},\"type\":", json_string(&field.name)));
+            if field.types.len() == 1 {
+                out.push_str(&self.canonical_type(&field.types[0]));
+            } else {
+                let types : Vec<String> = field.types.iter().map(|t| self.canonical_type(t)).collect();
+                out.push_str(&format!("[{}]", types.join(",")));
+            }
+            out.push('}');
+        }
+        out.push_str("]}");
+        out
+    }
+
+    /// The CRC-64-AVRO fingerprint of the canonical form
+    pub fn fingerprint(&self) -> u64 {
+        rabin_fingerprint(self.canonical_form().as_bytes())
+    }
+
+    fn canonical_type(&self, field_type: &SchemaFieldType) -> String {
+        match *field_type {
+            SchemaFieldType::Primitive(ref primitive) => json_string(&self.qualify(primitive.type_name())),
+            SchemaFieldType::Complex(Complex::Fixed { ref name, size }) =>
+                format!("{{\"name\":{},\"type\":\"fixed\",\"size\":{}}}", json_string(&self.qualify(name)), size),
+            SchemaFieldType::Complex(Complex::Map { ref values }) =>
+                format!("{{\"type\":\"map\",\"values\":{}}}", json_string(&self.qualify(values))),
+            SchemaFieldType::Complex(Complex::Array { ref items }) =>
+                format!("{{\"type\":\"array\",\"items\":{}}}", json_string(&self.qualify(items))),
+        }
+    }
+
+    // primitives stay bare, names of our own types pick up the namespace
+    pub(crate) fn qualify(&self, name: &str) -> String {
+        let primitive = matches!(name, "null" | "boolean" | "int" | "long" | "float" | "double" | "bytes" | "string");
+        if primitive || name.contains('.') || self.namespace.is_empty() {
+            name.into()
+        } else {
+            format!("{}.{}", self.namespace, name)
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+fn json_string(string: &str) -> String {
+    serde_json::to_string(string).unwrap()
+}
+
+const EMPTY_FINGERPRINT: u64 = 0xc15d_213a_a4d7_a795;
+
+static FINGERPRINT_TABLE: [u64; 256] = fingerprint_table();
+
+const fn fingerprint_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut idx = 0;
+    while idx < 256 {
+        let mut fp = idx as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            fp = (fp >> 1) ^ (EMPTY_FINGERPRINT & (fp & 1).wrapping_neg());
+            bit += 1;
+        }
+        table[idx] = fp;
+        idx += 1;
+    }
+    table
+}
+
+/// CRC-64-AVRO, the Rabin fingerprint the spec uses to identify schemas
+pub fn rabin_fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(EMPTY_FINGERPRINT, |fp, &byte| (fp >> 8) ^ FINGERPRINT_TABLE[((fp ^ byte as u64) & 0xff) as usize])
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(from = "AnnotatedField", into = "AnnotatedField")]
 pub struct SchemaField {
     pub name: String,
-    #[serde(rename = "type", deserialize_with = "one_or_many")]
     pub types: Vec<SchemaFieldType>,
+    /// Filled in when resolving data written without this field
+    pub default: Option<serde_json::Value>,
+    // The `logicalType` on each branch with whatever came with it, kept as
+    // written so the ones we don't know survive `to_json`
+    annotations: Vec<Option<Annotation>>,
+}
+
+impl SchemaField {
+    pub fn new(name: &str, types: Vec<SchemaFieldType>) -> Self {
+        let annotations = vec![None; types.len()];
+        SchemaField { name: name.into(), types, default: None, annotations }
+    }
+
+    /// How to read the `int` or `long` in union branch `branch` (`0` for a
+    /// field that isn't a union), from a `logicalType` on it. Only changes
+    /// what the value means, never how it's encoded.
+    pub fn logical_type(&self, branch: usize) -> Option<LogicalType> {
+        let annotation = self.annotations.get(branch)?.as_ref()?;
+        match self.types[branch] {
+            SchemaFieldType::Primitive(ref primitive) => LogicalType::from_name(&annotation.logical_type)
+                .filter(|logical| logical.primitive() == *primitive),
+            _ => None,
+        }
+    }
+}
+
+/// The logical types we know, anything else is read as its underlying type
+/// as the spec asks
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LogicalType {
+    /// Days since the epoch, on an `int`
+    Date,
+    /// Milliseconds after midnight, on an `int`
+    TimeMillis,
+    /// Microseconds after midnight, on a `long`
+    TimeMicros,
+    /// Milliseconds since the epoch, UTC, on a `long`
+    TimestampMillis,
+    TimestampMicros,
+    TimestampNanos,
+    /// Milliseconds since the epoch in no particular timezone, on a `long`
+    LocalTimestampMillis,
+    LocalTimestampMicros,
+    LocalTimestampNanos,
+}
+
+impl LogicalType {
+    pub fn from_name(name: &str) -> Option<LogicalType> {
+        match name {
+            "date" => Some(LogicalType::Date),
+            "time-millis" => Some(LogicalType::TimeMillis),
+            "time-micros" => Some(LogicalType::TimeMicros),
+            "timestamp-millis" => Some(LogicalType::TimestampMillis),
+            "timestamp-micros" => Some(LogicalType::TimestampMicros),
+            "timestamp-nanos" => Some(LogicalType::TimestampNanos),
+            "local-timestamp-millis" => Some(LogicalType::LocalTimestampMillis),
+            "local-timestamp-micros" => Some(LogicalType::LocalTimestampMicros),
+            "local-timestamp-nanos" => Some(LogicalType::LocalTimestampNanos),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            LogicalType::Date => "date",
+            LogicalType::TimeMillis => "time-millis",
+            LogicalType::TimeMicros => "time-micros",
+            LogicalType::TimestampMillis => "timestamp-millis",
+            LogicalType::TimestampMicros => "timestamp-micros",
+            LogicalType::TimestampNanos => "timestamp-nanos",
+            LogicalType::LocalTimestampMillis => "local-timestamp-millis",
+            LogicalType::LocalTimestampMicros => "local-timestamp-micros",
+            LogicalType::LocalTimestampNanos => "local-timestamp-nanos",
+        }
+    }
+
+    /// The type it annotates
+    pub fn primitive(&self) -> Primitive {
+        match *self {
+            LogicalType::Date | LogicalType::TimeMillis => Primitive::Int,
+            _ => Primitive::Long,
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+// A field as it's written, logical types nested in the branch they annotate
+#[derive(Serialize, Deserialize)]
+struct AnnotatedField {
+    name: String,
+    #[serde(rename = "type", deserialize_with = "one_or_many", serialize_with = "one_or_many_ser")]
+    types: Vec<AnnotatedType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+enum AnnotatedType {
+    Plain(SchemaFieldType),
+    Logical {
+        #[serde(rename = "type")]
+        primitive: Primitive,
+        #[serde(flatten)]
+        annotation: Annotation,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Annotation {
+    #[serde(rename = "logicalType")]
+    logical_type: String,
+    // `precision`, `scale` and the like
+    #[serde(flatten)]
+    attributes: serde_json::Map<String, serde_json::Value>,
+}
+
+impl From<AnnotatedField> for SchemaField {
+    fn from(field: AnnotatedField) -> Self {
+        let (types, annotations) = field.types.into_iter().map(|field_type| match field_type {
+            AnnotatedType::Plain(field_type) => (field_type, None),
+            AnnotatedType::Logical { primitive, annotation } => (SchemaFieldType::Primitive(primitive), Some(annotation)),
+        }).unzip();
+        SchemaField { name: field.name, types, default: field.default, annotations }
+    }
+}
+
+impl From<SchemaField> for AnnotatedField {
+    fn from(field: SchemaField) -> Self {
+        let mut annotations = field.annotations.into_iter();
+        let types = field.types.into_iter().map(|field_type| match (field_type, annotations.next()) {
+            (SchemaFieldType::Primitive(primitive), Some(Some(annotation))) => AnnotatedType::Logical { primitive, annotation },
+            (field_type, _) => AnnotatedType::Plain(field_type),
+        }).collect();
+        AnnotatedField { name: field.name, types, default: field.default }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(untagged)]
 pub enum SchemaFieldType {
     Primitive(Primitive),
     Complex(Complex),
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum Primitive {
     Null,
@@ -47,7 +270,7 @@ pub enum Primitive {
     Int64T,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum Complex {
     Fixed {
@@ -57,21 +280,101 @@ pub enum Complex {
     Map {
         values: String,
     },
+    Array {
+        items: String,
+    },
 }
 
-fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<SchemaFieldType>, D::Error>
+impl SchemaFieldType {
+    /// Resolves a type referenced by name, as in a map's `values` or an array's `items`
+    pub fn named(name: &str) -> Option<SchemaFieldType> {
+        Primitive::from_name(name).map(SchemaFieldType::Primitive)
+    }
+
+    /// `named`, as an error for a map's `values` or an array's `items`
+    /// that isn't supported
+    pub(crate) fn element(name: &str) -> Result<SchemaFieldType, AvroError> {
+        Primitive::element(name).map(SchemaFieldType::Primitive)
+    }
+
+    /// The name a union branch goes by: the primitive's name, the declared
+    /// name of a fixed, or `map`/`array`.
+    pub fn type_name(&self) -> &str {
+        match *self {
+            SchemaFieldType::Primitive(ref primitive) => primitive.type_name(),
+            SchemaFieldType::Complex(Complex::Fixed { ref name, .. }) => &name[..],
+            SchemaFieldType::Complex(Complex::Map { .. }) => "map",
+            SchemaFieldType::Complex(Complex::Array { .. }) => "array",
+        }
+    }
+}
+
+impl Primitive {
+    /// Looks up a primitive by the name it has in a schema, e.g. a map's `values`
+    pub fn from_name(name: &str) -> Option<Primitive> {
+        match name {
+            "null" => Some(Primitive::Null),
+            "int" => Some(Primitive::Int),
+            "long" => Some(Primitive::Long),
+            "float" => Some(Primitive::Float),
+            "double" => Some(Primitive::Double),
+            "boolean" => Some(Primitive::Boolean),
+            "bytes" => Some(Primitive::Bytes),
+            "string" => Some(Primitive::String),
+            "uint64_t" => Some(Primitive::Uint64T),
+            "int64_t" => Some(Primitive::Int64T),
+            _ => None,
+        }
+    }
+
+    /// `from_name`, as an error for a map's `values` or an array's `items`
+    /// that isn't supported
+    pub(crate) fn element(name: &str) -> Result<Primitive, AvroError> {
+        Primitive::from_name(name)
+            .ok_or_else(|| AvroError{ reason: format!("element type {} isn't supported", name) })
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Primitive::Null => "null",
+            Primitive::Int => "int",
+            Primitive::Long => "long",
+            Primitive::Float => "float",
+            Primitive::Double => "double",
+            Primitive::Boolean => "boolean",
+            Primitive::Bytes => "bytes",
+            Primitive::String => "string",
+            Primitive::Uint64T => "uint64_t",
+            Primitive::Int64T => "int64_t",
+        }
+    }
+}
+
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<AnnotatedType>, D::Error>
     where
         D: serde::de::Deserializer<'de>,
 {
     #[derive(Deserialize)]
     #[serde(untagged)]
     enum OneOrMany {
-        One(SchemaFieldType),
-        Many(Vec<SchemaFieldType>),
+        One(AnnotatedType),
+        Many(Vec<AnnotatedType>),
     }
 
     match OneOrMany::deserialize(deserializer)? {
         OneOrMany::One(field) => Ok(vec![field]),
         OneOrMany::Many(fields) => Ok(fields),
     }
-}
\ No newline at end of file
+}
+
+fn one_or_many_ser<S>(types: &[AnnotatedType], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+{
+    // a lone type isn't a union, don't write it back as one
+    if types.len() == 1 {
+        types[0].serialize(serializer)
+    } else {
+        types.serialize(serializer)
+    }
+}
@@ -0,0 +1,14 @@
+use integer_encoding::VarInt;
+
+/// Appends a zigzag varint, the encoding of Avro's `int` and `long`
+pub(crate) fn write_long(val: i64, buf: &mut Vec<u8>) {
+    let mut varint = [0u8; 10];
+    let size = val.encode_var(&mut varint);
+    buf.extend_from_slice(&varint[..size]);
+}
+
+/// Appends length-prefixed `bytes`/`string` data
+pub(crate) fn write_bytes(bytes: &[u8], buf: &mut Vec<u8>) {
+    write_long(bytes.len() as i64, buf);
+    buf.extend_from_slice(bytes);
+}
@@ -0,0 +1,5 @@
+mod serializer;
+pub use self::serializer::*;
+
+mod encode;
+pub(crate) use self::encode::*;
@@ -0,0 +1,480 @@
+use std::io::Write;
+
+use serde::ser::{ self, Serialize, Serializer };
+
+use super::super::*;
+
+use byteorder::{ LittleEndian, WriteBytesExt };
+use integer_encoding::VarInt;
+
+/// Schema-driven counterpart to `AvroDeserializer`. Struct fields are matched
+/// to the schema's fields by name and must arrive in schema order. Unions are
+/// written from an `Option` (the `null` branch for `None`) or an enum, whose
+/// variant picks the branch by type name and falls back to its index.
+/// Values have to match the type they're written as, except that an integer
+/// can go into a fixed such as `uint16_t`, little endian in the fixed's width.
+pub struct AvroSerializer<'s, W> {
+    pub writer: W,
+    pub schema: &'s Schema,
+    pub current_field_index: Option<usize>,
+    // a union field whose branch hasn't been picked yet
+    union: Option<&'s [SchemaFieldType]>,
+    // the type the next value is written as, when known
+    branch: Option<&'s SchemaFieldType>,
+}
+
+pub fn to_vec<T: Serialize + ?Sized>(schema: &Schema, value: &T) -> Result<Vec<u8>, AvroError> {
+    let mut serializer = AvroSerializer::new(schema, Vec::new());
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_inner())
+}
+
+impl<'s, W: Write> AvroSerializer<'s, W> {
+    pub fn new(schema: &'s Schema, writer: W) -> Self {
+        AvroSerializer {
+            writer,
+            schema,
+            current_field_index: None,
+            union: None,
+            branch: None,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Zigzag varint, the encoding of Avro's `int` and `long`
+    pub fn write_long(&mut self, val: i64) -> Result<(), AvroError> {
+        let mut buf = [0u8; 10];
+        let size = val.encode_var(&mut buf);
+        self.writer.write_all(&buf[..size])?;
+        Ok(())
+    }
+
+    /// Plain varint, mirroring how `AvroDeserializer` reads unsigned integers
+    pub fn write_ulong(&mut self, val: u64) -> Result<(), AvroError> {
+        let mut buf = [0u8; 10];
+        let size = val.encode_var(&mut buf);
+        self.writer.write_all(&buf[..size])?;
+        Ok(())
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), AvroError> {
+        self.write_long(bytes.len() as i64)?;
+        self.writer.write_all(bytes)?;
+        Ok(())
+    }
+
+    fn field_name(&self) -> &str {
+        match self.current_field_index {
+            Some(idx) => &self.schema.fields[idx].name[..],
+            None => &self.schema.name[..],
+        }
+    }
+
+    fn next_field(&mut self, name: &str) -> Result<(), AvroError> {
+        let schema = self.schema;
+        let idx = match schema.fields.iter().position(|field| field.name == name) {
+            Some(idx) => idx,
+            None => return Err(AvroError{ reason: format!("schema {} has no field {}", schema.name, name) }),
+        };
+
+        let expected = self.current_field_index.map_or(0, |cfi| cfi + 1);
+        if idx != expected {
+            return Err(AvroError{ reason: format!("field {} is out of schema order, expected {}", name, schema.fields[expected.min(schema.fields.len() - 1)].name) })
+        }
+
+        info!("serializing field {}", name);
+        self.current_field_index = Some(idx);
+        let types = &schema.fields[idx].types;
+        if types.len() > 1 {
+            self.union = Some(&types[..]);
+            self.branch = None;
+        } else {
+            self.union = None;
+            self.branch = types.first();
+        }
+        Ok(())
+    }
+
+    fn select_branch(&mut self, types: &'s [SchemaFieldType], index: usize) -> Result<(), AvroError> {
+        if index >= types.len() {
+            return Err(AvroError{ reason: format!("union branch for {} is out of scope, got {} but max is {}", self.field_name(), index, types.len()) })
+        }
+        info!("union branch {} ({})", index, types[index].type_name());
+        self.write_long(index as i64)?;
+        self.branch = Some(&types[index]);
+        Ok(())
+    }
+
+    fn select_variant(&mut self, variant_index: u32, variant: &str) -> Result<(), AvroError> {
+        match self.union.take() {
+            Some(types) => {
+                let index = types.iter()
+                    .position(|t| t.type_name().eq_ignore_ascii_case(variant))
+                    .unwrap_or(variant_index as usize);
+                self.select_branch(types, index)
+            },
+            None => Err(AvroError{ reason: format!("field {} is not a union, can't write variant {}", self.field_name(), variant) }),
+        }
+    }
+
+    // Takes the type of a value that doesn't pick a union branch itself
+    fn plain(&mut self) -> Result<Option<&'s SchemaFieldType>, AvroError> {
+        if self.union.is_some() {
+            return Err(AvroError{ reason: format!("field {} is a union, serialize it from an enum or Option", self.field_name()) })
+        }
+        Ok(self.branch.take())
+    }
+
+    fn mismatch(&self, field_type: &SchemaFieldType, value: &str) -> AvroError {
+        AvroError{ reason: format!("field {} is a {}, can't write {} to it", self.field_name(), field_type.type_name(), value) }
+    }
+
+    fn out_of_range<T: ::std::fmt::Display>(&self, field_type: &SchemaFieldType, val: T) -> AvroError {
+        AvroError{ reason: format!("{} doesn't fit the {} of field {}", val, field_type.type_name(), self.field_name()) }
+    }
+
+    // An integer in a fixed, e.g. `uint16_t`: little endian, in the fixed's
+    // width, which has to hold it signed or unsigned
+    fn write_fixed_int(&mut self, field_type: &SchemaFieldType, val: i128) -> Result<(), AvroError> {
+        let size = match *field_type {
+            SchemaFieldType::Complex(Complex::Fixed { size, .. }) if (1..=8).contains(&size) => size,
+            SchemaFieldType::Primitive(Primitive::Uint64T) | SchemaFieldType::Primitive(Primitive::Int64T) => 8,
+            _ => return Err(self.mismatch(field_type, "an integer")),
+        };
+        let bits = 8 * size as u32;
+        if val < -(1i128 << (bits - 1)) || val >= 1i128 << bits {
+            return Err(self.out_of_range(field_type, val))
+        }
+        self.writer.write_all(&(val as u128).to_le_bytes()[..size])?;
+        Ok(())
+    }
+}
+
+impl<'a, 's, W: Write> Serializer for &'a mut AvroSerializer<'s, W> {
+    type Ok = ();
+    type Error = AvroError;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<(), AvroError> {
+        match self.plain()? {
+            None | Some(&SchemaFieldType::Primitive(Primitive::Boolean)) => {},
+            Some(field_type) => return Err(self.mismatch(field_type, "a bool")),
+        }
+        self.writer.write_all(&[v as u8])?;
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), AvroError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), AvroError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), AvroError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), AvroError> {
+        match self.plain()? {
+            None | Some(&SchemaFieldType::Primitive(Primitive::Long)) => self.write_long(v),
+            Some(field_type @ &SchemaFieldType::Primitive(Primitive::Int)) => {
+                if v < i32::MIN as i64 || v > i32::MAX as i64 {
+                    return Err(self.out_of_range(field_type, v))
+                }
+                self.write_long(v)
+            },
+            Some(field_type) => self.write_fixed_int(field_type, v as i128),
+        }
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), AvroError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), AvroError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), AvroError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), AvroError> {
+        match self.plain()? {
+            None => self.write_ulong(v),
+            Some(field_type @ &SchemaFieldType::Primitive(Primitive::Int)) |
+            Some(field_type @ &SchemaFieldType::Primitive(Primitive::Long)) => {
+                let max = if *field_type == SchemaFieldType::Primitive(Primitive::Int) { i32::MAX as u64 } else { i64::MAX as u64 };
+                if v > max {
+                    return Err(self.out_of_range(field_type, v))
+                }
+                self.write_ulong(v)
+            },
+            Some(field_type) => self.write_fixed_int(field_type, v as i128),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), AvroError> {
+        match self.plain()? {
+            None | Some(&SchemaFieldType::Primitive(Primitive::Float)) => self.writer.write_f32::<LittleEndian>(v)?,
+            // float promotes to double
+            Some(&SchemaFieldType::Primitive(Primitive::Double)) => self.writer.write_f64::<LittleEndian>(v as f64)?,
+            Some(field_type) => return Err(self.mismatch(field_type, "an f32")),
+        }
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), AvroError> {
+        match self.plain()? {
+            None | Some(&SchemaFieldType::Primitive(Primitive::Double)) => self.writer.write_f64::<LittleEndian>(v)?,
+            Some(field_type) => return Err(self.mismatch(field_type, "an f64")),
+        }
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), AvroError> {
+        let mut buf = [0u8; 4];
+        self.serialize_str(v.encode_utf8(&mut buf))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), AvroError> {
+        match self.plain()? {
+            None | Some(&SchemaFieldType::Primitive(Primitive::String)) | Some(&SchemaFieldType::Primitive(Primitive::Bytes)) =>
+                self.write_bytes(v.as_bytes()),
+            Some(field_type) => Err(self.mismatch(field_type, "a str")),
+        }
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), AvroError> {
+        let size = match self.plain()? {
+            None | Some(&SchemaFieldType::Primitive(Primitive::Bytes)) | Some(&SchemaFieldType::Primitive(Primitive::String)) =>
+                return self.write_bytes(v),
+            Some(&SchemaFieldType::Complex(Complex::Fixed { size, .. })) => size,
+            Some(&SchemaFieldType::Primitive(Primitive::Uint64T)) | Some(&SchemaFieldType::Primitive(Primitive::Int64T)) => 8,
+            Some(field_type) => return Err(self.mismatch(field_type, "bytes")),
+        };
+        if v.len() != size {
+            return Err(AvroError{ reason: format!("fixed field {} needs {} bytes, got {}", self.field_name(), size, v.len()) })
+        }
+        self.writer.write_all(v)?;
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), AvroError> {
+        match self.union.take() {
+            Some(types) => {
+                match types.iter().position(|t| *t == SchemaFieldType::Primitive(Primitive::Null)) {
+                    Some(index) => self.select_branch(types, index),
+                    None => Err(AvroError{ reason: format!("field {} has no null branch for None", self.field_name()) }),
+                }
+            },
+            None => match self.branch.take() {
+                Some(&SchemaFieldType::Primitive(Primitive::Null)) | None => Ok(()),
+                Some(_) => Err(AvroError{ reason: format!("field {} is not nullable", self.field_name()) }),
+            },
+        }
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), AvroError> {
+        if let Some(types) = self.union.take() {
+            match types.iter().position(|t| *t != SchemaFieldType::Primitive(Primitive::Null)) {
+                Some(index) => self.select_branch(types, index)?,
+                None => return Err(AvroError{ reason: format!("field {} has no branch for Some", self.field_name()) }),
+            }
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), AvroError> {
+        match self.plain()? {
+            None | Some(&SchemaFieldType::Primitive(Primitive::Null)) => Ok(()),
+            Some(field_type) => Err(self.mismatch(field_type, "a unit")),
+        }
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), AvroError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, variant_index: u32, variant: &'static str) -> Result<(), AvroError> {
+        if self.union.is_some() {
+            self.select_variant(variant_index, variant)?;
+            self.branch = None;
+            Ok(())
+        } else {
+            match self.plain()? {
+                None | Some(&SchemaFieldType::Primitive(Primitive::Int)) | Some(&SchemaFieldType::Primitive(Primitive::Long)) =>
+                    self.write_long(variant_index as i64),
+                Some(field_type) => Err(self.mismatch(field_type, format!("variant {}", variant).as_str())),
+            }
+        }
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<(), AvroError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _name: &'static str, variant_index: u32, variant: &'static str, value: &T) -> Result<(), AvroError> {
+        self.select_variant(variant_index, variant)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, AvroError> {
+        match self.plain()? {
+            None | Some(&SchemaFieldType::Complex(Complex::Array { .. })) | Some(&SchemaFieldType::Complex(Complex::Map { .. })) => {},
+            Some(field_type) => return Err(self.mismatch(field_type, "a sequence")),
+        }
+        match len {
+            // a single block, end() writes the terminating empty block
+            Some(0) => Ok(self),
+            Some(len) => {
+                self.write_long(len as i64)?;
+                Ok(self)
+            },
+            None => Err(AvroError{ reason: format!("field {} needs a known length to write its block", self.field_name()) }),
+        }
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, AvroError> {
+        self.plain()?;
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Self, AvroError> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str, _len: usize) -> Result<Self, AvroError> {
+        Err(AvroError{ reason: format!("tuple variant {} has no avro representation", variant) })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, AvroError> {
+        self.serialize_seq(len)
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self, AvroError> {
+        if self.current_field_index.is_some() {
+            return Err(AvroError{ reason: format!("can't write {} into field {}, nested records aren't supported", name, self.field_name()) })
+        }
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str, _len: usize) -> Result<Self, AvroError> {
+        Err(AvroError{ reason: format!("struct variant {} has no avro representation", variant) })
+    }
+}
+
+impl<'a, 's, W: Write> ser::SerializeSeq for &'a mut AvroSerializer<'s, W> {
+    type Ok = ();
+    type Error = AvroError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), AvroError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), AvroError> {
+        self.write_long(0)
+    }
+}
+
+impl<'a, 's, W: Write> ser::SerializeTuple for &'a mut AvroSerializer<'s, W> {
+    type Ok = ();
+    type Error = AvroError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), AvroError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), AvroError> {
+        Ok(())
+    }
+}
+
+impl<'a, 's, W: Write> ser::SerializeTupleStruct for &'a mut AvroSerializer<'s, W> {
+    type Ok = ();
+    type Error = AvroError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), AvroError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), AvroError> {
+        Ok(())
+    }
+}
+
+impl<'a, 's, W: Write> ser::SerializeTupleVariant for &'a mut AvroSerializer<'s, W> {
+    type Ok = ();
+    type Error = AvroError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), AvroError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), AvroError> {
+        Ok(())
+    }
+}
+
+impl<'a, 's, W: Write> ser::SerializeMap for &'a mut AvroSerializer<'s, W> {
+    type Ok = ();
+    type Error = AvroError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), AvroError> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), AvroError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), AvroError> {
+        self.write_long(0)
+    }
+}
+
+impl<'a, 's, W: Write> ser::SerializeStruct for &'a mut AvroSerializer<'s, W> {
+    type Ok = ();
+    type Error = AvroError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), AvroError> {
+        self.next_field(key)?;
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), AvroError> {
+        let written = self.current_field_index.map_or(0, |cfi| cfi + 1);
+        if written < self.schema.fields.len() {
+            return Err(AvroError{ reason: format!("record {} is missing field {}", self.schema.name, self.schema.fields[written].name) })
+        }
+
+        // ready for the next record on the same writer
+        self.current_field_index = None;
+        Ok(())
+    }
+}
+
+impl<'a, 's, W: Write> ser::SerializeStructVariant for &'a mut AvroSerializer<'s, W> {
+    type Ok = ();
+    type Error = AvroError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, _key: &'static str, value: &T) -> Result<(), AvroError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), AvroError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+#![cfg(feature = "arrow")]
+
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate avvy;
+extern crate serde_json;
+extern crate arrow_array;
+extern crate arrow_schema;
+
+use arrow_array::{ Array, Float64Array, Int64Array, MapArray, StringArray, TimestampMillisecondArray, UnionArray };
+use arrow_array::cast::AsArray;
+use arrow_schema::{ DataType, TimeUnit, UnionMode };
+
+use avvy::columnar::{ arrow_schema, ArrowDecoder };
+use avvy::container::{ ContainerReader, ContainerWriter };
+
+const SCHEMA_STR: &str = r###"{
+    "type": "record",
+    "name": "reading",
+    "namespace": "test",
+    "fields": [
+        { "name": "at", "type": { "type": "long", "logicalType": "timestamp-millis" } },
+        { "name": "sensor", "type": "string" },
+        { "name": "value", "type": ["null", "double"] },
+        { "name": "raw", "type": ["long", "string", "null"] },
+        { "name": "tags", "type": { "type": "map", "values": "long" } }
+    ]
+}"###;
+
+#[derive(Serialize,Deserialize,Debug,PartialEq,Clone)]
+enum Raw {
+    Long(i64),
+    Str(String),
+    Null,
+}
+
+#[derive(Serialize,Deserialize,Debug,PartialEq,Clone)]
+struct Reading {
+    at: i64,
+    sensor: String,
+    value: Option<f64>,
+    raw: Raw,
+    tags: Vec<(String, i64)>,
+}
+
+fn readings(count: usize) -> Vec<Reading> {
+    (0..count).map(|i| Reading {
+        at: 1_500_000_000_000 + i as i64,
+        sensor: format!("s{}", i),
+        value: if i % 3 == 0 { None } else { Some(i as f64) },
+        raw: match i % 3 { 0 => Raw::Long(i as i64), 1 => Raw::Str(format!("r{}", i)), _ => Raw::Null },
+        tags: (0..i % 4).map(|tag| (format!("t{}", tag), tag as i64)).collect(),
+    }).collect()
+}
+
+#[test]
+fn logical_types_survive_a_schema_round_trip() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    assert_eq!(schema.fields[0].logical_type(0), Some(avvy::LogicalType::TimestampMillis));
+    assert_eq!(schema.fields[0].types, vec![avvy::SchemaFieldType::Primitive(avvy::Primitive::Long)]);
+    assert_eq!(schema.fields[1].logical_type(0), None);
+    assert!(!schema.canonical_form().contains("logicalType"));
+
+    let again = avvy::Schema::from_str(&schema.to_json()).unwrap();
+    assert_eq!(again.fields[0].logical_type(0), Some(avvy::LogicalType::TimestampMillis));
+
+    // unknown or misplaced logical types are read as the plain type
+    let loose = avvy::Schema::from_str(&SCHEMA_STR.replace("timestamp-millis", "date")).unwrap();
+    assert_eq!(loose.fields[0].logical_type(0), None);
+}
+
+#[test]
+fn logical_types_belong_to_their_union_branch() {
+    let schema = avvy::Schema::from_str(r#"{
+      "type": "record", "name": "Span", "namespace": "test",
+      "fields": [
+        { "name": "at", "type": [
+          "null",
+          { "type": "int", "logicalType": "date" },
+          { "type": "long", "logicalType": "timestamp-micros" }
+        ] }
+      ]
+    }"#).unwrap();
+    let field = &schema.fields[0];
+    assert_eq!(field.logical_type(0), None);
+    assert_eq!(field.logical_type(1), Some(avvy::LogicalType::Date));
+    assert_eq!(field.logical_type(2), Some(avvy::LogicalType::TimestampMicros));
+
+    let again = avvy::Schema::from_str(&schema.to_json()).unwrap();
+    assert_eq!(again.fields[0].logical_type(1), Some(avvy::LogicalType::Date));
+    assert_eq!(again.fields[0].logical_type(2), Some(avvy::LogicalType::TimestampMicros));
+}
+
+#[test]
+fn unknown_logical_types_are_written_back() {
+    let schema = avvy::Schema::from_str(r#"{
+      "type": "record", "name": "Price", "namespace": "test",
+      "fields": [
+        { "name": "amount", "type": { "type": "bytes", "logicalType": "decimal", "precision": 9, "scale": 2 } }
+      ]
+    }"#).unwrap();
+    assert_eq!(schema.fields[0].logical_type(0), None);
+
+    let json: serde_json::Value = serde_json::from_str(&schema.to_json()).unwrap();
+    let written: serde_json::Value = serde_json::from_str(r#"{ "type": "bytes", "logicalType": "decimal", "precision": 9, "scale": 2 }"#).unwrap();
+    assert_eq!(json["fields"][0]["type"], written);
+}
+
+#[test]
+fn avro_schema_maps_to_arrow() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let arrow = arrow_schema(&schema);
+
+    assert_eq!(arrow.field(0).data_type(), &DataType::Timestamp(TimeUnit::Millisecond, Some("+00:00".into())));
+    assert!(!arrow.field(0).is_nullable());
+    assert_eq!(arrow.field(1).data_type(), &DataType::Utf8);
+    assert_eq!(arrow.field(2).data_type(), &DataType::Float64);
+    assert!(arrow.field(2).is_nullable());
+    match *arrow.field(3).data_type() {
+        DataType::Union(ref fields, UnionMode::Dense) => {
+            let types = fields.iter().map(|(id, field)| (id, field.name().clone(), field.data_type().clone())).collect::<Vec<_>>();
+            assert_eq!(types, vec![(0, "long".into(), DataType::Int64), (1, "string".into(), DataType::Utf8), (2, "null".into(), DataType::Null)]);
+        },
+        ref other => panic!("raw is a {}", other),
+    }
+    assert!(matches!(*arrow.field(4).data_type(), DataType::Map(_, false)));
+}
+
+#[test]
+fn arrow_decoder_batches_a_record_stream() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let readings = readings(7);
+    let stream = readings.iter().flat_map(|reading| avvy::to_vec(&schema, reading).unwrap()).collect::<Vec<u8>>();
+
+    let mut decoder = ArrowDecoder::new(&schema).unwrap().batch_size(4);
+    let mut batches = Vec::new();
+    let mut pos = 0;
+    while pos < stream.len() {
+        pos += decoder.decode(&stream[pos..]).unwrap();
+        if decoder.is_full() {
+            batches.push(decoder.flush().unwrap().unwrap());
+        }
+    }
+    batches.extend(decoder.flush().unwrap());
+    assert!(decoder.flush().unwrap().is_none());
+
+    assert_eq!(batches.iter().map(|batch| batch.num_rows()).collect::<Vec<_>>(), vec![4, 3]);
+    check_batch(&batches[0], &readings[..4]);
+    check_batch(&batches[1], &readings[4..]);
+}
+
+#[test]
+fn container_reads_as_record_batches() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let readings = readings(10);
+    let mut writer = ContainerWriter::new(&schema, Vec::new()).block_records(3);
+    for reading in &readings {
+        writer.write_record(reading).unwrap();
+    }
+    let file = writer.finish().unwrap();
+
+    // batches straddle the three record blocks
+    let batches = ContainerReader::new(&file[..]).unwrap().arrow_batches(4).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(batches.iter().map(|batch| batch.num_rows()).collect::<Vec<_>>(), vec![4, 4, 2]);
+    assert_eq!(*batches[0].schema(), arrow_schema(&schema));
+    for (batch, readings) in batches.iter().zip(readings.chunks(4)) {
+        check_batch(batch, readings);
+    }
+
+    let mut truncated = file.clone();
+    truncated.truncate(file.len() - 20);
+    let results = ContainerReader::new(&truncated[..]).unwrap().arrow_batches(100).unwrap().collect::<Vec<_>>();
+    assert_eq!(results.len(), 1);
+    assert!(results[0].is_err());
+}
+
+fn check_batch(batch: &arrow_array::RecordBatch, readings: &[Reading]) {
+    let at = batch.column(0).as_any().downcast_ref::<TimestampMillisecondArray>().unwrap();
+    let sensor = batch.column(1).as_any().downcast_ref::<StringArray>().unwrap();
+    let value = batch.column(2).as_any().downcast_ref::<Float64Array>().unwrap();
+    let raw = batch.column(3).as_any().downcast_ref::<UnionArray>().unwrap();
+    let tags = batch.column(4).as_any().downcast_ref::<MapArray>().unwrap();
+
+    for (row, reading) in readings.iter().enumerate() {
+        assert_eq!(at.value(row), reading.at);
+        assert_eq!(sensor.value(row), reading.sensor);
+        assert_eq!(value.is_valid(row), reading.value.is_some());
+        if let Some(val) = reading.value {
+            assert_eq!(value.value(row), val);
+        }
+
+        let branch = raw.value(row);
+        match (raw.type_id(row), &reading.raw) {
+            (0, &Raw::Long(val)) => assert_eq!(branch.as_any().downcast_ref::<Int64Array>().unwrap().value(0), val),
+            (1, Raw::Str(val)) => assert_eq!(branch.as_string::<i32>().value(0), val),
+            (2, &Raw::Null) => assert!(branch.is_null(0) || branch.data_type() == &DataType::Null),
+            (id, raw) => panic!("{:?} landed in branch {}", raw, id),
+        }
+
+        let entries = tags.value(row);
+        let keys = entries.column(0).as_string::<i32>();
+        let values = entries.column(1).as_any().downcast_ref::<Int64Array>().unwrap();
+        let decoded = (0..entries.len()).map(|entry| (keys.value(entry).to_string(), values.value(entry))).collect::<Vec<_>>();
+        assert_eq!(decoded, reading.tags);
+    }
+}
@@ -0,0 +1,131 @@
+#![cfg(feature = "async")]
+
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate avvy;
+extern crate bytes;
+extern crate futures_executor;
+extern crate tokio_util;
+
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use futures_executor::block_on_stream;
+use tokio_util::codec::{ Decoder, Encoder };
+
+use avvy::container::{ AsyncContainerReader, Codec, ContainerCodec, ContainerWriter };
+use avvy::message::{ ConfluentCodec, ConfluentDecoder, ConfluentError, DatumCodec };
+
+const SCHEMA_STR: &str = r###"{
+    "type": "record",
+    "name": "reading",
+    "namespace": "test",
+    "fields": [
+        { "name": "sensor", "type": "string" },
+        { "name": "value", "type": ["null", "double"] }
+    ]
+}"###;
+
+#[derive(Serialize,Deserialize,Debug,PartialEq,Clone)]
+struct Reading {
+    sensor: String,
+    value: Option<f64>,
+}
+
+fn readings(count: usize) -> Vec<Reading> {
+    (0..count).map(|i| Reading { sensor: format!("s{}", i), value: if i % 3 == 0 { None } else { Some(i as f64) } }).collect()
+}
+
+// feeds `wire` to `decoder` a few bytes at a time, like reads off a socket
+fn decode_in_chunks<D: Decoder>(decoder: &mut D, wire: &[u8]) -> Result<Vec<D::Item>, D::Error> {
+    let mut buf = BytesMut::new();
+    let mut items = Vec::new();
+    for chunk in wire.chunks(5) {
+        buf.extend_from_slice(chunk);
+        while let Some(item) = decoder.decode(&mut buf)? {
+            items.push(item);
+        }
+    }
+    if let Some(item) = decoder.decode_eof(&mut buf)? {
+        items.push(item);
+    }
+    Ok(items)
+}
+
+#[test]
+fn datum_codec_round_trips() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let mut codec = DatumCodec::<Reading>::new(&schema);
+
+    let mut wire = BytesMut::new();
+    for reading in readings(10) {
+        codec.encode(reading, &mut wire).unwrap();
+    }
+    assert_eq!(&wire[..4], &[0, 0, 0, 4][..]);
+    assert_eq!(decode_in_chunks(&mut codec, &wire[..]).unwrap(), readings(10));
+
+    let mut codec = DatumCodec::<Reading>::new(&schema).max_frame_length(3);
+    assert!(codec.decode(&mut BytesMut::from(&wire[..])).is_err());
+}
+
+#[test]
+fn confluent_codec_picks_the_writer_schema() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let mut decoder = ConfluentDecoder::new(&schema);
+    decoder.add_schema(7, &schema).unwrap();
+    let decoder = Arc::new(decoder);
+
+    let mut codec = ConfluentCodec::<Reading>::new(decoder.clone()).writer(&schema, 7);
+    let mut wire = BytesMut::new();
+    for reading in readings(4) {
+        codec.encode(reading, &mut wire).unwrap();
+    }
+    assert_eq!(&wire[4..9], &[0, 0, 0, 0, 7][..]);
+    assert_eq!(decode_in_chunks(&mut codec, &wire[..]).unwrap(), readings(4));
+
+    let mut wire = BytesMut::new();
+    ConfluentCodec::<Reading>::new(decoder.clone()).writer(&schema, 8).encode(readings(1).remove(0), &mut wire).unwrap();
+    match codec.decode(&mut wire) {
+        Err(ConfluentError::UnknownSchemaId(8)) => {},
+        other => panic!("expected an unknown schema id, got {:?}", other),
+    }
+
+    assert!(ConfluentCodec::<Reading>::new(decoder).encode(readings(1).remove(0), &mut BytesMut::new()).is_err());
+}
+
+fn container_file(codec: Codec, records: &[Reading]) -> Vec<u8> {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let mut writer = ContainerWriter::with_codec(&schema, codec, Vec::new()).block_records(7);
+    for record in records {
+        writer.write_record(record).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+#[test]
+fn async_container_reader_streams_records() {
+    let file = container_file(Codec::Null, &readings(30));
+
+    let reader = AsyncContainerReader::new(&file[..]);
+    assert!(reader.schema().is_none());
+    let records = block_on_stream(reader.records::<Reading>()).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(records, readings(30));
+
+    let blocks = decode_in_chunks(&mut ContainerCodec::new(), &file[..]).unwrap();
+    assert_eq!(blocks.iter().map(|block| block.count).collect::<Vec<_>>(), vec![7, 7, 7, 7, 2]);
+
+    let err = decode_in_chunks(&mut ContainerCodec::new(), &file[..file.len() - 1]).err().unwrap();
+    assert!(err.reason.contains("ends partway through the block"), "{}", err.reason);
+    assert!(decode_in_chunks(&mut ContainerCodec::new(), &[]).is_err());
+
+    let err = decode_in_chunks(&mut ContainerCodec::new().max_block_length(16), &file[..]).err().unwrap();
+    assert!(err.reason.contains("more than the 16 allowed"), "{}", err.reason);
+}
+
+#[cfg(feature = "deflate")]
+#[test]
+fn async_container_reader_decompresses_blocks() {
+    let file = container_file(Codec::Deflate, &readings(30));
+    let records = block_on_stream(AsyncContainerReader::new(&file[..]).records::<Reading>()).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(records, readings(30));
+}
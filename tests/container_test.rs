@@ -0,0 +1,522 @@
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate avvy;
+extern crate integer_encoding;
+
+use std::io::Cursor;
+
+use integer_encoding::VarInt;
+
+use avvy::container::{ self, BlockIndex, Codec, ContainerReader, ContainerWriter, ParallelReader, SliceReader };
+
+const SCHEMA_STR: &str = r###"{
+    "type": "record",
+    "name": "reading",
+    "namespace": "test",
+    "fields": [
+        { "name": "sensor", "type": "string" },
+        { "name": "value", "type": ["null", "double"] }
+    ]
+}"###;
+
+const SYNC: [u8; 16] = [7, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+#[derive(Serialize,Deserialize,Debug,PartialEq)]
+struct Reading {
+    sensor: String,
+    value: Option<f64>,
+}
+
+#[derive(Deserialize,Debug,PartialEq)]
+struct ReadingRef<'a> {
+    sensor: &'a str,
+    value: Option<f64>,
+}
+
+fn long(val: i64) -> Vec<u8> {
+    val.encode_var_vec()
+}
+
+fn reading(sensor: &str, value: Option<f64>) -> Reading {
+    Reading { sensor: sensor.into(), value }
+}
+
+fn container(blocks: &[&[Reading]]) -> Vec<u8> {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+
+    let mut file = b"Obj\x01".to_vec();
+    file.extend(long(1));
+    file.extend(long(11));
+    file.extend(b"avro.schema");
+    file.extend(long(SCHEMA_STR.len() as i64));
+    file.extend(SCHEMA_STR.as_bytes());
+    file.extend(long(0));
+    file.extend(&SYNC);
+
+    for block in blocks {
+        let mut data = Vec::new();
+        for record in block.iter() {
+            data.extend(avvy::to_vec(&schema, record).unwrap());
+        }
+        file.extend(long(block.len() as i64));
+        file.extend(long(data.len() as i64));
+        file.extend(data);
+        file.extend(&SYNC);
+    }
+    file
+}
+
+#[test]
+fn reads_typed_records_across_blocks() {
+    let file = container(&[&[reading("a", Some(1.5)), reading("b", None)], &[reading("c", Some(-2.0))]]);
+
+    let reader = ContainerReader::new(Cursor::new(file)).unwrap();
+    assert_eq!(reader.header().codec, "null");
+    assert_eq!(reader.schema().name, "reading");
+
+    let records : Vec<Reading> = reader.records().collect::<Result<_, _>>().unwrap();
+    assert_eq!(records, vec![reading("a", Some(1.5)), reading("b", None), reading("c", Some(-2.0))]);
+}
+
+#[test]
+fn reads_borrowed_records_and_values() {
+    use avvy::Value;
+
+    let file = container(&[&[reading("a", Some(1.5)), reading("b", None)]]);
+
+    let mut reader = ContainerReader::new(Cursor::new(&file[..])).unwrap();
+    let block = reader.next_block().unwrap().unwrap();
+    assert_eq!(block.count, 2);
+    let records : Vec<ReadingRef> = block.records(reader.schema()).collect::<Result<_, _>>().unwrap();
+    assert_eq!(records, vec![ReadingRef { sensor: "a", value: Some(1.5) }, ReadingRef { sensor: "b", value: None }]);
+    assert!(reader.next_block().unwrap().is_none());
+
+    let values : Vec<Value> = ContainerReader::new(Cursor::new(&file[..])).unwrap().values().collect::<Result<_, _>>().unwrap();
+    assert_eq!(values[1], Value::Record(vec![
+        ("sensor".into(), Value::String("b".into())),
+        ("value".into(), Value::Union(0, Box::new(Value::Null))),
+    ]));
+}
+
+#[test]
+fn rejects_bad_magic_and_sync() {
+    let mut file = container(&[&[reading("a", Some(1.5))]]);
+    let last = file.len() - 1;
+    file[last] ^= 0xff;
+
+    let err = ContainerReader::new(Cursor::new(&file[..])).unwrap().next_block().err().unwrap();
+    assert!(err.reason.starts_with("sync marker mismatch"));
+
+    file[0] = b'X';
+    assert!(ContainerReader::new(Cursor::new(&file[..])).is_err());
+
+    // a metadata value claiming far more bytes than the file holds
+    let mut file = b"Obj\x01".to_vec();
+    file.extend(long(1));
+    file.extend(long(11));
+    file.extend(b"avro.schema");
+    file.extend(long(1 << 60));
+    file.extend(SCHEMA_STR.as_bytes());
+    assert!(ContainerReader::new(Cursor::new(&file[..])).is_err());
+}
+
+#[test]
+fn writer_flushes_by_record_count_and_bytes() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let records : Vec<Reading> = (0..5).map(|i| reading(&format!("s{}", i), Some(i as f64))).collect();
+
+    let mut writer = ContainerWriter::new(&schema, Vec::new()).block_records(2);
+    for record in &records {
+        writer.write_record(record).unwrap();
+    }
+    let file = writer.finish().unwrap();
+
+    let mut reader = ContainerReader::new(Cursor::new(&file[..])).unwrap();
+    let mut counts = Vec::new();
+    while let Some(block) = reader.next_block().unwrap() {
+        counts.push(block.count);
+    }
+    assert_eq!(counts, vec![2, 2, 1]);
+
+    let read : Vec<Reading> = ContainerReader::new(Cursor::new(&file[..])).unwrap().records().collect::<Result<_, _>>().unwrap();
+    assert_eq!(read, records);
+
+    // each record is 12 bytes, so a 20 byte threshold flushes every second one
+    let mut writer = ContainerWriter::new(&schema, Vec::new()).block_bytes(20);
+    for record in &records {
+        writer.write_record(record).unwrap();
+    }
+    let file = writer.finish().unwrap();
+    let mut reader = ContainerReader::new(Cursor::new(&file[..])).unwrap();
+    let mut counts = Vec::new();
+    while let Some(block) = reader.next_block().unwrap() {
+        counts.push(block.count);
+    }
+    assert_eq!(counts, vec![2, 2, 1]);
+}
+
+#[test]
+fn writer_appends_to_existing_file() {
+    use avvy::Value;
+
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let mut writer = ContainerWriter::new(&schema, Cursor::new(Vec::new())).metadata("origin", b"test");
+    let sync = writer.sync_marker();
+    writer.write_record(&reading("a", None)).unwrap();
+    writer.flush().unwrap();
+    writer.write_record(&reading("b", Some(2.0))).unwrap();
+    let file = writer.finish().unwrap();
+
+    let mut writer = ContainerWriter::append(file).unwrap();
+    assert_eq!(writer.sync_marker(), sync);
+    assert!(writer.write_value(&Value::Record(vec![("sensor".into(), Value::Long(1))])).is_err());
+    writer.write_value(&Value::Record(vec![
+        ("sensor".into(), Value::String("c".into())),
+        ("value".into(), Value::Double(3.0)),
+    ])).unwrap();
+    let file = writer.finish().unwrap().into_inner();
+
+    let reader = ContainerReader::new(Cursor::new(&file[..])).unwrap();
+    assert_eq!(reader.header().metadata["origin"], b"test");
+    let read : Vec<Reading> = reader.records().collect::<Result<_, _>>().unwrap();
+    assert_eq!(read, vec![reading("a", None), reading("b", Some(2.0)), reading("c", Some(3.0))]);
+}
+
+#[test]
+fn codecs_round_trip_when_compiled_in() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let records : Vec<Reading> = (0..50).map(|i| reading("sensor", Some(i as f64))).collect();
+
+    for &codec in &[Codec::Null, Codec::Deflate, Codec::Snappy, Codec::Zstandard, Codec::Bzip2, Codec::Xz] {
+        let mut writer = ContainerWriter::with_codec(&schema, codec, Vec::new()).block_records(20);
+        if !codec.is_available() {
+            let err = writer.flush().err().unwrap();
+            assert!(err.reason.contains("wasn't compiled in"), "{}", err);
+            continue
+        }
+
+        for record in &records {
+            writer.write_record(record).unwrap();
+        }
+        let file = writer.finish().unwrap();
+        let reader = ContainerReader::new(Cursor::new(&file[..])).unwrap();
+        assert_eq!(reader.codec(), codec);
+        assert_eq!(Codec::from_name(&reader.header().codec).unwrap(), codec);
+        let read : Vec<Reading> = reader.records().collect::<Result<_, _>>().unwrap();
+        assert_eq!(read, records, "{:?}", codec);
+    }
+
+    assert_eq!(Codec::from_name("lz4").err().unwrap().reason, "unknown codec lz4");
+}
+
+#[cfg(feature = "snappy")]
+#[test]
+fn snappy_checks_crc() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let mut writer = ContainerWriter::with_codec(&schema, Codec::Snappy, Vec::new());
+    writer.write_record(&reading("a", None)).unwrap();
+    let mut file = writer.finish().unwrap();
+
+    // last byte of the crc trailer, just ahead of the sync marker
+    let crc = file.len() - 17;
+    file[crc] ^= 0xff;
+    let err = ContainerReader::new(Cursor::new(&file[..])).unwrap().next_block().err().unwrap();
+    assert!(err.reason.ends_with("snappy block failed its crc check"), "{}", err);
+}
+
+#[cfg(feature = "deflate")]
+#[test]
+fn appending_keeps_the_files_codec() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let mut writer = ContainerWriter::with_codec(&schema, Codec::Deflate, Cursor::new(Vec::new()));
+    writer.write_record(&reading("a", None)).unwrap();
+    let file = writer.finish().unwrap();
+
+    let mut writer = ContainerWriter::append(file).unwrap();
+    writer.write_record(&reading("b", None)).unwrap();
+    let file = writer.finish().unwrap().into_inner();
+
+    let reader = ContainerReader::new(Cursor::new(&file[..])).unwrap();
+    assert_eq!(reader.codec(), Codec::Deflate);
+    let read : Vec<Reading> = reader.records().collect::<Result<_, _>>().unwrap();
+    assert_eq!(read, vec![reading("a", None), reading("b", None)]);
+}
+
+fn damaged() -> (Vec<u8>, Vec<Reading>) {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let records : Vec<Reading> = (0..6).map(|i| reading(&format!("s{}", i), Some(i as f64))).collect();
+
+    let mut writer = ContainerWriter::new(&schema, Vec::new()).block_records(2);
+    let sync = writer.sync_marker();
+    for record in &records {
+        writer.write_record(record).unwrap();
+    }
+    let mut file = writer.finish().unwrap();
+
+    // cut a few bytes out of the second block's data
+    let second = file.windows(16).position(|window| window == sync).unwrap();
+    let second = second + 16 + file[second + 16..].windows(16).position(|window| window == sync).unwrap() + 16;
+    file.drain(second + 5..second + 9);
+    (file, records)
+}
+
+#[test]
+fn recovery_skips_damaged_blocks() {
+    let (file, records) = damaged();
+
+    let read : Result<Vec<Reading>, _> = ContainerReader::new(Cursor::new(&file[..])).unwrap().records().collect();
+    assert!(read.is_err());
+
+    let mut records_read = ContainerReader::new(Cursor::new(&file[..])).unwrap().recovery(true).records::<Reading>();
+    let read : Vec<Reading> = records_read.by_ref().collect::<Result<_, _>>().unwrap();
+    assert_eq!(read.iter().collect::<Vec<_>>(), vec![&records[0], &records[1], &records[4], &records[5]]);
+
+    let bad_blocks = records_read.reader().bad_blocks();
+    assert_eq!(bad_blocks.len(), 1);
+    assert_eq!(bad_blocks[0].count, Some(2));
+}
+
+#[test]
+fn iterators_stop_after_a_record_that_doesnt_decode() {
+    let mut file = container(&[&[reading("zz", None)], &[reading("b", None)]]);
+    // the string's length runs past the end of its block
+    let length = file.windows(3).position(|window| window == [4, b'z', b'z']).unwrap();
+    file[length] = 0x7e;
+
+    let read = ContainerReader::new(Cursor::new(&file[..])).unwrap().records::<Reading>().collect::<Vec<_>>();
+    assert_eq!(read.len(), 1);
+    assert!(read[0].is_err());
+    let read = ContainerReader::new(Cursor::new(&file[..])).unwrap().values().filter_map(Result::ok).collect::<Vec<_>>();
+    assert!(read.is_empty());
+}
+
+#[test]
+fn block_iterators_stop_after_a_record_that_doesnt_decode() {
+    let mut file = container(&[&[reading("zz", None), reading("b", None), reading("c", None)]]);
+    // the first string's length runs into the records after it
+    let length = file.windows(3).position(|window| window == [4, b'z', b'z']).unwrap();
+    file[length] = 2;
+
+    let mut reader = ContainerReader::new(Cursor::new(&file[..])).unwrap();
+    let block = reader.next_block().unwrap().unwrap();
+    let read = block.records::<Reading>(reader.schema()).collect::<Vec<_>>();
+    assert_eq!(read.len(), 1);
+    assert!(read[0].is_err());
+    let read = block.values(reader.schema()).collect::<Vec<_>>();
+    assert_eq!(read.len(), 1);
+    assert!(read[0].is_err());
+}
+
+#[cfg(feature = "snappy")]
+#[test]
+fn recovery_skips_blocks_that_dont_decompress() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let mut writer = ContainerWriter::with_codec(&schema, Codec::Snappy, Vec::new()).block_records(1);
+    for record in &[reading("a", None), reading("b", None)] {
+        writer.write_record(record).unwrap();
+    }
+    let mut file = writer.finish().unwrap();
+
+    // the first block's crc trailer
+    let sync = file.len() - 16;
+    let first = file[..sync].windows(16).rposition(|window| window == &file[sync..]).unwrap();
+    file[first - 1] ^= 0xff;
+
+    let mut reader = ContainerReader::new(Cursor::new(&file[..])).unwrap().recovery(true);
+    let block = reader.next_block().unwrap().unwrap();
+    assert_eq!(block.records::<Reading>(reader.schema()).next().unwrap().unwrap(), reading("b", None));
+    assert!(reader.next_block().unwrap().is_none());
+    assert!(reader.bad_blocks()[0].error.reason.ends_with("snappy block failed its crc check"));
+}
+
+#[test]
+fn repair_copies_intact_blocks() {
+    let (file, records) = damaged();
+
+    let (repaired, bad_blocks) = container::repair(Cursor::new(&file[..]), Vec::new()).unwrap();
+    assert_eq!(bad_blocks.len(), 1);
+
+    let reader = ContainerReader::new(Cursor::new(&repaired[..])).unwrap();
+    let read : Vec<Reading> = reader.records().collect::<Result<_, _>>().unwrap();
+    assert_eq!(read.len(), 4);
+    assert_eq!(read[2], records[4]);
+
+    // the schema is copied as written, not re-serialized
+    let file = container(&[&[reading("a", None)]]);
+    let (repaired, _) = container::repair(Cursor::new(&file[..]), Vec::new()).unwrap();
+    let reader = ContainerReader::new(Cursor::new(&repaired[..])).unwrap();
+    assert_eq!(reader.header().metadata["avro.schema"], SCHEMA_STR.as_bytes());
+}
+
+fn numbered(records: usize, block_records: usize) -> Vec<u8> {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let mut writer = ContainerWriter::new(&schema, Vec::new()).block_records(block_records);
+    for i in 0..records {
+        writer.write_record(&reading(&format!("s{}", i), Some(i as f64))).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+#[test]
+fn seeks_to_records_through_an_index() {
+    let file = numbered(25, 10);
+
+    let mut reader = ContainerReader::new(Cursor::new(&file[..])).unwrap();
+    let first : Reading = reader.next_record().unwrap().unwrap();
+    let index = reader.build_index().unwrap();
+    assert_eq!(index.records(), 25);
+    assert_eq!(index.entries().iter().map(|entry| (entry.first_record, entry.count)).collect::<Vec<_>>(), vec![(0, 10), (10, 10), (20, 5)]);
+    assert_eq!(index.find(19).unwrap().first_record, 10);
+    assert!(index.find(25).is_none());
+
+    // building the index doesn't move the reader
+    assert_eq!(first, reading("s0", Some(0.0)));
+    assert_eq!(reader.next_record::<Reading>().unwrap().unwrap(), reading("s1", Some(1.0)));
+
+    reader.seek_record(&index, 17).unwrap();
+    let rest : Vec<Reading> = reader.records().collect::<Result<_, _>>().unwrap();
+    assert_eq!(rest.len(), 8);
+    assert_eq!(rest[0], reading("s17", Some(17.0)));
+
+    let mut reader = ContainerReader::new(Cursor::new(&file[..])).unwrap();
+    let block = reader.read_block_at(index.entries()[2].offset).unwrap();
+    let records : Vec<Reading> = block.records(reader.schema()).collect::<Result<_, _>>().unwrap();
+    assert_eq!(records[0], reading("s20", Some(20.0)));
+    assert!(reader.seek_record(&index, 30).err().unwrap().reason.contains("past the end"));
+}
+
+#[test]
+fn keeps_the_index_in_a_sidecar() {
+    use std::fs;
+
+    let path = std::env::temp_dir().join(format!("avvy-index-{}.avro", std::process::id()));
+    let sidecar = BlockIndex::sidecar_path(&path);
+    fs::write(&path, numbered(25, 10)).unwrap();
+    let _ = fs::remove_file(&sidecar);
+
+    let index = BlockIndex::load_or_build(&path).unwrap();
+    assert_eq!(BlockIndex::read(&mut fs::File::open(&sidecar).unwrap()).unwrap(), index);
+    assert_eq!(BlockIndex::load_or_build(&path).unwrap(), index);
+
+    // appending makes the sidecar stale
+    let file = fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+    let mut writer = ContainerWriter::append(file).unwrap();
+    writer.write_record(&reading("s25", None)).unwrap();
+    writer.finish().unwrap();
+    assert_eq!(BlockIndex::load_or_build(&path).unwrap().records(), 26);
+
+    // a sidecar that can't be written still leaves an index
+    fs::remove_file(&sidecar).unwrap();
+    fs::create_dir(&sidecar).unwrap();
+    assert_eq!(BlockIndex::load_or_build(&path).unwrap().records(), 26);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_dir(&sidecar).unwrap();
+}
+
+#[test]
+fn decodes_blocks_in_parallel() {
+    let file = numbered(1000, 7);
+    let expected : Vec<Reading> = ContainerReader::new(Cursor::new(file.clone())).unwrap().records().collect::<Result<_, _>>().unwrap();
+
+    let reader = ContainerReader::new(Cursor::new(file.clone())).unwrap();
+    let read : Vec<Reading> = ParallelReader::new(reader).threads(4).records().collect::<Result<_, _>>().unwrap();
+    assert_eq!(read, expected);
+
+    let reader = ContainerReader::new(Cursor::new(file.clone())).unwrap();
+    let mut read : Vec<Reading> = ParallelReader::new(reader).threads(4).ordered(false).records().collect::<Result<_, _>>().unwrap();
+    read.sort_by(|a, b| a.value.partial_cmp(&b.value).unwrap());
+    assert_eq!(read, expected);
+
+    let reader = ContainerReader::new(Cursor::new(file.clone())).unwrap();
+    let values : Vec<avvy::Value> = ParallelReader::new(reader).threads(4).values().collect::<Result<_, _>>().unwrap();
+    assert_eq!(values[999], avvy::Value::Record(vec![
+        ("sensor".into(), avvy::Value::String("s999".into())),
+        ("value".into(), avvy::Value::Union(1, Box::new(avvy::Value::Double(999.0)))),
+    ]));
+
+    // stopping early winds the pool down
+    let reader = ContainerReader::new(Cursor::new(file)).unwrap();
+    let first : Vec<Reading> = ParallelReader::new(reader).threads(2).records().take(3).collect::<Result<_, _>>().unwrap();
+    assert_eq!(first, expected[..3]);
+}
+
+#[test]
+fn parallel_decoding_reports_bad_blocks() {
+    let (file, _) = damaged();
+
+    let reader = ContainerReader::new(Cursor::new(file)).unwrap();
+    let read : Vec<Result<Reading, _>> = ParallelReader::new(reader).threads(3).records().collect();
+    assert_eq!(read.iter().filter(|record| record.is_ok()).count(), 2);
+    assert!(read.last().unwrap().is_err());
+}
+
+#[test]
+fn parallel_recovery_keeps_bad_blocks() {
+    let (file, records) = damaged();
+
+    let reader = ContainerReader::new(Cursor::new(file)).unwrap().recovery(true);
+    let mut records_read = ParallelReader::new(reader).threads(3).records::<Reading>();
+    let read : Vec<Reading> = records_read.by_ref().collect::<Result<_, _>>().unwrap();
+    assert_eq!(read.iter().collect::<Vec<_>>(), vec![&records[0], &records[1], &records[4], &records[5]]);
+
+    let bad_blocks = records_read.bad_blocks();
+    assert_eq!(bad_blocks.len(), 1);
+    assert_eq!(bad_blocks[0].count, Some(2));
+}
+
+#[test]
+fn borrows_records_from_the_buffer() {
+    let file = numbered(25, 10);
+
+    let reader = SliceReader::new(&file[..]).unwrap();
+    assert_eq!(reader.blocks().map(|block| block.unwrap().count).collect::<Vec<_>>(), vec![10, 10, 5]);
+    let records : Vec<ReadingRef> = reader.records().collect::<Result<_, _>>().unwrap();
+    assert_eq!(records.len(), 25);
+    assert_eq!(records[24], ReadingRef { sensor: "s24", value: Some(24.0) });
+    // the strings point into the file itself
+    let range = file.as_ptr() as usize..file.as_ptr() as usize + file.len();
+    assert!(range.contains(&(records[24].sensor.as_ptr() as usize)));
+
+    assert_eq!(reader.values().count(), 25);
+
+    let (damaged, _) = damaged();
+    let reader = SliceReader::new(&damaged[..]).unwrap();
+    let records : Vec<Result<ReadingRef, _>> = reader.records().collect();
+    assert!(records[2].as_ref().err().unwrap().reason.starts_with("block at offset"));
+    assert_eq!(records.len(), 3);
+
+    // borrowed strings are checked like owned ones
+    let mut file = container(&[&[reading("zz", None)]]);
+    let string = file.windows(3).position(|window| window == [4, b'z', b'z']).unwrap();
+    file[string + 1] = 0xff;
+    let reader = SliceReader::new(&file[..]).unwrap();
+    let err = reader.records::<ReadingRef>().next().unwrap().unwrap_err();
+    assert!(err.reason.contains("invalid utf-8 in string"), "{}", err.reason);
+}
+
+#[cfg(feature = "deflate")]
+#[test]
+fn borrowing_needs_an_uncompressed_file() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let mut writer = ContainerWriter::with_codec(&schema, Codec::Deflate, Vec::new());
+    writer.write_record(&reading("a", None)).unwrap();
+    let file = writer.finish().unwrap();
+
+    assert!(SliceReader::new(file).err().unwrap().reason.contains("deflate compressed"));
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn reads_a_mapped_file() {
+    use avvy::container::MmapReader;
+
+    let path = std::env::temp_dir().join(format!("avvy-mmap-{}.avro", std::process::id()));
+    std::fs::write(&path, numbered(25, 10)).unwrap();
+
+    let reader = MmapReader::open(&path).unwrap();
+    let records : Vec<ReadingRef> = reader.records().collect::<Result<_, _>>().unwrap();
+    assert_eq!(records[3], ReadingRef { sensor: "s3", value: Some(3.0) });
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,221 @@
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate avvy;
+
+use std::collections::HashMap;
+
+use serde::de::Deserialize;
+
+use avvy::{ Schema, Value };
+use avvy::message::{ SingleObjectDecoder, SingleObjectEncoder };
+
+const V1: &str = r###"{
+    "type": "record",
+    "name": "reading",
+    "namespace": "test",
+    "fields": [
+        { "name": "sensor", "type": "string" },
+        { "name": "value", "type": ["null", "int"] },
+        { "name": "raw", "type": { "type": "fixed", "name": "raw_t", "size": 2 } }
+    ]
+}"###;
+
+// drops raw, widens value and adds unit
+const V2: &str = r###"{
+    "type": "record",
+    "name": "reading",
+    "namespace": "test",
+    "fields": [
+        { "name": "value", "type": ["null", "double"] },
+        { "name": "sensor", "type": "string" },
+        { "name": "unit", "type": "string", "default": "C" }
+    ]
+}"###;
+
+#[derive(Serialize,Deserialize,Debug,PartialEq)]
+struct ReadingV1<'a> {
+    sensor: &'a str,
+    value: Option<i32>,
+    #[serde(with = "fixed")]
+    raw: [u8; 2],
+}
+
+#[derive(Deserialize,Debug,PartialEq)]
+struct ReadingV2 {
+    value: Option<f64>,
+    sensor: String,
+    unit: String,
+}
+
+mod fixed {
+    use serde::{ Deserialize, Deserializer, Serializer };
+
+    pub fn serialize<S: Serializer>(raw: &[u8; 2], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(raw)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 2], D::Error> {
+        let raw = <&[u8]>::deserialize(deserializer)?;
+        Ok([raw[0], raw[1]])
+    }
+}
+
+fn store(schemas: &[&Schema]) -> HashMap<u64, Schema> {
+    schemas.iter().map(|schema| (schema.fingerprint(), (*schema).clone())).collect()
+}
+
+#[test]
+fn fingerprints_the_canonical_form() {
+    assert_eq!(avvy::rabin_fingerprint(b"\"null\""), 7195948357588979594);
+
+    let schema = Schema::from_str(V1).unwrap();
+    assert_eq!(schema.canonical_form(), concat!(
+        r#"{"name":"test.reading","type":"record","fields":["#,
+        r#"{"name":"sensor","type":"string"},"#,
+        r#"{"name":"value","type":["null","int"]},"#,
+        r#"{"name":"raw","type":{"name":"test.raw_t","type":"fixed","size":2}}]}"#));
+    assert_eq!(schema.fingerprint(), avvy::rabin_fingerprint(schema.canonical_form().as_bytes()));
+
+    // whitespace and defaults don't change the fingerprint
+    let v2 = Schema::from_str(V2).unwrap();
+    let compact = Schema::from_str(&v2.to_json().replace("\"default\":\"C\"", "\"default\":\"F\"")).unwrap();
+    assert_eq!(compact.fingerprint(), v2.fingerprint());
+}
+
+#[test]
+fn round_trips_single_objects() {
+    let schema = Schema::from_str(V1).unwrap();
+    let encoder = SingleObjectEncoder::new(&schema);
+    let record = ReadingV1 { sensor: "a", value: Some(7), raw: [1, 2] };
+    let message = encoder.encode(&record).unwrap();
+    assert_eq!(&message[..2], &[0xC3, 0x01]);
+    assert_eq!(&message[2..10], &schema.fingerprint().to_le_bytes());
+
+    let decoder = SingleObjectDecoder::new(store(&[&schema]));
+    assert_eq!(decoder.decode_borrowed::<ReadingV1>(&message).unwrap(), record);
+    let value = decoder.decode_value(&message).unwrap();
+    assert_eq!(encoder.encode_value(&value).unwrap(), message);
+
+    let mut trailing = message.clone();
+    trailing.push(0);
+    assert!(decoder.decode_value(&trailing).err().unwrap().reason.contains("1 bytes left"));
+    assert!(decoder.decode_value(&message[1..]).err().unwrap().reason.starts_with("not a single object"));
+
+    let stranger = SingleObjectDecoder::new(HashMap::new());
+    assert!(stranger.decode_value(&message).err().unwrap().reason.starts_with("no schema with fingerprint"));
+}
+
+#[test]
+fn resolves_to_the_reader_schema() {
+    let v1 = Schema::from_str(V1).unwrap();
+    let v2 = Schema::from_str(V2).unwrap();
+    let message = SingleObjectEncoder::new(&v1).encode(&ReadingV1 { sensor: "a", value: Some(7), raw: [1, 2] }).unwrap();
+
+    let decoder = SingleObjectDecoder::new(store(&[&v1, &v2])).reader_schema(&v2);
+    assert_eq!(decoder.decode::<ReadingV2>(&message).unwrap(), ReadingV2 { value: Some(7.0), sensor: "a".into(), unit: "C".into() });
+    assert_eq!(decoder.decode_value(&message).unwrap(), Value::Record(vec![
+        ("value".into(), Value::Union(1, Box::new(Value::Double(7.0)))),
+        ("sensor".into(), Value::String("a".into())),
+        ("unit".into(), Value::String("C".into())),
+    ]));
+    assert!(decoder.decode_borrowed::<ReadingV2>(&message).is_err());
+
+    // v1 can't read v2's data, raw has no default
+    let message = SingleObjectEncoder::new(&v2).encode_value(&decoder.decode_value(&message).unwrap()).unwrap();
+    let decoder = SingleObjectDecoder::new(store(&[&v1, &v2])).reader_schema(&v1);
+    assert_eq!(decoder.decode_value(&message).err().unwrap().reason, "reading.raw: not in the writer's schema and has no default");
+}
+
+#[test]
+fn rejects_int_defaults_out_of_range() {
+    let v1 = Schema::from_str(V1).unwrap();
+    let reader = Schema::from_str(r###"{
+        "type": "record",
+        "name": "reading",
+        "namespace": "test",
+        "fields": [
+            { "name": "sensor", "type": "string" },
+            { "name": "count", "type": "int", "default": 1099511627776 }
+        ]
+    }"###).unwrap();
+    let message = SingleObjectEncoder::new(&v1).encode(&ReadingV1 { sensor: "a", value: None, raw: [1, 2] }).unwrap();
+
+    let decoder = SingleObjectDecoder::new(store(&[&v1])).reader_schema(&reader);
+    assert!(decoder.decode_value(&message).err().unwrap().reason.contains("1099511627776 is out of range for an int"));
+}
+
+#[test]
+fn frames_confluent_messages() {
+    use avvy::message::{ self, ConfluentEncoder };
+
+    let schema = Schema::from_str(V1).unwrap();
+    let record = ReadingV1 { sensor: "a", value: None, raw: [1, 2] };
+    let framed = ConfluentEncoder::new(&schema, 618).encode(&record).unwrap();
+    assert_eq!(&framed[..5], &[0, 0, 0, 2, 106]);
+
+    let mut de = avvy::AvroDeserializer::from_slice(&schema, &framed);
+    assert_eq!(de.read_confluent_header().unwrap(), 618);
+    assert_eq!(ReadingV1::deserialize(&mut de).unwrap(), record);
+
+    let mut bad = framed.clone();
+    bad[0] = 1;
+    assert_eq!(message::read_confluent_header(&bad).err().unwrap().reason, "not a confluent framed message, magic byte was 1");
+    assert!(message::read_confluent_header(&framed[..3]).is_err());
+}
+
+#[test]
+fn reads_protobuf_message_indexes() {
+    use avvy::message;
+
+    for indexes in &[vec![0], vec![1], vec![2, 0, 3]] {
+        let mut buf = Vec::new();
+        message::write_message_indexes(indexes, &mut buf);
+        buf.push(42);
+        assert_eq!(message::read_message_indexes(&buf).unwrap(), (indexes.clone(), &[42][..]));
+    }
+
+    // the shorthand for the first message type
+    assert_eq!(message::read_message_indexes(&[0]).unwrap().0, vec![0]);
+    assert!(message::read_message_indexes(&[6]).is_err());
+}
+
+#[test]
+fn decodes_by_schema_id() {
+    use std::fs;
+    use avvy::message::{ ConfluentDecoder, ConfluentEncoder, ConfluentError };
+
+    let v1 = Schema::from_str(V1).unwrap();
+    let v2 = Schema::from_str(V2).unwrap();
+
+    let dir = std::env::temp_dir().join(format!("avvy-schemas-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("1.avsc"), V1).unwrap();
+    fs::write(dir.join("README"), "not a schema").unwrap();
+
+    let mut decoder = ConfluentDecoder::new(&v2);
+    assert_eq!(decoder.load_dir(&dir).unwrap(), 1);
+    decoder.add_schema(2, &v2).unwrap();
+    assert_eq!(decoder.schema_ids(), vec![1, 2]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let old = ConfluentEncoder::new(&v1, 1).encode(&ReadingV1 { sensor: "a", value: Some(3), raw: [0, 0] }).unwrap();
+    let new = ConfluentEncoder::new(&v2, 2).encode_value(&Value::Record(vec![
+        ("value".into(), Value::Union(0, Box::new(Value::Null))),
+        ("sensor".into(), Value::String("b".into())),
+        ("unit".into(), Value::String("F".into())),
+    ])).unwrap();
+
+    assert_eq!(decoder.decode::<ReadingV2>(&old).unwrap(), ReadingV2 { value: Some(3.0), sensor: "a".into(), unit: "C".into() });
+    assert_eq!(decoder.decode::<ReadingV2>(&new).unwrap(), ReadingV2 { value: None, sensor: "b".into(), unit: "F".into() });
+    assert!(decoder.decode_borrowed::<ReadingV2>(&old).is_err());
+
+    let mut unknown = new.clone();
+    unknown[4] = 99;
+    match decoder.decode_value(&unknown) {
+        Err(ConfluentError::UnknownSchemaId(99)) => {},
+        other => panic!("expected an unknown schema id, got {:?}", other),
+    }
+
+    // v2 data can't be read as v1, which has no default for raw
+    assert!(ConfluentDecoder::new(&v1).add_schema(2, &v2).is_err());
+}
@@ -0,0 +1,240 @@
+#![cfg(feature = "parquet")]
+
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate avvy;
+extern crate arrow_array;
+extern crate arrow_schema;
+extern crate arrow_select;
+extern crate parquet;
+
+use std::fs::{ self, File };
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{ Float64Type, Int64Type, TimestampMillisecondType };
+use arrow_array::{ Array, RecordBatch };
+use arrow_schema::{ DataType, TimeUnit };
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::basic::Compression;
+
+use avvy::columnar::ParquetWriter;
+use avvy::message::{ ConfluentDecoder, ConfluentEncoder };
+
+const SCHEMA_STR: &str = r###"{
+    "type": "record",
+    "name": "sample",
+    "namespace": "test",
+    "fields": [
+        { "name": "at", "type": { "type": "long", "logicalType": "timestamp-millis" } },
+        { "name": "count", "type": ["int", "long"] },
+        { "name": "value", "type": ["null", "int", "long", "double"] },
+        { "name": "label", "type": ["long", "string"] },
+        { "name": "tags", "type": ["null", { "type": "map", "values": "string" }] }
+    ]
+}"###;
+
+// the same record from before `value` could be anything but an int
+const OLD_SCHEMA_STR: &str = r###"{
+    "type": "record",
+    "name": "sample",
+    "namespace": "test",
+    "fields": [
+        { "name": "at", "type": { "type": "long", "logicalType": "timestamp-millis" } },
+        { "name": "count", "type": ["int", "long"] },
+        { "name": "value", "type": ["null", "int"] },
+        { "name": "label", "type": ["long", "string"] },
+        { "name": "tags", "type": ["null", { "type": "map", "values": "string" }] }
+    ]
+}"###;
+
+#[derive(Serialize,Deserialize,Debug,PartialEq,Clone)]
+enum Count {
+    Int(i32),
+    Long(i64),
+}
+
+#[derive(Serialize,Deserialize,Debug,PartialEq,Clone)]
+enum Number {
+    Null,
+    Int(i32),
+    Long(i64),
+    Double(f64),
+}
+
+#[derive(Serialize,Deserialize,Debug,PartialEq,Clone)]
+enum Label {
+    Long(i64),
+    Str(String),
+}
+
+#[derive(Serialize,Deserialize,Debug,PartialEq,Clone)]
+struct Sample {
+    at: i64,
+    count: Count,
+    value: Number,
+    label: Label,
+    tags: Option<Vec<(String, String)>>,
+}
+
+#[derive(Serialize,Deserialize,Debug,PartialEq,Clone)]
+struct OldSample {
+    at: i64,
+    count: Count,
+    value: Option<i32>,
+    label: Label,
+    tags: Option<Vec<(String, String)>>,
+}
+
+fn samples(count: usize) -> Vec<Sample> {
+    (0..count).map(|i| Sample {
+        at: 1_600_000_000_000 + i as i64,
+        count: if i % 2 == 0 { Count::Int(i as i32) } else { Count::Long(i as i64 * 1_000_000_000_000) },
+        value: match i % 4 { 0 => Number::Null, 1 => Number::Int(i as i32), 2 => Number::Long(i as i64), _ => Number::Double(i as f64 + 0.5) },
+        label: if i % 3 == 0 { Label::Str(format!("l{}", i)) } else { Label::Long(i as i64) },
+        tags: if i % 2 == 0 { Some(vec![("k".into(), format!("v{}", i))]) } else { None },
+    }).collect()
+}
+
+fn read_back(path: &std::path::Path) -> (Vec<RecordBatch>, usize, Compression) {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap()).unwrap();
+    let row_groups = builder.metadata().num_row_groups();
+    let compression = builder.metadata().row_group(0).column(0).compression();
+    let batches = builder.build().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+    (batches, row_groups, compression)
+}
+
+fn check_rows(batches: &[RecordBatch], samples: &[Sample]) {
+    let batch = arrow_select::concat::concat_batches(&batches[0].schema(), batches).unwrap();
+    assert_eq!(batch.num_rows(), samples.len());
+
+    let at = batch.column(0).as_primitive::<TimestampMillisecondType>();
+    let count = batch.column(1).as_primitive::<Int64Type>();
+    let value = batch.column(2).as_primitive::<Float64Type>();
+    let label = batch.column(3).as_struct();
+    let tags = batch.column(4).as_map();
+
+    for (row, sample) in samples.iter().enumerate() {
+        assert_eq!(at.value(row), sample.at);
+        match sample.count {
+            Count::Int(val) => assert_eq!(count.value(row), val as i64),
+            Count::Long(val) => assert_eq!(count.value(row), val),
+        }
+        match sample.value {
+            Number::Null => assert!(value.is_null(row)),
+            Number::Int(val) => assert_eq!(value.value(row), val as f64),
+            Number::Long(val) => assert_eq!(value.value(row), val as f64),
+            Number::Double(val) => assert_eq!(value.value(row), val),
+        }
+        let (long, string) = (label.column(0).as_primitive::<Int64Type>(), label.column(1).as_string::<i32>());
+        match sample.label {
+            Label::Long(val) => assert!(long.value(row) == val && string.is_null(row)),
+            Label::Str(ref val) => assert!(long.is_null(row) && string.value(row) == val),
+        }
+        assert_eq!(tags.is_valid(row), sample.tags.is_some());
+    }
+}
+
+#[test]
+fn parquet_schema_maps_unions() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let parquet = avvy::columnar::parquet_schema(&schema);
+
+    assert_eq!(parquet.field(0).data_type(), &DataType::Timestamp(TimeUnit::Millisecond, Some("+00:00".into())));
+    assert_eq!(parquet.field(1).data_type(), &DataType::Int64);
+    assert_eq!(parquet.field(2).data_type(), &DataType::Float64);
+    assert!(parquet.field(2).is_nullable());
+    match *parquet.field(3).data_type() {
+        DataType::Struct(ref members) => {
+            let members = members.iter().map(|member| (member.name().clone(), member.data_type().clone())).collect::<Vec<_>>();
+            assert_eq!(members, vec![("long".into(), DataType::Int64), ("string".into(), DataType::Utf8)]);
+        },
+        ref other => panic!("label is a {}", other),
+    }
+}
+
+#[test]
+#[cfg(feature = "snappy")]
+fn container_exports_to_parquet() {
+    use avvy::container::{ Codec, ContainerReader, ContainerWriter };
+
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let samples = samples(10);
+    let mut writer = ContainerWriter::new(&schema, Vec::new()).block_records(3);
+    for sample in &samples {
+        writer.write_record(sample).unwrap();
+    }
+    let file = writer.finish().unwrap();
+
+    let path = std::env::temp_dir().join(format!("avvy-parquet-{}.parquet", std::process::id()));
+    let mut parquet = ParquetWriter::new(&schema, File::create(&path).unwrap()).unwrap()
+        .row_group_size(4)
+        .compression(Codec::Snappy);
+    assert_eq!(parquet.write_container(ContainerReader::new(&file[..]).unwrap()).unwrap(), 10);
+    parquet.finish().unwrap();
+
+    let (batches, row_groups, compression) = read_back(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(row_groups, 3);
+    assert_eq!(compression, Compression::SNAPPY);
+    check_rows(&batches, &samples);
+
+    let other = avvy::Schema::from_str(OLD_SCHEMA_STR).unwrap();
+    let mut parquet = ParquetWriter::new(&other, Vec::new()).unwrap();
+    assert!(parquet.write_container(ContainerReader::new(&file[..]).unwrap()).unwrap_err().reason.starts_with("container holds"));
+    assert_eq!(parquet.rows(), 0);
+    assert!(ParquetWriter::new(&schema, Vec::new()).unwrap().compression(Codec::Bzip2).finish().is_err());
+}
+
+#[test]
+fn confluent_dump_exports_to_parquet() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let old = avvy::Schema::from_str(OLD_SCHEMA_STR).unwrap();
+    let mut decoder = ConfluentDecoder::new(&schema);
+    decoder.add_schema(1, &old).unwrap();
+    decoder.add_schema(2, &schema).unwrap();
+
+    // old messages resolve to the current schema
+    let mut samples = samples(6);
+    let mut dump = Vec::new();
+    for (i, sample) in samples.iter_mut().enumerate() {
+        let message = if i < 3 {
+            let value = if i == 0 { None } else { Some(i as i32) };
+            sample.value = value.map_or(Number::Null, Number::Int);
+            let old_sample = OldSample { at: sample.at, count: sample.count.clone(), value, label: sample.label.clone(), tags: sample.tags.clone() };
+            ConfluentEncoder::new(&old, 1).encode(&old_sample).unwrap()
+        } else {
+            ConfluentEncoder::new(&schema, 2).encode(sample).unwrap()
+        };
+        dump.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        dump.extend_from_slice(&message);
+    }
+
+    let path = std::env::temp_dir().join(format!("avvy-parquet-dump-{}.parquet", std::process::id()));
+    let mut parquet = ParquetWriter::new(&schema, File::create(&path).unwrap()).unwrap();
+    assert_eq!(parquet.write_message_dump(&decoder, &dump[..]).unwrap(), 6);
+    assert_eq!(parquet.rows(), 6);
+    parquet.finish().unwrap();
+
+    let (batches, row_groups, compression) = read_back(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(row_groups, 1);
+    assert_eq!(compression, Compression::UNCOMPRESSED);
+    check_rows(&batches, &samples);
+
+    // a message cut short
+    let mut parquet = ParquetWriter::new(&schema, Vec::new()).unwrap();
+    assert!(parquet.write_message_dump(&decoder, &dump[..dump.len() - 2]).unwrap_err().reason.starts_with("message 5"));
+
+    // a message holding more than its record leaves nothing behind
+    let mut parquet = ParquetWriter::new(&schema, File::create(&path).unwrap()).unwrap();
+    let mut message = ConfluentEncoder::new(&schema, 2).encode(&samples[4]).unwrap();
+    parquet.write_message(&decoder, &message[..]).unwrap();
+    message.extend(avvy::to_vec(&schema, &samples[5]).unwrap());
+    assert!(parquet.write_message(&decoder, &message[..]).unwrap_err().reason.ends_with("bytes left after its record"));
+    assert_eq!(parquet.rows(), 1);
+    parquet.finish().unwrap();
+
+    let (batches, _, _) = read_back(&path);
+    fs::remove_file(&path).unwrap();
+    check_rows(&batches, &samples[4..5]);
+}
@@ -0,0 +1,235 @@
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate serde_json;
+extern crate avvy;
+
+use std::io::{ Read, Write };
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use std::thread;
+
+use avvy::Schema;
+use avvy::message::{ ConfluentDecoder, ConfluentEncoder, ConfluentError };
+use avvy::registry::RegistryClient;
+
+const SCHEMA_STR: &str = r###"{
+    "type": "record",
+    "name": "reading",
+    "namespace": "test",
+    "fields": [
+        { "name": "sensor", "type": "string" },
+        { "name": "value", "type": ["null", "double"] }
+    ]
+}"###;
+
+#[derive(Serialize,Deserialize,Debug,PartialEq)]
+struct Reading {
+    sensor: String,
+    value: Option<f64>,
+}
+
+/// Serves canned registry responses, counting the requests it gets
+fn stub<F>(respond: F) -> (String, Arc<AtomicUsize>)
+    where F: Fn(&str, &str, &str) -> (u16, String) + Send + 'static {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/registry/", listener.local_addr().unwrap());
+    let requests = Arc::new(AtomicUsize::new(0));
+
+    let counter = requests.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            // headers, then as much body as they announce
+            let (head, len) = loop {
+                let read = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..read]);
+                if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    let head = String::from_utf8(request[..end].to_vec()).unwrap();
+                    let len = head.lines().find(|line| line.to_lowercase().starts_with("content-length:"))
+                        .map_or(0, |line| line[15..].trim().parse().unwrap());
+                    if request.len() >= end + 4 + len {
+                        break (head, end + 4)
+                    }
+                }
+            };
+            counter.fetch_add(1, Ordering::SeqCst);
+
+            let mut start = head.split(' ');
+            let (method, path) = (start.next().unwrap().to_owned(), start.next().unwrap().to_owned());
+            let (status, body) = respond(&method, path.trim_start_matches("/registry"), std::str::from_utf8(&request[len..]).unwrap());
+            let response = if path.ends_with("latest") {
+                // chunked, like registries behind some proxies answer
+                format!("HTTP/1.1 {} X\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n", status, body.len(), body)
+            } else {
+                format!("HTTP/1.1 {} X\r\nContent-Length: {}\r\n\r\n{}", status, body.len(), body)
+            };
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    (url, requests)
+}
+
+fn schema_json() -> String {
+    serde_json::to_string(&Schema::from_str(SCHEMA_STR).unwrap().to_json()).unwrap()
+}
+
+fn registry(method: &str, path: &str, body: &str) -> (u16, String) {
+    match (method, path) {
+        ("GET", "/schemas/ids/7") => (200, format!("{{\"schema\":{}}}", schema_json())),
+        ("GET", "/subjects/readings-value/versions/latest") | ("GET", "/subjects/readings-value/versions/3") =>
+            (200, format!("{{\"subject\":\"readings-value\",\"version\":3,\"id\":7,\"schema\":{}}}", schema_json())),
+        // only the schema as written, not re-serialized
+        ("POST", "/subjects/readings-value/versions") if body.contains(&serde_json::to_string(SCHEMA_STR).unwrap()) => (200, "{\"id\":7}".into()),
+        _ => (404, "{\"error_code\":40403,\"message\":\"Schema not found\"}".into()),
+    }
+}
+
+fn dead_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+#[test]
+fn fetches_and_caches_schemas() {
+    let (url, requests) = stub(registry);
+    let client = RegistryClient::new(&url).unwrap();
+
+    let schema = client.schema_by_id(7).unwrap().unwrap();
+    assert_eq!(schema.name, "reading");
+    assert!(client.schema_by_id(7).unwrap().is_some());
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    assert!(client.schema_by_id(8).unwrap().is_none());
+
+    let latest = client.subject_version("readings-value", None).unwrap().unwrap();
+    assert_eq!((latest.version, latest.id), (3, 7));
+    let third = client.subject_version("readings-value", Some(3)).unwrap().unwrap();
+    assert_eq!(third.id, 7);
+    assert!(client.subject_version("readings-value", Some(4)).unwrap().is_none());
+
+    assert_eq!(client.register("readings-value", SCHEMA_STR).unwrap(), 7);
+    let err = client.register("other", SCHEMA_STR).err().unwrap();
+    assert!(err.reason.ends_with("registry returned 404: Schema not found (40403)"), "{}", err);
+    assert!(client.register("readings-value", "{").err().unwrap().reason.starts_with("schema doesn't parse"));
+
+    // a cache that can't be written doesn't fail the fetch
+    let file = std::env::temp_dir().join(format!("avvy-registry-file-{}", std::process::id()));
+    std::fs::write(&file, b"").unwrap();
+    let client = RegistryClient::new(&url).unwrap().cache_dir(&file);
+    assert!(client.schema_by_id(7).unwrap().is_some());
+    assert!(client.subject_version("readings-value", Some(3)).unwrap().is_some());
+    std::fs::remove_file(&file).unwrap();
+
+    assert!(RegistryClient::new("https://registry").is_err());
+}
+
+#[test]
+fn decodes_offline_from_the_disk_cache() {
+    let cache = std::env::temp_dir().join(format!("avvy-registry-{}", std::process::id()));
+    let schema = Schema::from_str(SCHEMA_STR).unwrap();
+    let message = ConfluentEncoder::new(&schema, 7).encode(&Reading { sensor: "a".into(), value: None }).unwrap();
+
+    let (url, _) = stub(registry);
+    let online = RegistryClient::new(&url).unwrap().cache_dir(&cache);
+    let decoder = ConfluentDecoder::new(&schema).source(online);
+    assert_eq!(decoder.decode::<Reading>(&message).unwrap(), Reading { sensor: "a".into(), value: None });
+    let latest = RegistryClient::new(&url).unwrap().cache_dir(&cache).subject_version("readings-value", None).unwrap();
+    assert!(latest.is_some());
+
+    // nothing is listening any more, the cache has to do
+    let offline = RegistryClient::new(&dead_url()).unwrap().cache_dir(&cache);
+    assert_eq!(offline.subject_version("readings-value", None).unwrap().unwrap().id, 7);
+    let decoder = ConfluentDecoder::new(&schema).source(offline);
+    assert_eq!(decoder.decode::<Reading>(&message).unwrap(), Reading { sensor: "a".into(), value: None });
+
+    let mut unknown = message.clone();
+    unknown[4] = 8;
+    match decoder.decode::<Reading>(&unknown) {
+        Err(ConfluentError::Avro(err)) => assert!(err.reason.starts_with("GET http://"), "{}", err),
+        other => panic!("expected a connection error, got {:?}", other),
+    }
+
+    std::fs::remove_dir_all(&cache).unwrap();
+}
+
+#[test]
+fn serves_a_registry_from_a_directory() {
+    use avvy::registry::RegistryServer;
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("avvy-registry-server-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    // a schema dropped in by hand gets an id when the registry opens
+    fs::create_dir_all(dir.join("seeded")).unwrap();
+    fs::write(dir.join("seeded").join("1.avsc"), SCHEMA_STR).unwrap();
+
+    let v1 = SCHEMA_STR;
+    let v2 = SCHEMA_STR.replace(r#"{ "name": "value""#, r#"{ "name": "unit", "type": "string", "default": "C" },
+        { "name": "value""#);
+    let broken = SCHEMA_STR.replace(r#""double""#, r#""string""#);
+
+    let running = RegistryServer::open(&dir).unwrap().serve("127.0.0.1:0").unwrap();
+    let client = RegistryClient::new(&running.url()).unwrap();
+    assert_eq!(client.subjects().unwrap(), vec!["seeded"]);
+    assert_eq!(client.subject_version("seeded", Some(1)).unwrap().unwrap().id, 1);
+
+    // the same schema has the same id under every subject
+    assert_eq!(client.register("readings-value", v1).unwrap(), 1);
+    assert_eq!(client.register("readings-value", v1).unwrap(), 1);
+    assert!(client.is_compatible("readings-value", &v2).unwrap());
+    assert!(!client.is_compatible("readings-value", &broken).unwrap());
+    assert!(client.register("readings-value", &broken).err().unwrap().reason.contains("incompatible"));
+    assert_eq!(client.register("readings-value", &v2).unwrap(), 2);
+    assert_eq!(client.versions("readings-value").unwrap(), vec![1, 2]);
+    assert!(client.versions("nope").unwrap().is_empty());
+    drop(running);
+
+    // ids survive a restart
+    let running = RegistryServer::open(&dir).unwrap().serve("127.0.0.1:0").unwrap();
+    let client = RegistryClient::new(&running.url()).unwrap();
+    let latest = client.subject_version("readings-value", None).unwrap().unwrap();
+    assert_eq!((latest.version, latest.id), (2, 2));
+    assert_eq!(client.schema_by_id(2).unwrap().unwrap().fields.len(), 3);
+    assert!(client.schema_by_id(3).unwrap().is_none());
+
+    let v1 = Schema::from_str(v1).unwrap();
+    let message = ConfluentEncoder::new(&v1, 1).encode(&Reading { sensor: "a".into(), value: Some(1.0) }).unwrap();
+    let decoder = ConfluentDecoder::new(&v1).source(client);
+    assert_eq!(decoder.decode::<Reading>(&message).unwrap(), Reading { sensor: "a".into(), value: Some(1.0) });
+
+    drop(running);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn registry_server_keeps_subjects_inside_its_directory() {
+    use avvy::registry::RegistryServer;
+    use std::fs;
+    use std::io::{ Read, Write };
+    use std::net::TcpStream;
+
+    let root = std::env::temp_dir().join(format!("avvy-registry-subjects-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let dir = root.join("registry");
+    fs::create_dir_all(&dir).unwrap();
+
+    let running = RegistryServer::open(&dir).unwrap().serve("127.0.0.1:0").unwrap();
+    let addr = running.url().trim_start_matches("http://").to_owned();
+    let body = format!("{{\"schema\":{}}}", serde_json::to_string(SCHEMA_STR).unwrap());
+    for subject in &["%2E%2E", "%2E", "."] {
+        let mut stream = TcpStream::connect(&addr[..]).unwrap();
+        write!(stream, "POST /subjects/{}/versions HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            subject, addr, body.len(), body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 422"), "{}: {}", subject, response);
+    }
+    assert!(!root.join("1.avsc").exists());
+    assert!(!dir.join("1.avsc").exists());
+    assert!(RegistryClient::new(&running.url()).unwrap().subjects().unwrap().is_empty());
+
+    drop(running);
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -110,7 +110,18 @@ pub struct UT<'a> {
     metadata: Option<smallvec::SmallVec<[(&'a [u8], &'a [u8]); 20]>>
 }
 
-#[derive(Deserialize,Debug)]
+#[derive(Serialize,Deserialize,Debug,PartialEq)]
+pub struct UTStr<'a> {
+    timestamp: Timestamp,
+    metric: &'a str,
+    value: Value,
+    #[serde(borrow)]
+    tags: Option<Vec<(&'a str, &'a str)>>,
+    #[serde(borrow)]
+    metadata: Option<Vec<(&'a str, &'a str)>>
+}
+
+#[derive(Serialize,Deserialize,Debug,PartialEq)]
 enum Timestamp {
     Long(i64),
     Int(i32),
@@ -118,7 +129,7 @@ enum Timestamp {
     Double(f64)
 }
 
-#[derive(Deserialize,Debug)]
+#[derive(Serialize,Deserialize,Debug,PartialEq)]
 enum Value {
     Long(i64),
     Int(i32),
@@ -143,12 +154,608 @@ fn run_through_test_data() {
     for test in tests {
         let buf = &test[..];
 
-        let mut de = avvy::AvroDeserializer{buf, schema: &schema, current_field_index: None  };
-        de.skip(5);
+        let mut de = avvy::AvroDeserializer::from_slice(&schema, buf);
+        assert_eq!(de.read_confluent_header().unwrap(), 618);
         UT::deserialize(&mut de).unwrap();
     }
 }
 
+#[test]
+fn serialize_reproduces_test_data() {
+    let tests = test_data();
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+
+    for test in tests {
+        let (schema_id, buf) = avvy::message::read_confluent_header(&test).unwrap();
+
+        let mut de = avvy::AvroDeserializer::from_slice(&schema, buf);
+        let ut = UTStr::deserialize(&mut de).unwrap();
+        assert!(de.buf.is_empty());
+
+        let encoded = avvy::to_vec(&schema, &ut).unwrap();
+        assert_eq!(&encoded[..], buf);
+        assert_eq!(avvy::message::ConfluentEncoder::new(&schema, schema_id).encode(&ut).unwrap(), test);
+
+        let mut de = avvy::AvroDeserializer::from_slice(&schema, &encoded[..]);
+        assert_eq!(UTStr::deserialize(&mut de).unwrap(), ut);
+    }
+}
+
+#[test]
+fn serialize_metadata_and_a_float_branch() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let ut = UTStr {
+        timestamp: Timestamp::Int(-7),
+        metric: "m",
+        value: Value::Float(1.5),
+        tags: None,
+        metadata: Some(vec![("k", "v"), ("", "")]),
+    };
+
+    let encoded = avvy::to_vec(&schema, &ut).unwrap();
+    assert_eq!(&encoded[..], &[2, 13, 2, 109, 4, 0, 0, 192, 63, 0, 2, 4, 2, 107, 2, 118, 0, 0, 0][..]);
+
+    let mut de = avvy::AvroDeserializer::from_slice(&schema, &encoded[..]);
+    assert_eq!(UTStr::deserialize(&mut de).unwrap(), ut);
+    assert!(de.buf.is_empty());
+}
+
+#[test]
+fn serialize_through_a_fixed_branch() {
+    use avvy::Value as V;
+
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let ut = UTStr {
+        timestamp: Timestamp::Int(-7),
+        metric: "m",
+        value: Value::Long16(0x1234),
+        tags: None,
+        metadata: None,
+    };
+
+    // uint16_t is a fixed of 2, the value goes in little endian
+    let encoded = avvy::to_vec(&schema, &ut).unwrap();
+    assert_eq!(&encoded[..], &[2, 13, 2, 109, 10, 0x34, 0x12, 0, 0][..]);
+    match avvy::DatumReader::new(&schema).from_slice(&encoded[..]).unwrap() {
+        V::Record(fields) => assert_eq!(fields[2].1, V::Union(5, Box::new(V::Fixed(vec![0x34, 0x12])))),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn serialize_rejects_values_of_the_wrong_type() {
+    #[derive(Serialize)]
+    enum Loose<'a> {
+        Long(&'a str),
+        Int(i64),
+        Float(f64),
+    }
+
+    #[derive(Serialize)]
+    struct LooseUT<'a> {
+        timestamp: Timestamp,
+        metric: &'a str,
+        value: Loose<'a>,
+        tags: Option<Vec<(&'a str, &'a str)>>,
+        metadata: Option<Vec<(&'a str, &'a str)>>,
+    }
+
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let loose = |value| LooseUT { timestamp: Timestamp::Int(1), metric: "m", value, tags: None, metadata: None };
+    let err = avvy::to_vec(&schema, &loose(Loose::Long("7"))).unwrap_err();
+    assert_eq!(err.reason, "field value is a long, can't write a str to it");
+    let err = avvy::to_vec(&schema, &loose(Loose::Float(0.5))).unwrap_err();
+    assert_eq!(err.reason, "field value is a float, can't write an f64 to it");
+    let err = avvy::to_vec(&schema, &loose(Loose::Int(1 << 40))).unwrap_err();
+    assert_eq!(err.reason, "1099511627776 doesn't fit the int of field value");
+}
+
+#[test]
+fn datum_writer_resolves_unions() {
+    use avvy::Value as V;
+
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let record = V::Record(vec![
+        ("timestamp".into(), V::Long(1532395932)),
+        ("metric".into(), V::String("m".into())),
+        ("value".into(), V::Double(0.25)),
+        ("tags".into(), V::Map(vec![("an-id".into(), V::String("1".into()))])),
+        ("metadata".into(), V::Null),
+    ]);
+
+    let encoded = avvy::DatumWriter::new(&schema).to_vec(&record).unwrap();
+    let mut de = avvy::AvroDeserializer::from_slice(&schema, &encoded[..]);
+    assert_eq!(UTStr::deserialize(&mut de).unwrap(), UTStr {
+        timestamp: Timestamp::Long(1532395932),
+        metric: "m",
+        value: Value::Double(0.25),
+        tags: Some(vec![("an-id", "1")]),
+        metadata: None,
+    });
+}
+
+#[test]
+fn datum_writer_reports_field_paths() {
+    use avvy::Value as V;
+
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let writer = avvy::DatumWriter::new(&schema);
+    let record = |timestamp: V, tags: V| V::Record(vec![
+        ("timestamp".into(), timestamp),
+        ("metric".into(), V::String("m".into())),
+        ("value".into(), V::Int(1)),
+        ("tags".into(), tags),
+        ("metadata".into(), V::Null),
+    ]);
+
+    let err = writer.to_vec(&record(V::Long(1), V::Map(vec![("k".into(), V::Long(2))]))).unwrap_err();
+    assert_eq!(err.reason, "ut.tags[\"k\"]: expected string, got long");
+
+    let err = writer.to_vec(&record(V::String("now".into()), V::Null)).unwrap_err();
+    assert!(err.reason.starts_with("ut.timestamp: no branch of [long, int, float, double, uint64_t, int64_t]"));
+
+    let err = writer.to_vec(&record(V::Fixed(vec![0; 8]), V::Null)).unwrap_err();
+    assert!(err.reason.contains("ambiguous"));
+
+    let mut out = Vec::new();
+    writer.write(&record(V::Union(4, Box::new(V::Fixed(vec![0; 8]))), V::Null), &mut out).unwrap();
+    assert_eq!(&out[..10], &[8, 0, 0, 0, 0, 0, 0, 0, 0, 2][..]);
+}
+
+#[test]
+fn stream_deserializer_walks_back_to_back_records() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let mut buf = Vec::new();
+    let mut offsets = Vec::new();
+    for test in test_data() {
+        offsets.push(buf.len());
+        buf.extend_from_slice(&test[5..]);
+    }
+
+    let records = avvy::StreamDeserializer::<UTStr>::new(&schema, &buf[..]).with_offsets()
+        .collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(records.iter().map(|record| record.0).collect::<Vec<_>>(), offsets);
+    for ((_, ut), test) in records.iter().zip(test_data()) {
+        assert_eq!(&avvy::to_vec(&schema, ut).unwrap()[..], &test[5..]);
+    }
+
+    let mut stream = avvy::StreamDeserializer::<UTStr>::new(&schema, &buf[..]);
+    assert_eq!(stream.by_ref().count(), offsets.len());
+    assert_eq!(stream.byte_offset(), buf.len());
+    assert!(stream.next().is_none());
+
+    buf.extend_from_slice(&[2, 13]);
+    let mut stream = avvy::StreamDeserializer::<UTStr>::new(&schema, &buf[..]);
+    for _ in 0..offsets.len() {
+        stream.next().unwrap().unwrap();
+    }
+    let err = stream.next().unwrap().unwrap_err();
+    assert!(err.reason.starts_with(&format!("2 bytes at offset {} aren't a record", buf.len() - 2)), "{}", err.reason);
+    assert_eq!(stream.byte_offset(), buf.len() - 2);
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn stream_deserializer_stops_on_records_that_take_no_bytes() {
+    #[derive(Deserialize,Debug)]
+    struct Empty {}
+
+    let schema = avvy::Schema::from_str(r#"{ "type": "record", "name": "Empty", "namespace": "test", "fields": [] }"#).unwrap();
+    let mut stream = avvy::StreamDeserializer::<Empty>::new(&schema, &[1, 2]);
+    let err = stream.next().unwrap().unwrap_err();
+    assert!(err.reason.ends_with("the record took no bytes"), "{}", err.reason);
+    assert_eq!(stream.byte_offset(), 0);
+    assert!(stream.next().is_none());
+}
+
+#[derive(Deserialize,Debug,PartialEq)]
+pub struct UTOwned {
+    timestamp: Timestamp,
+    metric: String,
+    value: Value,
+    tags: Option<Vec<(String, String)>>,
+    metadata: Option<Vec<(String, String)>>
+}
+
+// hands out a byte at a time, like a slow socket
+struct Trickle<'a>(&'a [u8]);
+
+impl<'a> std::io::Read for Trickle<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.0.is_empty() || buf.is_empty() {
+            return Ok(0)
+        }
+        buf[0] = self.0[0];
+        self.0 = &self.0[1..];
+        Ok(1)
+    }
+}
+
+#[test]
+fn read_deserializer_pulls_owned_records() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let mut buf = Vec::new();
+    for test in test_data() {
+        buf.extend_from_slice(&test[5..]);
+    }
+
+    let mut de = avvy::ReadDeserializer::new(&schema, Trickle(&buf[..]));
+    for test in test_data() {
+        let owned : UTOwned = de.next_record().unwrap().unwrap();
+        let mut slice = avvy::AvroDeserializer::from_slice(&schema, &test[5..]);
+        let borrowed = UTStr::deserialize(&mut slice).unwrap();
+        assert_eq!(owned.metric, borrowed.metric);
+        assert_eq!(owned.tags.map(|tags| tags.len()), borrowed.tags.map(|tags| tags.len()));
+    }
+    assert_eq!(de.byte_offset(), buf.len() as u64);
+    assert!(de.next_record::<UTOwned>().unwrap().is_none());
+
+    // nothing past the record is taken off the reader
+    let first = test_data().remove(0);
+    let mut reader = &buf[..];
+    let _ : UTOwned = avvy::from_reader(&schema, &mut reader).unwrap();
+    assert_eq!(reader.len(), buf.len() - (first.len() - 5));
+
+    let records = avvy::ReadDeserializer::new(&schema, &buf[..buf.len() - 3]).records::<UTOwned>().collect::<Vec<_>>();
+    assert_eq!(records.len(), test_data().len());
+    let err = records.last().unwrap().as_ref().unwrap_err();
+    assert!(err.reason.contains("reader ended, needed"), "{}", err.reason);
+}
+
+#[test]
+fn partial_decoder_waits_for_the_rest_of_a_record() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let decoder = avvy::PartialDecoder::new(&schema);
+
+    let first = test_data().remove(0);
+    let datum = &first[5..];
+    for cut in 0..datum.len() {
+        match decoder.decode::<UTStr>(&datum[..cut]).unwrap() {
+            avvy::Partial::Incomplete(needed) => assert!(needed >= 1 && cut + needed <= datum.len()),
+            complete => panic!("{} of {} bytes decoded to {:?}", cut, datum.len(), complete),
+        }
+    }
+    assert!(decoder.decode::<UTStr>(datum).unwrap().is_complete());
+
+    // chunks arriving off the network, with records straddling them
+    let mut wire = Vec::new();
+    for test in test_data() {
+        wire.extend_from_slice(&test[5..]);
+    }
+    let mut pending = Vec::new();
+    let mut metrics = Vec::new();
+    for chunk in wire.chunks(7) {
+        pending.extend_from_slice(chunk);
+        while let avvy::Partial::Complete(ut, len) = decoder.decode::<UTOwned>(&pending[..]).unwrap() {
+            metrics.push(ut.metric);
+            pending.drain(..len);
+        }
+    }
+    assert!(pending.is_empty());
+    assert_eq!(metrics.len(), test_data().len());
+
+    assert!(decoder.decode::<UTStr>(&[13, 0]).is_err());
+}
+
+// A block of 2^40 nulls, which take no bytes, then a string
+const NULLS_SCHEMA: &str = r#"{
+  "type": "record", "name": "Nulls", "namespace": "test",
+  "fields": [
+    { "name": "nulls", "type": { "type": "array", "items": "null" } },
+    { "name": "name", "type": "string" }
+  ]
+}"#;
+const NULLS_DATUM: [u8; 9] = [0x80, 0x80, 0x80, 0x80, 0x80, 0x40, 0x00, 0x02, b'a'];
+
+#[test]
+fn partial_decoder_counts_arrays_of_nulls() {
+    let schema = avvy::Schema::from_str(NULLS_SCHEMA).unwrap();
+    let decoder = avvy::PartialDecoder::new(&schema);
+    assert_eq!(decoder.record_len(&NULLS_DATUM).unwrap(), avvy::Partial::Complete((), NULLS_DATUM.len()));
+}
+
+#[test]
+fn event_reader_walks_a_datum() {
+    use avvy::Event as E;
+    use avvy::Value as V;
+
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let record = V::Record(vec![
+        ("timestamp".into(), V::Long(1532395932)),
+        ("metric".into(), V::String("m".into())),
+        ("value".into(), V::Union(4, Box::new(V::Fixed(vec![9])))),
+        ("tags".into(), V::Map(vec![("an-id".into(), V::String("1".into())), ("host".into(), V::String("h".into()))])),
+        ("metadata".into(), V::Map(vec![])),
+    ]);
+    let encoded = avvy::DatumWriter::new(&schema).to_vec(&record).unwrap();
+    let events = avvy::EventReader::from_slice(&schema, &encoded[..]).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(events, vec![
+        E::RecordStart("ut"),
+        E::Field("timestamp"), E::UnionBranch(0), E::Long(1532395932),
+        E::Field("metric"), E::Str("m"),
+        E::Field("value"), E::UnionBranch(4), E::Fixed(&[9]),
+        E::Field("tags"), E::UnionBranch(1), E::MapStart(2), E::Key("an-id"), E::Str("1"), E::Key("host"), E::Str("h"), E::End,
+        E::Field("metadata"), E::UnionBranch(1), E::MapStart(0), E::End,
+        E::End,
+    ]);
+
+    for test in test_data() {
+        let mut events = avvy::EventReader::from_slice(&schema, &test[5..]);
+        let mut depth = 0;
+        for event in events.by_ref() {
+            match event.unwrap() {
+                E::RecordStart(_) | E::ArrayStart(_) | E::MapStart(_) => depth += 1,
+                E::End => depth -= 1,
+                _ => {},
+            }
+        }
+        assert_eq!(depth, 0);
+        assert!(events.deserializer().buf.is_empty());
+    }
+
+    let mut events = avvy::EventReader::from_slice(&schema, &encoded[..4]);
+    assert!(events.by_ref().any(|event| event.is_err()));
+    assert!(events.next().is_none());
+}
+
+#[test]
+fn record_view_finds_fields_on_demand() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+
+    for test in test_data() {
+        let datum = &test[5..];
+        let ut = UTStr::deserialize(&mut avvy::AvroDeserializer::from_slice(&schema, datum)).unwrap();
+
+        let mut view = avvy::RecordView::new(&schema, datum);
+        let metric = view.field_named("metric").unwrap();
+        assert_eq!(metric.index(), 1);
+        assert_eq!(metric.branch().unwrap(), None);
+        assert_eq!(metric.as_str().unwrap(), Some(ut.metric));
+        assert_eq!(metric.as_long().unwrap(), None);
+
+        let tags = view.field(3).unwrap();
+        assert_eq!(tags.name(), "tags");
+        assert_eq!(tags.decode::<Option<Vec<(&str, &str)>>>().unwrap(), ut.tags);
+        assert_eq!(tags.is_null().unwrap(), ut.tags.is_none());
+
+        let value = view.field_named("value").unwrap();
+        match ut.value {
+            Value::Long(val) => assert_eq!(value.as_long().unwrap(), Some(val)),
+            Value::Double(val) => assert_eq!(value.as_double().unwrap(), Some(val)),
+            _ => {},
+        }
+        match avvy::DatumReader::new(&schema).from_slice(datum).unwrap() {
+            avvy::Value::Record(fields) => assert_eq!(value.value().unwrap(), fields[2].1),
+            record => panic!("{:?} isn't a record", record),
+        }
+
+        assert_eq!(view.record_len().unwrap(), datum.len());
+        assert_eq!(view.decode::<UTStr>().unwrap(), ut);
+    }
+
+    // cut off partway through the tags
+    let first = test_data().remove(0);
+    let mut view = avvy::RecordView::new(&schema, &first[5..]);
+    let cut = 5 + (0..3).map(|field| view.field(field).unwrap().bytes().len()).sum::<usize>() + 2;
+    let mut view = avvy::RecordView::new(&schema, &first[5..cut]);
+    assert!(view.field_named("metric").is_ok());
+    assert!(view.field_named("nope").unwrap_err().reason.contains("no field nope"));
+    assert!(view.field(5).is_err());
+    assert!(view.record_len().unwrap_err().reason.starts_with("ut.tags: unexpected end of buffer"));
+}
+
+#[derive(Deserialize,Debug,PartialEq)]
+pub struct MetricTags<'a> {
+    #[serde(borrow)]
+    tags: Option<Vec<(&'a str, &'a str)>>,
+    metric: &'a str,
+}
+
+#[test]
+fn decode_plan_matches_schema_lookups() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let plan = avvy::DecodePlan::new::<UTStr>(&schema).unwrap();
+    let partial = avvy::DecodePlan::new::<MetricTags>(&schema).unwrap();
+
+    for test in test_data() {
+        let datum = &test[5..];
+        let ut = UTStr::deserialize(&mut avvy::AvroDeserializer::from_slice(&schema, datum)).unwrap();
+
+        let mut de = avvy::AvroDeserializer::with_plan(&plan, datum);
+        assert_eq!(UTStr::deserialize(&mut de).unwrap(), ut);
+        assert!(de.buf.is_empty());
+
+        // fields the struct doesn't have are skipped, whatever order it has them in
+        let mut de = avvy::AvroDeserializer::with_plan(&partial, datum);
+        assert_eq!(MetricTags::deserialize(&mut de).unwrap(), MetricTags { tags: ut.tags.clone(), metric: ut.metric });
+        assert!(de.buf.is_empty());
+    }
+
+    let record = avvy::to_vec(&schema, &UTStr { timestamp: Timestamp::Int(1), metric: "m", value: Value::Int(2), tags: None, metadata: None }).unwrap();
+    let mut bad = record.clone();
+    bad[0] = 12;
+    assert!(UTStr::deserialize(&mut avvy::AvroDeserializer::with_plan(&plan, &bad[..])).unwrap_err().reason.contains("union branch 6 is out of scope"));
+    let mut bad = record.clone();
+    let tags = bad.len() - 2;
+    bad[tags] = 4;
+    assert!(UTStr::deserialize(&mut avvy::AvroDeserializer::with_plan(&plan, &bad[..])).unwrap_err().reason.contains("out of scope"));
+
+    assert!(avvy::DecodePlan::new::<String>(&schema).is_err());
+}
+
+#[test]
+fn datum_reader_limits_arrays_of_nulls() {
+    use avvy::Value as V;
+
+    let schema = avvy::Schema::from_str(NULLS_SCHEMA).unwrap();
+    let reader = avvy::DatumReader::new(&schema);
+    assert!(reader.from_slice(&NULLS_DATUM).unwrap_err().reason.contains("over the limit"));
+
+    assert_eq!(reader.from_slice(&[0x06, 0x00, 0x02, b'a']).unwrap(), V::Record(vec![
+        ("nulls".into(), V::Array(vec![V::Null; 3])),
+        ("name".into(), V::String("a".into())),
+    ]));
+}
+
+#[test]
+fn decode_plan_skips_arrays_of_nulls_by_count() {
+    #[derive(Deserialize,Debug,PartialEq)]
+    struct Name {
+        name: String,
+    }
+
+    let schema = avvy::Schema::from_str(NULLS_SCHEMA).unwrap();
+    let plan = avvy::DecodePlan::new::<Name>(&schema).unwrap();
+    let mut de = avvy::AvroDeserializer::with_plan(&plan, &NULLS_DATUM);
+    assert_eq!(Name::deserialize(&mut de).unwrap(), Name { name: "a".into() });
+    assert!(de.buf.is_empty());
+}
+
+#[test]
+fn column_batch_lays_records_out_by_field() {
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let data = test_data();
+    let records = data.iter().map(|test| UTStr::deserialize(&mut avvy::AvroDeserializer::from_slice(&schema, &test[5..])).unwrap()).collect::<Vec<_>>();
+    let stream = data.iter().flat_map(|test| test[5..].to_vec()).collect::<Vec<u8>>();
+
+    let mut batch = avvy::columnar::ColumnBatch::new(&schema).unwrap();
+    let half = records.len() / 2;
+    let used = batch.extend_from_slice(&stream[..], half).unwrap();
+    assert_eq!(batch.len(), half);
+    check_columns(&batch, &records[..half]);
+
+    // the same builders again for the rest
+    batch.clear();
+    assert!(batch.columns().iter().all(|column| column.is_empty()));
+    assert_eq!(batch.extend_from_slice(&stream[used..], usize::MAX).unwrap(), stream.len() - used);
+    check_columns(&batch, &records[half..]);
+
+    // a record cut short is taken back out of every column
+    batch.clear();
+    assert!(batch.extend_from_slice(&stream[..used - 1], usize::MAX).unwrap_err().reason.starts_with(&format!("record {}, ", half - 1)));
+    assert_eq!(batch.len(), half - 1);
+    assert!(batch.columns().iter().all(|column| column.len() == half - 1));
+    check_columns(&batch, &records[..half - 1]);
+}
+
+#[test]
+fn column_batch_counts_arrays_of_nulls() {
+    use avvy::columnar::Column;
+
+    let schema = avvy::Schema::from_str(NULLS_SCHEMA).unwrap();
+    let mut batch = avvy::columnar::ColumnBatch::new(&schema).unwrap();
+    assert!(batch.extend_from_slice(&NULLS_DATUM, 1).unwrap_err().reason.contains("32 bit offsets"));
+
+    assert_eq!(batch.extend_from_slice(&[0x06, 0x00, 0x02, b'a'], 1).unwrap(), 4);
+    match *batch.column(0).unwrap() {
+        Column::Array { ref offsets, ref items } => {
+            assert_eq!(offsets[..], [0, 3]);
+            assert_eq!(items.len(), 3);
+        },
+        ref column => panic!("nulls is a {:?}", column),
+    }
+}
+
+fn check_columns(batch: &avvy::columnar::ColumnBatch, records: &[UTStr]) {
+    use avvy::columnar::Column;
+
+    match *batch.column(0).unwrap() {
+        Column::Union(ref timestamps) => for (index, record) in records.iter().enumerate() {
+            let (id, offset) = (timestamps.type_ids[index], timestamps.offsets[index] as usize);
+            match (&record.timestamp, &timestamps.children[id as usize]) {
+                (&Timestamp::Long(val), Column::Long(longs)) => assert_eq!(longs[offset], val),
+                (&Timestamp::Int(val), Column::Int(ints)) => assert_eq!(ints[offset], val),
+                (timestamp, column) => panic!("{:?} landed in {:?}", timestamp, column),
+            }
+        },
+        ref column => panic!("timestamp is a {:?}", column),
+    }
+
+    match *batch.column_named("metric").unwrap() {
+        Column::String(ref metrics) => for (index, record) in records.iter().enumerate() {
+            assert_eq!(metrics.get(index), Some(record.metric.as_bytes()));
+        },
+        ref column => panic!("metric is a {:?}", column),
+    }
+
+    match *batch.column_named("tags").unwrap() {
+        Column::Nullable { ref validity, ref values, .. } => match **values {
+            Column::Map { ref offsets, ref keys, ref values } => for (index, record) in records.iter().enumerate() {
+                assert_eq!(validity.get(index), record.tags.is_some());
+                let tags = record.tags.clone().unwrap_or_default();
+                assert_eq!((offsets[index + 1] - offsets[index]) as usize, tags.len());
+                for (entry, (key, value)) in (offsets[index] as usize..).zip(tags) {
+                    assert_eq!(keys.get(entry), Some(key.as_bytes()));
+                    match **values {
+                        Column::String(ref values) => assert_eq!(values.get(entry), Some(value.as_bytes())),
+                        ref column => panic!("tag values are a {:?}", column),
+                    }
+                }
+            },
+            ref column => panic!("tags are a {:?}", column),
+        },
+        ref column => panic!("tags is a {:?}", column),
+    }
+}
+
+#[test]
+fn json_encoding_wraps_unions_and_round_trips() {
+    use avvy::Value as V;
+
+    let schema = avvy::Schema::from_str(SCHEMA_STR).unwrap();
+    let encoder = avvy::JsonEncoder::new(&schema);
+    let decoder = avvy::JsonDecoder::new(&schema);
+    for test in test_data() {
+        let (_, datum) = avvy::message::read_confluent_header(&test).unwrap();
+        let json = encoder.encode_datum(datum).unwrap();
+        let again = decoder.decode_datum(&json).unwrap();
+        assert_eq!(encoder.encode_datum(&again[..]).unwrap(), json);
+    }
+
+    let ut = UTStr {
+        timestamp: Timestamp::Double(f64::NAN),
+        metric: "m",
+        value: Value::Int(7),
+        tags: None,
+        metadata: Some(vec![("k", "v")]),
+    };
+    let json = avvy::to_avro_json(&schema, &ut).unwrap();
+    assert_eq!(json, r#"{"metadata":{"map":{"k":"v"}},"metric":"m","tags":null,"timestamp":{"double":"NaN"},"value":{"int":7}}"#);
+
+    let owned = avvy::from_avro_json::<UTOwned>(&schema, &json).unwrap();
+    match owned {
+        UTOwned { timestamp: Timestamp::Double(nan), value: Value::Int(7), tags: None, ref metadata, .. } => {
+            assert!(nan.is_nan());
+            assert_eq!(metadata, &Some(vec![("k".to_string(), "v".to_string())]));
+        },
+        other => panic!("decoded {:?}", other),
+    }
+    assert_eq!(owned.metric, "m");
+
+    let err = decoder.from_str(&json.replace(r#"{"double":"NaN"}"#, "1")).unwrap_err();
+    assert!(err.reason.starts_with("ut.timestamp: expected null or"), "{}", err.reason);
+
+    // fixed branches are named in full, and read with or without the namespace
+    let fixed = r#"{"vnoportal.uint16_t":"\u0001ÿ"}"#;
+    let record = V::Record(vec![
+        ("timestamp".into(), V::Long(1)),
+        ("metric".into(), V::String("m".into())),
+        ("value".into(), V::Union(5, Box::new(V::Fixed(vec![1, 0xff])))),
+        ("tags".into(), V::Null),
+        ("metadata".into(), V::Null),
+    ]);
+    let encoded = encoder.encode(&record).unwrap();
+    assert_eq!(encoded["value"].to_string(), fixed);
+    let short = encoded.to_string().replace("vnoportal.uint16_t", "uint16_t");
+    assert_eq!(encoder.encode(&decoder.from_str(&short).unwrap()).unwrap(), encoded);
+    let err = decoder.from_str(&short.replace(r#""\u0001ÿ""#, r#""\u0001""#)).unwrap_err();
+    assert!(err.reason.starts_with("ut.value: \"\\u{1}\" is the wrong size"), "{}", err.reason);
+    let err = decoder.from_str(&json.replace(r#""k":"v""#, r#""k":1"#)).unwrap_err();
+    assert_eq!(err.reason, "ut.metadata[\"k\"]: expected string, got 1");
+    assert_eq!(decoder.from_str("{}").unwrap_err().reason, "ut.timestamp: missing field");
+    assert!(encoder.encode(&V::Record(vec![])).is_err());
+}
+
 fn test_data() -> Vec<Vec<u8>> {
     vec![
         vec![0, 0, 0, 2, 106, 0, 184, 134, 180, 181, 11, 84, 118, 105, 97, 115, 97, 116, 45, 97, 98, 45, 118, 110, 111, 45, 112, 109, 46, 117, 116, 46, 114, 108, 45, 115, 121, 109, 98, 111, 108, 45, 116, 114, 97, 102, 102, 105, 99, 45, 114, 97, 116, 101, 6, 0, 0, 0, 0, 0, 0, 0, 0, 2, 18, 10, 97, 110, 45, 105, 100, 2, 49, 16, 115, 109, 97, 99, 100, 45, 105, 100, 6, 49, 52, 55, 24, 115, 97, 116, 101, 108, 108, 105, 116, 101, 45, 105, 100, 2, 52, 16, 109, 97, 99, 45, 97, 100, 100, 114, 24, 48, 48, 97, 48, 98, 99, 56, 99, 56, 52, 49, 49, 10, 115, 116, 97, 116, 101, 14, 114, 97, 110, 103, 105, 110, 103, 12, 118, 110, 111, 45, 105, 100, 0, 14, 98, 101, 97, 109, 45, 105, 100, 10, 49, 49, 48, 52, 53, 22, 99, 97, 114, 114, 105, 101, 114, 100, 45, 105, 100, 2, 55, 44, 115, 101, 114, 118, 105, 110, 103, 45, 115, 109, 97, 99, 45, 104, 111, 115, 116, 45, 110, 97, 109, 101, 38, 115, 109, 97, 99, 45, 99, 104, 105, 48, 55, 45, 110, 49, 45, 97, 108, 112, 104, 97, 0, 0],